@@ -2,27 +2,47 @@
 //!
 //! 这里承载应用的窗口、托盘菜单、命令绑定等逻辑。
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
 use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
 use tauri::tray::TrayIconBuilder;
 use tauri::{AppHandle, Manager, Wry};
 
 use crate::{
-	app_settings, format, litellm, proxy_config, raw_format, rightcodes, rightcodes_api,
-	rightcodes_token_store, time_range, usage,
+	app_settings, config_dir, format, litellm, proxy_config, raw_format, refresh, rightcodes,
+	rightcodes_api, rightcodes_token_store, status, time_parse, time_range, usage,
 };
 
 const REFRESH_INTERVAL_SECS: u64 = 30;
+/// 监听日志目录时定时刷新只作兜底（rc 额度、跨天这类不写日志的变化也靠它）。
+const WATCHED_REFRESH_INTERVAL_SECS: u64 = 120;
 type Runtime = Wry;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 enum Period {
 	Today,
+	Yesterday,
 	Week,
+	Last7d,
 	Month,
+	Last30d,
 	Year,
+	/// 本次启动（或上次“重置会话”）以来。
+	Session,
+}
+
+impl Period {
+	/// 全局快捷键的循环顺序：今天 → 本周 → 本月 → 本年 → 今天；不在循环里的周期从“今天”开始。
+	fn next_in_cycle(self) -> Self {
+		match self {
+			Period::Today => Period::Week,
+			Period::Week => Period::Month,
+			Period::Month => Period::Year,
+			_ => Period::Today,
+		}
+	}
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -32,7 +52,9 @@ enum Source {
 	Both,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// 托盘菜单里的周期/来源选择，保存在 `~/.tokbar/tray.json`，重启后恢复。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 struct Settings {
 	period: Period,
 	source: Source,
@@ -47,28 +69,62 @@ impl Default for Settings {
 	}
 }
 
+/// 读取上次保存的托盘设置（`~/.tokbar/tray.json`）；没有或解析失败时用默认值。
+fn load_tray_settings() -> Settings {
+	app_settings::tray_state_path()
+		.and_then(|path| std::fs::read_to_string(path).ok())
+		.and_then(|body| serde_json::from_str(&body).ok())
+		.unwrap_or_default()
+}
+
+/// 保存托盘设置；失败时（如 HOME 未设置）只是下次启动回到默认值，不打扰用户。
+fn save_tray_settings(settings: Settings) {
+	let Some(path) = app_settings::tray_state_path() else {
+		return;
+	};
+	let Ok(body) = serde_json::to_string_pretty(&settings) else {
+		return;
+	};
+	if config_dir::ensure_parent_dir(&path).is_ok() {
+		let _ = std::fs::write(path, body);
+	}
+}
+
 #[derive(Clone)]
 struct AppState {
 	settings: Arc<Mutex<Settings>>,
 	prefs: Arc<Mutex<app_settings::AppSettings>>,
 	menu: MenuHandles,
 	last_ui: Arc<Mutex<LastUiState>>,
+	/// `Period::Session` 的起点：启动时记录，菜单“重置会话”时改为当前时刻。
+	session_started_at: Arc<Mutex<DateTime<Local>>>,
 }
 
 #[derive(Clone)]
 struct MenuHandles {
+	stats_both_full: MenuItem<Runtime>,
 	stats_cx_full: MenuItem<Runtime>,
 	stats_cc_full: MenuItem<Runtime>,
+	models_menu: Submenu<Runtime>,
+	month_compare: MenuItem<Runtime>,
+	totals_combined_all: MenuItem<Runtime>,
 	totals_cx_all: MenuItem<Runtime>,
 	totals_cc_all: MenuItem<Runtime>,
 	rightcodes_status: MenuItem<Runtime>,
+	rightcodes_retry: MenuItem<Runtime>,
+	rc_in_title: CheckMenuItem<Runtime>,
 	dock_icon: CheckMenuItem<Runtime>,
 	autostart: CheckMenuItem<Runtime>,
 	pricing_status: MenuItem<Runtime>,
+	period_shortcut: CheckMenuItem<Runtime>,
 	period_today: CheckMenuItem<Runtime>,
+	period_yesterday: CheckMenuItem<Runtime>,
 	period_week: CheckMenuItem<Runtime>,
+	period_last7d: CheckMenuItem<Runtime>,
 	period_month: CheckMenuItem<Runtime>,
+	period_last30d: CheckMenuItem<Runtime>,
 	period_year: CheckMenuItem<Runtime>,
+	period_session: CheckMenuItem<Runtime>,
 	source_cx: CheckMenuItem<Runtime>,
 	source_cc: CheckMenuItem<Runtime>,
 	source_both: CheckMenuItem<Runtime>,
@@ -78,12 +134,43 @@ struct MenuHandles {
 struct LastUiState {
 	title: Option<String>,
 	tooltip: Option<String>,
+	stats_both_full: Option<String>,
 	stats_cx_full: Option<String>,
 	stats_cc_full: Option<String>,
+	model_rows: Option<Vec<String>>,
+	month_compare: Option<String>,
+	totals_combined_all: Option<String>,
 	totals_cx_all: Option<String>,
 	totals_cc_all: Option<String>,
 	pricing_status: Option<String>,
 	rightcodes_status: Option<String>,
+	/// 最近一次刷新的状态快照（仅在开启状态日志时生成，由刷新循环取走落盘）。
+	status: Option<status::StatusSnapshot>,
+	/// 每日花费提醒最近一次触发的本地日期（同一天只提醒一次）。
+	cost_alert_fired_on: Option<NaiveDate>,
+}
+
+/// 取设置类的锁（`Settings`/`AppSettings`/rc 退避）：里面都是整体读写的小数据，
+/// 某个线程持锁时 panic 也不会留下半更新的状态，直接沿用，避免之后每次刷新都跟着 panic。
+fn lock_recover<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+	mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// 取 UI 缓存锁；中毒时（上次刷新渲染到一半 panic）清空已渲染的文案缓存，
+/// 让下一次刷新把标题与菜单全部重设一遍。当天的花费提醒记录保留，避免重复通知。
+fn lock_last_ui(last_ui: &Mutex<LastUiState>) -> MutexGuard<'_, LastUiState> {
+	match last_ui.lock() {
+		Ok(guard) => guard,
+		Err(poisoned) => {
+			last_ui.clear_poison();
+			let mut guard = poisoned.into_inner();
+			*guard = LastUiState {
+				cost_alert_fired_on: guard.cost_alert_fired_on,
+				..LastUiState::default()
+			};
+			guard
+		}
+	}
 }
 
 fn load_tray_icon_image() -> Option<tauri::image::Image<'static>> {
@@ -115,42 +202,65 @@ fn apply_dock_icon_preference(app: &AppHandle, show_dock_icon: bool) {
 	}
 }
 
-fn range_for_period(period: Period) -> time_range::DateRange {
+/// 命令里扫描日志用的设置：取内存里的设置（还没有 AppState 时读一次 settings.json）。
+fn scan_settings(app: &AppHandle) -> usage::ScanSettings {
+	app.try_state::<AppState>()
+		.map(|state| lock_recover(&state.prefs).scan_settings())
+		.unwrap_or_else(usage::ScanSettings::from_settings)
+}
+
+fn range_for_period(app: &AppHandle, period: Period) -> time_range::DateRange {
 	match period {
 		Period::Today => time_range::range_today(),
+		Period::Yesterday => time_range::range_yesterday(),
 		Period::Week => time_range::range_week_monday(),
+		Period::Last7d => time_range::range_last_n_days(7),
 		Period::Month => time_range::range_month(),
+		Period::Last30d => time_range::range_last_n_days(30),
 		Period::Year => time_range::range_year(),
+		Period::Session => {
+			let started_at = app
+				.try_state::<AppState>()
+				.map(|state| *lock_recover(&state.session_started_at))
+				.unwrap_or_else(Local::now);
+			time_range::range_session(&started_at)
+		}
 	}
 }
 
-fn compute_title(_app: &AppHandle, settings: Settings) -> String {
-	let range = range_for_period(settings.period);
-	let period = range.label;
+fn compute_title(
+	app: &AppHandle,
+	settings: Settings,
+	labels: raw_format::SourceLabels<'_>,
+	style: &format::TrayStyle,
+	scan: &usage::ScanSettings,
+) -> String {
+	let range = range_for_period(app, settings.period);
+	let period = range.label.as_str();
 
 	let show_cost = false;
 	let dataset = std::collections::HashMap::new();
 
-	let cx = usage::load_cx_totals_with_pricing(&range, &dataset);
-	let cc_result = usage::load_cc_totals_with_pricing(&range, &dataset);
+	let cx = usage::load_cx_totals_with_pricing(&range, &dataset, scan);
+	let cc_result = usage::load_cc_totals_with_pricing(&range, &dataset, scan);
 	let cc_available = cc_result.is_ok();
 
 	match settings.source {
-		Source::Cx => format::format_single_title(period, "cx", cx, show_cost),
+		Source::Cx => format::format_single_title(period, labels.cx, cx, show_cost, style),
 		Source::Cc => match cc_result {
-			Ok(totals) => format::format_single_title(period, "cc", totals, show_cost),
+			Ok(totals) => format::format_single_title(period, labels.cc, totals, show_cost, style),
 			// 本机没有 Claude Code 日志目录时，不展示“0”，给出明确提示。
-			Err(_) => format!("{period} cc N/A"),
+			Err(_) => format!("{period} {} N/A", labels.cc),
 		},
 		Source::Both => {
 			// 当本机没有 cc 数据来源时（通常是未安装 Claude Code / 无日志目录），
 			// “Both” 也只展示 cx，避免出现 “cc 0” 的误导。
 			if !cc_available {
-				return format::format_single_title(period, "cx", cx, show_cost);
+				return format::format_single_title(period, labels.cx, cx, show_cost, style);
 			}
 
 			let cc = cc_result.unwrap_or_default();
-			format::format_both_title_one_line(period, cx, cc, show_cost)
+			format::format_both_title_one_line(period, labels, cx, cc, show_cost, style)
 		}
 	}
 }
@@ -160,10 +270,22 @@ fn build_menu(
 	settings: Settings,
 	prefs: &app_settings::AppSettings,
 ) -> tauri::Result<(Menu<Runtime>, MenuHandles)> {
+	let stats_both_full =
+		MenuItem::with_id(app, "stats.both_full", "正在加载…", false, None::<&str>)?;
 	let stats_cx_full =
 		MenuItem::with_id(app, "stats.cx_full", "正在加载 cx…", false, None::<&str>)?;
 	let stats_cc_full =
 		MenuItem::with_id(app, "stats.cc_full", "正在加载 cc…", false, None::<&str>)?;
+	let models_menu = Submenu::with_id(app, "models", "按模型", true)?;
+	let month_compare =
+		MenuItem::with_id(app, "stats.month_compare", "本月 vs 上月：加载中…", false, None::<&str>)?;
+	let totals_combined_all = MenuItem::with_id(
+		app,
+		"totals.combined_all",
+		"全部 cx+cc：加载中…",
+		false,
+		None::<&str>,
+	)?;
 	let totals_cx_all =
 		MenuItem::with_id(app, "totals.cx_all", "全部 cx：加载中…", false, None::<&str>)?;
 	let totals_cc_all =
@@ -185,11 +307,33 @@ fn build_menu(
 		None::<&str>,
 	)?;
 	let pricing_status = MenuItem::with_id(app, "pricing.status", "模型价格：检查中…", true, None::<&str>)?;
+	let pricing_refresh =
+		MenuItem::with_id(app, "pricing.refresh", "立即刷新价格", true, None::<&str>)?;
 	let proxy_open = MenuItem::with_id(app, "proxy.open", "代理设置…", true, None::<&str>)?;
+	let cost_alert_open =
+		MenuItem::with_id(app, "cost_alert.open", "每日花费提醒…", true, None::<&str>)?;
 	let rightcodes_status =
 		MenuItem::with_id(app, "rightcodes.status", "rc：未登录（点击登录）", false, None::<&str>)?;
+	// 只在最近一次 rc 请求失败时可点。
+	let rightcodes_retry =
+		MenuItem::with_id(app, "rightcodes.retry", "rc：重试", false, None::<&str>)?;
 	let rightcodes_login =
 		MenuItem::with_id(app, "rightcodes.login", "Right.codes 登录…", true, None::<&str>)?;
+	let rightcodes_paste_token = MenuItem::with_id(
+		app,
+		"rightcodes.paste_token",
+		"粘贴 Right.codes Token",
+		true,
+		None::<&str>,
+	)?;
+	let rc_in_title = CheckMenuItem::with_id(
+		app,
+		"rightcodes.in_title",
+		"状态栏显示 rc 额度",
+		true,
+		prefs.rc_in_title,
+		None::<&str>,
+	)?;
 
 	let period_today = CheckMenuItem::with_id(
 		app,
@@ -199,6 +343,14 @@ fn build_menu(
 		settings.period == Period::Today,
 		None::<&str>,
 	)?;
+	let period_yesterday = CheckMenuItem::with_id(
+		app,
+		"period.yesterday",
+		"昨天",
+		true,
+		settings.period == Period::Yesterday,
+		None::<&str>,
+	)?;
 	let period_week = CheckMenuItem::with_id(
 		app,
 		"period.week",
@@ -207,6 +359,14 @@ fn build_menu(
 		settings.period == Period::Week,
 		None::<&str>,
 	)?;
+	let period_last7d = CheckMenuItem::with_id(
+		app,
+		"period.last7d",
+		"最近 7 天",
+		true,
+		settings.period == Period::Last7d,
+		None::<&str>,
+	)?;
 	let period_month = CheckMenuItem::with_id(
 		app,
 		"period.month",
@@ -215,6 +375,14 @@ fn build_menu(
 		settings.period == Period::Month,
 		None::<&str>,
 	)?;
+	let period_last30d = CheckMenuItem::with_id(
+		app,
+		"period.last30d",
+		"最近 30 天",
+		true,
+		settings.period == Period::Last30d,
+		None::<&str>,
+	)?;
 	let period_year = CheckMenuItem::with_id(
 		app,
 		"period.year",
@@ -223,6 +391,24 @@ fn build_menu(
 		settings.period == Period::Year,
 		None::<&str>,
 	)?;
+	let period_session = CheckMenuItem::with_id(
+		app,
+		"period.session",
+		"本次启动以来",
+		true,
+		settings.period == Period::Session,
+		None::<&str>,
+	)?;
+	let period_session_reset =
+		MenuItem::with_id(app, "period.session_reset", "重置会话起点", true, None::<&str>)?;
+	let period_shortcut = CheckMenuItem::with_id(
+		app,
+		"period.shortcut",
+		period_shortcut_text(prefs.period_shortcut.as_deref(), None),
+		true,
+		prefs.period_shortcut.is_some(),
+		None::<&str>,
+	)?;
 
 	let source_cx = CheckMenuItem::with_id(
 		app,
@@ -254,7 +440,19 @@ fn build_menu(
 		"period",
 		"统计周期",
 		true,
-		&[&period_today, &period_week, &period_month, &period_year],
+		&[
+			&period_today,
+			&period_yesterday,
+			&period_week,
+			&period_last7d,
+			&period_month,
+			&period_last30d,
+			&period_year,
+			&period_session,
+			&PredefinedMenuItem::separator(app)?,
+			&period_session_reset,
+			&period_shortcut,
+		],
 	)?;
 	let source_menu =
 		Submenu::with_id_and_items(app, "source", "数据来源", true, &[&source_cx, &source_cc, &source_both])?;
@@ -262,18 +460,28 @@ fn build_menu(
 	let menu = Menu::with_items(
 		app,
 		&[
+			&stats_both_full,
 			&stats_cx_full,
 			&stats_cc_full,
+			&models_menu,
+			&month_compare,
 			&PredefinedMenuItem::separator(app)?,
+			&totals_combined_all,
 			&totals_cx_all,
 			&totals_cc_all,
+			&MenuItem::with_id(app, "totals.recompute", "重新计算总计", true, None::<&str>)?,
 			&PredefinedMenuItem::separator(app)?,
 			&dock_icon,
 			&autostart,
 			&pricing_status,
+			&pricing_refresh,
 			&proxy_open,
+			&cost_alert_open,
 			&rightcodes_status,
+			&rightcodes_retry,
 			&rightcodes_login,
+			&rightcodes_paste_token,
+			&rc_in_title,
 			&PredefinedMenuItem::separator(app)?,
 			&MenuItem::with_id(app, "refresh", "立即刷新", true, None::<&str>)?,
 			&period_menu,
@@ -286,18 +494,29 @@ fn build_menu(
 	Ok((
 		menu,
 		MenuHandles {
+			stats_both_full,
 			stats_cx_full,
 			stats_cc_full,
+			models_menu,
+			month_compare,
+			totals_combined_all,
 			totals_cx_all,
 			totals_cc_all,
 			rightcodes_status,
+			rightcodes_retry,
+			rc_in_title,
 			dock_icon,
 			autostart,
 			pricing_status,
+			period_shortcut,
 			period_today,
+			period_yesterday,
 			period_week,
+			period_last7d,
 			period_month,
+			period_last30d,
 			period_year,
+			period_session,
 			source_cx,
 			source_cc,
 			source_both,
@@ -309,69 +528,192 @@ fn sync_menu_checks(menu: &MenuHandles, settings: Settings) {
 	let _ = menu
 		.period_today
 		.set_checked(settings.period == Period::Today);
+	let _ = menu
+		.period_yesterday
+		.set_checked(settings.period == Period::Yesterday);
 	let _ = menu.period_week.set_checked(settings.period == Period::Week);
+	let _ = menu
+		.period_last7d
+		.set_checked(settings.period == Period::Last7d);
 	let _ = menu
 		.period_month
 		.set_checked(settings.period == Period::Month);
+	let _ = menu
+		.period_last30d
+		.set_checked(settings.period == Period::Last30d);
 	let _ = menu.period_year.set_checked(settings.period == Period::Year);
+	let _ = menu
+		.period_session
+		.set_checked(settings.period == Period::Session);
 
 	let _ = menu.source_cx.set_checked(settings.source == Source::Cx);
 	let _ = menu.source_cc.set_checked(settings.source == Source::Cc);
 	let _ = menu.source_both.set_checked(settings.source == Source::Both);
 }
 
+/// 菜单里快捷键开关的文案；`error` 为注册失败的原因。
+fn period_shortcut_text(shortcut: Option<&str>, error: Option<&str>) -> String {
+	match (shortcut, error) {
+		(None, _) => "快捷键切换周期：未设置".to_string(),
+		(Some(shortcut), None) => format!("快捷键切换周期：{shortcut}"),
+		(Some(_), Some(error)) => error.to_string(),
+	}
+}
+
+/// 重新注册切换周期的全局快捷键（先清掉旧的）；`shortcut` 为 None 时只清除。
+fn register_period_shortcut(app: &AppHandle, shortcut: Option<&str>) -> Result<(), String> {
+	use tauri_plugin_global_shortcut::GlobalShortcutExt as _;
+
+	let manager = app.global_shortcut();
+	manager.unregister_all().map_err(|e| e.to_string())?;
+	let Some(shortcut) = shortcut.map(str::trim).filter(|s| !s.is_empty()) else {
+		return Ok(());
+	};
+	manager
+		.register(shortcut)
+		.map_err(|e| format!("快捷键 {shortcut} 注册失败：{e}"))
+}
+
+/// 按设置注册快捷键，并把结果（或失败原因）同步到菜单。
+fn apply_period_shortcut(app: &AppHandle, menu: &MenuHandles, shortcut: Option<&str>) {
+	let error = register_period_shortcut(app, shortcut).err();
+	let _ = menu
+		.period_shortcut
+		.set_text(period_shortcut_text(shortcut, error.as_deref()));
+	let _ = menu.period_shortcut.set_checked(shortcut.is_some() && error.is_none());
+}
+
+/// 全局快捷键：把统计周期切到循环里的下一个，保存并刷新托盘。
+fn cycle_period(app: &AppHandle) {
+	let Some(state) = app.try_state::<AppState>() else {
+		return;
+	};
+	let updated = {
+		let mut settings = lock_recover(&state.settings);
+		settings.period = settings.period.next_in_cycle();
+		*settings
+	};
+	save_tray_settings(updated);
+	sync_menu_checks(&state.menu, updated);
+	let app = app.clone();
+	std::thread::spawn(move || update_tray_title(&app, updated));
+}
+
+/// 托盘刷新的单飞闸门：菜单点击、定时刷新与日志监听可能同时触发，合并成一次尾随刷新。
+static TRAY_REFRESH: refresh::SingleFlight<Settings> = refresh::SingleFlight::new();
+
+/// 请求刷新托盘：已有刷新在跑时只记下最新的设置，由正在跑的那次结束后接着刷新一轮。
 fn update_tray_title(app: &AppHandle, settings: Settings) {
+	TRAY_REFRESH.run(settings, |settings| refresh_tray_title(app, settings));
+}
+
+fn refresh_tray_title(app: &AppHandle, settings: Settings) {
 	if let Some(tray) = app.tray_by_id("tokbar-tray") {
 		let state = app.try_state::<AppState>();
+		let prefs = state
+			.as_ref()
+			.map(|s| lock_recover(&s.prefs).clone())
+			.unwrap_or_default();
 		let mut settings = settings;
-		let range = range_for_period(settings.period);
-		let period = range.label;
+		usage::apply_day_anchor_from_settings();
+		let scan = prefs.scan_settings();
+		let cost = prefs.cost_format();
+		let tray_style = prefs.tray_style();
+		let range = range_for_period(app, settings.period);
+		let period = range.label.as_str();
 		let pricing = litellm::get_pricing_context();
 		let show_cost = pricing.available;
 		let dataset = &pricing.dataset;
 
-		let cx = usage::load_cx_totals_with_pricing(&range, dataset);
-		let cc_result = usage::load_cc_totals_with_pricing(&range, dataset);
+		// 先按模型拆分再求和：菜单的“按模型”与总计共用一次扫描。
+		let cx_breakdown = usage::load_cx_breakdown_with_pricing(&range, dataset, &scan);
+		let cx = usage::sum_breakdown(&cx_breakdown);
+		let cc_breakdown_result = usage::load_cc_breakdown_with_pricing(&range, dataset, &scan);
+		let cc_result = cc_breakdown_result.as_ref().map(usage::sum_breakdown);
 		let cc_available = cc_result.is_ok();
 		let cc_for_both = cc_result.as_ref().copied().unwrap_or_default();
 		let all_label = "All";
 		let show_all_cost = pricing.available;
-		let cx_all = usage::load_cx_totals_all_time_cached_with_pricing(dataset);
-		let cc_all_result = usage::load_cc_totals_all_time_cached_with_pricing(dataset);
+		let cx_all = usage::load_cx_totals_all_time_cached_with_pricing(dataset, &scan);
+		let cc_all_result = usage::load_cc_totals_all_time_cached_with_pricing(dataset, &scan);
 
-		// 当本机没有 cc 数据来源时，强制把 source 降级为 Cx（即使用户选了 Both）。
-		// 这样避免展示误导性的 “cc 0”，并让菜单勾选状态保持一致。
+		// 当本机没有 cc 数据来源时，本次展示把 source 降级为 Cx（即使用户选了 Both）。
+		// 这样避免展示误导性的 “cc 0”，并让菜单勾选状态保持一致；
+		// 用户的选择（`state.settings` 与 tray.json）保持不变，cc 出现后自动恢复。
 		if !cc_available && settings.source != Source::Cx {
 			settings.source = Source::Cx;
 			if let Some(state) = state.as_ref() {
-				if let Ok(mut guard) = state.settings.lock() {
-					guard.source = Source::Cx;
-				}
 				sync_menu_checks(&state.menu, settings);
 			}
 		}
 
+		let labels = prefs.source_labels();
 		let base_title = match settings.source {
-			Source::Cx => format::format_single_title(period, "cx", cx, show_cost),
+			Source::Cx => format::format_single_title_with_reasoning(
+				period,
+				labels.cx,
+				cx,
+				show_cost,
+				prefs.codex_reasoning_display,
+				&tray_style,
+			),
 			Source::Cc => match cc_result {
-				Ok(totals) => format::format_single_title(period, "cc", totals, show_cost),
-				Err(_) => format!("{period} cc ERR"),
+				Ok(totals) => {
+					format::format_single_title(period, labels.cc, totals, show_cost, &tray_style)
+				}
+				Err(_) => format!("{period} {} ERR", labels.cc),
 			},
-			Source::Both => format::format_both_title_one_line(period, cx, cc_for_both, show_cost),
+			Source::Both => format::format_both_title_one_line(
+				period,
+				labels,
+				cx,
+				cc_for_both,
+				show_cost,
+				&tray_style,
+			),
 		};
 
+		let model_breakdown = match settings.source {
+			Source::Cx => cx_breakdown,
+			Source::Cc => cc_breakdown_result.unwrap_or_default(),
+			Source::Both => usage::merge_breakdowns(&[
+				&cx_breakdown,
+				&cc_breakdown_result.unwrap_or_default(),
+			]),
+		};
+
+		// 每日花费提醒：与当前选择的周期无关，始终按“今天”的 cx+cc 成本判断。
+		let daily_cost_alert = format::daily_cost_alert_threshold(prefs.daily_cost_alert_usd)
+			.filter(|_| pricing.available)
+			.map(|threshold| {
+				let today_cost = if settings.period == Period::Today {
+					cx.cost_usd + cc_for_both.cost_usd
+				} else {
+					let today = time_range::range_today();
+					usage::load_cx_totals_with_pricing(&today, dataset, &scan).cost_usd
+						+ usage::load_cc_totals_with_pricing(&today, dataset, &scan)
+							.map(|totals| totals.cost_usd)
+							.unwrap_or(0.0)
+				};
+				(threshold, today_cost)
+			});
+
 		// Right.codes：只有当拉取成功且可计算套餐额度时，才在状态栏追加 `rc ...`；
 		// 任何失败/未登录/字段缺失，都只在菜单里提示原因，避免在状态栏制造噪音。
-		let (rc_title_part, rc_menu_text) = compute_rightcodes_ui();
-		let title = if let Some(rc) = rc_title_part {
-			format!("{base} {rc}", base = base_title, rc = rc)
-		} else {
-			base_title
+		let rc_ui = compute_rightcodes_ui(prefs.rc_title_decimals, &prefs.budget_thresholds);
+		let rc_logged_in = rc_ui.logged_in;
+		let rc_failed = lock_recover(rightcodes::rc_backoff()).last_failed();
+		let (title, rc_menu_text) = rc_ui.title_and_menu(base_title, prefs.rc_in_title);
+		let sources = format::SourceAvailability {
+			codex_logs: usage::cx_logs_available(),
+			claude_logs: cc_available,
+			pricing: pricing.available,
+			rightcodes: rc_logged_in,
 		};
+		let empty_state = sources.is_empty();
+		let title = format::title_or_empty_state(title, sources);
 
-		let mut last_ui = state
-			.as_ref()
-			.map(|s| s.last_ui.lock().expect("last_ui lock poisoned"));
+		let mut last_ui = state.as_ref().map(|s| lock_last_ui(&s.last_ui));
 
 		let should_set_title = last_ui
 			.as_ref()
@@ -401,40 +743,147 @@ fn update_tray_title(app: &AppHandle, settings: Settings) {
 
 		// 同步更新菜单中的“完整统计”文本（不做 compact）。
 		if let Some(state) = state.as_ref() {
-			let full_cx = raw_format::format_single_title_raw(period, "cx", cx, show_cost);
+			let full_cx =
+				raw_format::format_single_title_raw(period, labels.cx, cx, show_cost, &cost);
+			// cx/cc 并排的一行（cc 列位置固定），没有 cc 时只展示 cx；空状态时换成引导文案。
+			let full_both = if empty_state {
+				format::EMPTY_STATE_MENU_HINT.to_string()
+			} else if cc_available {
+				raw_format::format_both_menu_line(
+					period,
+					labels,
+					cx,
+					cc_for_both,
+					show_cost,
+					&cost,
+				)
+			} else {
+				full_cx.clone()
+			};
+			let full_both = format::append_scan_failure(full_both, &usage::scan_failed_dirs());
+			// `TOKBAR_TZ` 写错时统计已回退到系统时区，在菜单里说明，而不是悄悄按另一个时区算。
+			let full_both = match time_range::check_tz_override() {
+				Ok(()) => full_both,
+				Err(err) => format!("{full_both}（{err}，已按系统时区统计）"),
+			};
+			let full_cx = format::append_scan_failure(full_cx, &usage::cx_scan_failed_dirs());
 			let full_cc = if cc_available {
-				raw_format::format_single_title_raw(period, "cc", cc_for_both, show_cost)
+				let line = raw_format::format_single_title_raw(
+					period,
+					labels.cc,
+					cc_for_both,
+					show_cost,
+					&cost,
+				);
+				format::append_scan_failure(line, &usage::cc_scan_failed_dirs())
 			} else {
 				// 本机没有 cc：菜单中不展示具体数值（避免 0 误导），并禁用相关项。
 				"cc：未检测到（本机无 Claude Code 日志）".to_string()
 			};
-			let all_cx =
-				raw_format::format_single_title_raw(all_label, "cx", cx_all, show_all_cost);
-			let all_cc = if cc_available {
-				match cc_all_result {
-					Ok(totals) => raw_format::format_single_title_raw(
+			// 触发 `max_files_scanned` 上限时，“全部”只统计了最近的文件，需要明确标注。
+			let (cx_all_partial, cc_all_partial) = usage::all_time_partial();
+			let partial_suffix = |partial: bool| if partial { "（部分：文件过多）" } else { "" };
+			let all_cx = format!(
+				"{}{}",
+				raw_format::format_single_title_raw(
+					all_label,
+					labels.cx,
+					cx_all,
+					show_all_cost,
+					&cost,
+				),
+				partial_suffix(cx_all_partial)
+			);
+			// cx+cc 的全部合计；本机没有 cc 时只有 cx。cc 存在但“全部”读取失败时
+			// 也只能先给出 cx，但要标明 cc 未计入，避免把它当成完整合计。
+			let all_combined = {
+				let mut totals = cx_all;
+				let (label, suffix) = match &cc_all_result {
+					_ if !cc_available => (labels.cx.to_string(), partial_suffix(cx_all_partial)),
+					Ok(cc_all) => {
+						totals += *cc_all;
+						let (first, second) = labels.ordered(labels.cx, labels.cc);
+						let partial = cx_all_partial || cc_all_partial;
+						(format!("{first}+{second}"), partial_suffix(partial))
+					}
+					Err(_) => (labels.cx.to_string(), "（cc 读取失败，未计入）"),
+				};
+				format!(
+					"{}{}",
+					raw_format::format_single_title_raw(
 						all_label,
-						"cc",
+						&label,
 						totals,
 						show_all_cost,
+						&cost,
+					),
+					suffix
+				)
+			};
+			let all_cc = if cc_available {
+				match cc_all_result {
+					Ok(totals) => format!(
+						"{}{}",
+						raw_format::format_single_title_raw(
+							all_label,
+							labels.cc,
+							totals,
+							show_all_cost,
+							&cost,
+						),
+						partial_suffix(cc_all_partial)
 					),
-					Err(_) => format!("{all_label} cc ERR"),
+					Err(_) => format!("{all_label} {} ERR", labels.cc),
 				}
 			} else {
 				"All cc：未检测到".to_string()
 			};
 
+			// 本月 vs 上月（cx+cc 合计）；当前周期就是本月时直接复用上面的统计，
+			// 否则两边都走缓存，不在每次刷新时重扫两个月的日志。
+			let this_month = if settings.period == Period::Month {
+				let mut totals = cx;
+				totals += cc_for_both;
+				totals
+			} else {
+				usage::load_this_month_totals_cached(dataset, &scan)
+			};
+			let month_compare = raw_format::format_month_comparison(
+				usage::load_previous_month_totals_cached(dataset, &scan),
+				this_month,
+				show_cost,
+				&cost,
+			);
+
 			let pricing_text = if pricing.available && pricing.last_error.is_none() {
 				"模型价格：可用".to_string()
+			} else if pricing.stale {
+				"模型价格：缓存已过期（点击检查代理设置）".to_string()
 			} else if pricing.available {
-				"模型价格：使用缓存（离线）".to_string()
+				match pricing.fetched_at {
+					Some(fetched_at) => format!(
+						"模型价格：使用缓存（离线，更新于 {}）",
+						format::format_time_ago(fetched_at, Local::now())
+					),
+					None => "模型价格：使用缓存（离线）".to_string(),
+				}
 			} else {
 				"无法获取模型价格，请设置魔法代理（点击打开设置）".to_string()
 			};
+			// 选了只显示金额但没有价格：标题已退回显示 token，在这里说明原因。
+			let pricing_text = if !show_cost && tray_style.metric == format::TrayMetric::Cost {
+				format!("{pricing_text}；状态栏暂显示 token")
+			} else {
+				pricing_text
+			};
 
 			let ui = last_ui
 				.as_mut()
 				.expect("AppState exists but last_ui lock missing");
+			if ui.stats_both_full.as_deref() != Some(full_both.as_str()) {
+				let _ = state.menu.stats_both_full.set_text(full_both.clone());
+				ui.stats_both_full = Some(full_both);
+			}
 			if ui.stats_cx_full.as_deref() != Some(full_cx.as_str()) {
 				let _ = state.menu.stats_cx_full.set_text(full_cx.clone());
 				ui.stats_cx_full = Some(full_cx);
@@ -443,6 +892,24 @@ fn update_tray_title(app: &AppHandle, settings: Settings) {
 				let _ = state.menu.stats_cc_full.set_text(full_cc.clone());
 				ui.stats_cc_full = Some(full_cc);
 			}
+			let model_rows = raw_format::format_model_menu_rows(
+				&model_breakdown,
+				prefs.menu_model_rows,
+				show_cost,
+				&cost,
+			);
+			if ui.model_rows.as_ref() != Some(&model_rows) {
+				rebuild_models_menu(app, &state.menu.models_menu, &model_rows);
+				ui.model_rows = Some(model_rows);
+			}
+			if ui.month_compare.as_deref() != Some(month_compare.as_str()) {
+				let _ = state.menu.month_compare.set_text(month_compare.clone());
+				ui.month_compare = Some(month_compare);
+			}
+			if ui.totals_combined_all.as_deref() != Some(all_combined.as_str()) {
+				let _ = state.menu.totals_combined_all.set_text(all_combined.clone());
+				ui.totals_combined_all = Some(all_combined);
+			}
 			if ui.totals_cx_all.as_deref() != Some(all_cx.as_str()) {
 				let _ = state.menu.totals_cx_all.set_text(all_cx.clone());
 				ui.totals_cx_all = Some(all_cx);
@@ -460,6 +927,23 @@ fn update_tray_title(app: &AppHandle, settings: Settings) {
 				let _ = state.menu.rightcodes_status.set_text(rc_menu_text.clone());
 				ui.rightcodes_status = Some(rc_menu_text);
 			}
+			let _ = state.menu.rightcodes_retry.set_enabled(rc_failed);
+
+			if let Some((threshold, today_cost)) = daily_cost_alert {
+				let today = time_parse::today();
+				let fired_on = ui.cost_alert_fired_on;
+				if format::daily_cost_alert_due(threshold, today_cost, today, fired_on) {
+					ui.cost_alert_fired_on = Some(today);
+					notify_cost_alert(app, threshold, today_cost, &cost);
+				}
+			}
+
+			if prefs.status_log_path.is_some() {
+				let cc_status = cc_available.then_some(cc_for_both);
+				ui.status = Some(status::StatusSnapshot::from_totals(
+					&range, &pricing, cx, cc_status, &scan,
+				));
+			}
 
 			// 没有 cc 数据来源时禁用 cc/both 相关菜单项，避免用户选择后产生困惑。
 			let _ = state.menu.stats_cc_full.set_enabled(cc_available);
@@ -471,41 +955,161 @@ fn update_tray_title(app: &AppHandle, settings: Settings) {
 	}
 }
 
-fn compute_rightcodes_ui() -> (Option<String>, String) {
+/// 菜单项数量会随模型数变化，只能整体清空后重建（仅在文案变化时调用）。
+fn rebuild_models_menu(app: &AppHandle, menu: &Submenu<Runtime>, rows: &[String]) {
+	if let Ok(items) = menu.items() {
+		for item in items {
+			let _ = menu.remove(&item);
+		}
+	}
+
+	if rows.is_empty() {
+		if let Ok(item) = MenuItem::new(app, "暂无数据", false, None::<&str>) {
+			let _ = menu.append(&item);
+		}
+		return;
+	}
+	for row in rows {
+		if let Ok(item) = MenuItem::new(app, row, false, None::<&str>) {
+			let _ = menu.append(&item);
+		}
+	}
+}
+
+fn compute_rightcodes_ui(
+	title_decimals: usize,
+	thresholds: &format::BudgetThresholds,
+) -> rightcodes::RightcodesUi {
 	let store = rightcodes_token_store::RightcodesTokenStore::new();
 	let Some(token) = store.load_token() else {
-		return (
-			None,
-			"rc：未登录（点击 Right.codes 登录…）".to_string(),
-		);
+		return rightcodes::RightcodesUi {
+			title_part: None,
+			menu_text: "rc：未登录（点击 Right.codes 登录…）".to_string(),
+			logged_in: false,
+		};
+	};
+	let failed = |menu_text: String| rightcodes::RightcodesUi {
+		title_part: None,
+		menu_text,
+		logged_in: true,
 	};
 
+	// 限流退避期内不请求，沿用上一次失败的文案（菜单“rc：重试”可放行一次）。
+	let now = std::time::Instant::now();
+	{
+		let mut gate = lock_recover(rightcodes::rc_backoff());
+		if !gate.should_attempt(now) {
+			let text = gate.last_menu_text().unwrap_or("rc：触发限流（429），请稍后重试");
+			return failed(text.to_string());
+		}
+	}
+
 	let client = rightcodes_api::RightcodesApiClient::new("https://right.codes");
-	let payload = match client.list_subscriptions(&token) {
-		Ok(v) => v,
+	let result = rightcodes_api::list_subscriptions_cached(&client, &token);
+	let mut gate = lock_recover(rightcodes::rc_backoff());
+	let payload = match result {
+		Ok(v) => {
+			gate.record_success();
+			v
+		}
 		Err(e) => {
 			// 失败只显示在菜单里（标题不显示 rc）。
-			return (None, e.to_menu_text());
+			let text = e.to_menu_text();
+			gate.record_failure(now, e.backoff(), text.clone());
+			return failed(text);
 		}
 	};
+	drop(gate);
 
-	let Some(summary) = rightcodes::summarize_single_subscription(&payload) else {
-		return (
-			None,
-			"rc：套餐数据缺失（无法计算额度）".to_string(),
-		);
+	let Some(summary) = rightcodes::summarize_single_subscription(&payload, title_decimals) else {
+		return failed("rc：套餐数据缺失（无法计算额度）".to_string());
+	};
+	// 状态栏保持单个套餐的紧凑形式；有多个套餐时菜单展示合计，避免漏看其余套餐。
+	let plans = rightcodes::summarize_all_subscriptions(&payload).len();
+	let menu_status = match rightcodes::summarize_combined_subscriptions(&payload, title_decimals) {
+		Some(combined) if plans > 1 => format!("{}（{plans} 个套餐合计）", combined.menu_status),
+		_ => summary.menu_status,
+	};
+	// 额度用量达到预算阈值时，在状态栏片段前加上等级提示。
+	let title_part =
+		format::with_budget_indicator(summary.title_part, summary.used_percent, thresholds);
+	rightcodes::RightcodesUi {
+		title_part: Some(title_part),
+		menu_text: menu_status,
+		logged_in: true,
+	}
+}
+
+/// 在 rc 状态菜单项里展示一条提示（下一次刷新会按实际状态覆盖）。
+fn show_rightcodes_status(app: &AppHandle, text: String) {
+	let Some(state) = app.try_state::<AppState>() else {
+		return;
 	};
-	(Some(summary.title_part), summary.menu_status)
+	let _ = state.menu.rightcodes_status.set_text(text.clone());
+	lock_last_ui(&state.last_ui).rightcodes_status = Some(text);
 }
 
-fn spawn_refresh_loop(app: AppHandle, settings: Arc<Mutex<Settings>>) {
+fn spawn_refresh_loop(app: AppHandle, settings: Arc<Mutex<Settings>>, interval_secs: u64) {
 	std::thread::spawn(move || loop {
-		let settings = *settings.lock().expect("settings lock poisoned");
+		let interval = std::time::Duration::from_secs(interval_secs);
+		let settings = *lock_recover(&settings);
+		let started = std::time::Instant::now();
 		update_tray_title(&app, settings);
-		std::thread::sleep(std::time::Duration::from_secs(REFRESH_INTERVAL_SECS));
+		let elapsed = started.elapsed();
+		if refresh::refresh_overran(elapsed, interval) {
+			eprintln!(
+				"tokbar: refresh took {:.1}s (interval {interval_secs}s)",
+				elapsed.as_secs_f64()
+			);
+		}
+		append_status_log(&app);
+		std::thread::sleep(refresh::sleep_after_refresh(elapsed, interval));
 	});
 }
 
+/// 由 Tauri 托管的日志目录监听器（释放即停止监听）。
+struct LogWatcher {
+	_watcher: Mutex<notify::RecommendedWatcher>,
+}
+
+/// 监听日志目录，日志变更后（去抖）按当前设置刷新托盘；一个目录都监听不了时返回 false。
+fn start_log_watcher(app: &AppHandle, settings: Arc<Mutex<Settings>>) -> bool {
+	let handle = app.clone();
+	let scan = scan_settings(app);
+	let dirs = usage::usage_log_dirs(&scan);
+	let watcher = refresh::watch_usage_dirs(&dirs, usage::usage_log_filter(&scan), move || {
+		let settings = *lock_recover(&settings);
+		update_tray_title(&handle, settings);
+	});
+	match watcher {
+		Some(watcher) => {
+			app.manage(LogWatcher {
+				_watcher: Mutex::new(watcher),
+			});
+			true
+		}
+		None => false,
+	}
+}
+
+/// 开启状态日志时，把本轮刷新生成的快照追加到日志文件（失败只忽略，不影响托盘）。
+fn append_status_log(app: &AppHandle) {
+	let Some(state) = app.try_state::<AppState>() else {
+		return;
+	};
+	let (path, max_bytes) = {
+		let prefs = lock_recover(&state.prefs);
+		(prefs.status_log_path.clone(), prefs.status_log_max_bytes)
+	};
+	let Some(path) = path.filter(|p| !p.trim().is_empty()) else {
+		return;
+	};
+	let snapshot = lock_last_ui(&state.last_ui).status.take();
+	if let Some(snapshot) = snapshot {
+		let _ = status::append_snapshot(std::path::Path::new(path.trim()), &snapshot, max_bytes);
+	}
+}
+
 fn open_proxy_window(app: &AppHandle) {
 	if let Some(window) = app.get_webview_window("proxy") {
 		let _ = window.show();
@@ -528,6 +1132,48 @@ fn open_proxy_window(app: &AppHandle) {
 	let _ = builder.build();
 }
 
+fn notify_cost_alert(
+	app: &AppHandle,
+	threshold: f64,
+	today_cost: f64,
+	cost: &raw_format::CostFormat,
+) {
+	use tauri_plugin_notification::NotificationExt as _;
+	let body = format!(
+		"今天 cx+cc 已花费 {}，达到提醒阈值 {}。",
+		cost.format(today_cost),
+		cost.format(threshold)
+	);
+	let _ = app
+		.notification()
+		.builder()
+		.title("tokbar 花费提醒")
+		.body(body)
+		.show();
+}
+
+fn open_cost_alert_window(app: &AppHandle) {
+	if let Some(window) = app.get_webview_window("cost_alert") {
+		let _ = window.show();
+		let _ = window.set_focus();
+		return;
+	}
+
+	let builder = tauri::WebviewWindowBuilder::new(
+		app,
+		"cost_alert",
+		tauri::WebviewUrl::App("index.html?view=cost_alert".into()),
+	)
+	.title("每日花费提醒")
+	.inner_size(480.0, 280.0)
+	.resizable(true)
+	.maximizable(false)
+	.minimizable(true)
+	.closable(true);
+
+	let _ = builder.build();
+}
+
 fn open_rightcodes_login_window(app: &AppHandle) {
 	if let Some(window) = app.get_webview_window("rightcodes_login") {
 		let _ = window.show();
@@ -558,26 +1204,55 @@ struct ProxySaveResult {
 	last_error: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct ProxyTestResult {
+	ok: bool,
+	error: Option<String>,
+}
+
+/// 设置面板的价格状态：当前代理、价格是否可用、模型数与数据集更新时间。
+#[derive(Debug, Clone, Serialize)]
+struct PricingStatus {
+	proxy: proxy_config::ProxyConfig,
+	pricing_available: bool,
+	last_error: Option<String>,
+	model_count: usize,
+	fetched_at: Option<DateTime<Local>>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 struct RightcodesLoginResult {
 	stored_in: String,
 }
 
+/// 给前端（未来的统计面板）用的原始总计；未选中的来源为 null。
+#[derive(Debug, Clone, Serialize)]
+struct UsageResult {
+	period: String,
+	show_cost: bool,
+	cx: Option<usage::UsageTotals>,
+	cc: Option<usage::UsageTotals>,
+}
+
 #[tauri::command]
 fn tokbar_get_proxy_config() -> proxy_config::ProxyConfig {
 	litellm::current_proxy_config()
 }
 
+#[tauri::command]
+fn tokbar_proxy_env_exports(config: proxy_config::ProxyConfig) -> String {
+	config.to_env_exports()
+}
+
 #[tauri::command]
 fn tokbar_set_proxy_config(
 	app: AppHandle,
 	config: proxy_config::ProxyConfig,
 ) -> Result<ProxySaveResult, String> {
-	litellm::update_proxy_config(config)?;
-	let pricing = litellm::get_pricing_context();
+	let pricing = litellm::update_proxy_config_and_refresh(config)?;
 
 	if let Some(state) = app.try_state::<AppState>() {
-		let settings = *state.settings.lock().expect("settings lock poisoned");
+		let settings = *lock_recover(&state.settings);
 		update_tray_title(&app, settings);
 	}
 
@@ -587,6 +1262,208 @@ fn tokbar_set_proxy_config(
 	})
 }
 
+/// 只测试可达性，不保存配置、不改动价格缓存（代理窗口的即时红/绿提示）。
+#[tauri::command]
+fn tokbar_test_proxy(config: proxy_config::ProxyConfig) -> ProxyTestResult {
+	match litellm::test_proxy(config) {
+		Ok(()) => ProxyTestResult {
+			ok: true,
+			error: None,
+		},
+		Err(err) => ProxyTestResult {
+			ok: false,
+			error: Some(err),
+		},
+	}
+}
+
+/// 只读缓存，不触发检查/拉取（前端可以随时调用，不会打乱价格刷新的节奏）。
+#[tauri::command]
+fn tokbar_get_pricing_status() -> PricingStatus {
+	let pricing = litellm::peek_pricing_context();
+	PricingStatus {
+		proxy: litellm::current_proxy_config(),
+		pricing_available: pricing.available,
+		last_error: pricing.last_error,
+		model_count: pricing.dataset.len(),
+		fetched_at: pricing.fetched_at,
+	}
+}
+
+/// 按 Codex/Claude 的匹配规则查某个模型的价格（排查“为什么这个模型是 $0”）；找不到返回 null。
+#[tauri::command]
+fn tokbar_lookup_pricing(app: AppHandle, model: String) -> Option<usage::PricingLookup> {
+	let pricing = litellm::get_pricing_context();
+	usage::lookup_pricing(&model, &pricing.dataset, &scan_settings(&app))
+}
+
+#[tauri::command]
+fn tokbar_get_usage(
+	app: AppHandle,
+	period: Period,
+	source: Source,
+) -> Result<UsageResult, String> {
+	let range = range_for_period(&app, period);
+	let pricing = litellm::get_pricing_context();
+	let dataset = &pricing.dataset;
+	let scan = scan_settings(&app);
+
+	let cx = match source {
+		Source::Cx | Source::Both => {
+			Some(usage::load_cx_totals_with_pricing(&range, dataset, &scan))
+		}
+		Source::Cc => None,
+	};
+	// 与托盘一致：本机没有 cc 数据来源时不伪造 0，而是把原因交给前端展示。
+	let cc = match source {
+		Source::Cc | Source::Both => Some(
+			usage::load_cc_totals_with_pricing(&range, dataset, &scan)
+				.map_err(|e| e.to_string())?,
+		),
+		Source::Cx => None,
+	};
+
+	Ok(UsageResult {
+		period: range.label,
+		show_cost: pricing.available,
+		cx,
+		cc,
+	})
+}
+
+/// 与 `tokbar_get_usage` 相同的来源选择，但附带按模型拆分与未定价模型（给统计面板用）。
+#[tauri::command]
+fn tokbar_get_report(
+	app: AppHandle,
+	period: Period,
+	source: Source,
+) -> Result<usage::UsageReport, String> {
+	let range = range_for_period(&app, period);
+	let pricing = litellm::get_pricing_context();
+	let dataset = &pricing.dataset;
+	let scan = scan_settings(&app);
+
+	let cx = match source {
+		Source::Cx | Source::Both => {
+			Some(usage::load_cx_report_with_pricing(&range, dataset, &scan))
+		}
+		Source::Cc => None,
+	};
+	let cc = match source {
+		Source::Cc | Source::Both => Some(
+			usage::load_cc_report_with_pricing(&range, dataset, &scan)
+				.map_err(|e| e.to_string())?,
+		),
+		Source::Cx => None,
+	};
+
+	Ok(usage::UsageReport {
+		period: range.label,
+		cost_available: pricing.available,
+		cx,
+		cc,
+	})
+}
+
+/// Claude 用量按项目拆分（给后续的项目明细视图用）；本机没有 Claude 数据目录时返回错误。
+#[tauri::command]
+fn tokbar_get_project_breakdown(
+	app: AppHandle,
+	period: Period,
+) -> Result<std::collections::HashMap<String, usage::UsageTotals>, String> {
+	let range = range_for_period(&app, period);
+	let pricing = litellm::get_pricing_context();
+	let scan = scan_settings(&app);
+	usage::load_cc_breakdown_by_project_with_pricing(&range, &pricing.dataset, &scan)
+		.map_err(|e| e.to_string())
+}
+
+/// 某个区间的扫描诊断（总行数、解析失败/区间外跳过的行数、去重条数），用于排查数字对不上。
+///
+/// 说明：会重新顺序扫描一遍日志，比常规刷新慢，只在用户主动查看时调用。
+#[tauri::command]
+fn tokbar_get_diagnostics(app: AppHandle, period: Period) -> usage::DiagnosticsReport {
+	let range = range_for_period(&app, period);
+	let pricing = litellm::get_pricing_context();
+	let scan = scan_settings(&app);
+	let (_, cx) = usage::load_cx_totals_with_diagnostics(&range, &pricing.dataset, &scan);
+	let cc = usage::load_cc_totals_with_diagnostics(&range, &pricing.dataset, &scan)
+		.ok()
+		.map(|(_, diagnostics)| diagnostics);
+	usage::DiagnosticsReport {
+		period: range.label,
+		cx,
+		cc,
+	}
+}
+
+/// 把 `[since, until]`（`YYYY-MM-DD`，含两端）逐日的 cx/cc 用量导出为 CSV，返回数据行数。
+#[tauri::command]
+fn tokbar_export_csv(
+	app: AppHandle,
+	since: String,
+	until: String,
+	path: String,
+) -> Result<usize, String> {
+	let parse = |raw: &str| {
+		NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d")
+			.map_err(|_| format!("日期格式应为 YYYY-MM-DD：{raw}"))
+	};
+	let (since, until) = (parse(&since)?, parse(&until)?);
+	let path = path.trim();
+	if path.is_empty() {
+		return Err("请填写导出文件路径。".to_string());
+	}
+	let pricing = litellm::get_pricing_context();
+	let scan = scan_settings(&app);
+	usage::export_daily_usage_csv(
+		since,
+		until,
+		std::path::Path::new(path),
+		&pricing.dataset,
+		&scan,
+	)
+}
+
+#[tauri::command]
+fn tokbar_get_cost_alert(app: AppHandle) -> Option<f64> {
+	let state = app.try_state::<AppState>()?;
+	let prefs = lock_recover(&state.prefs);
+	format::daily_cost_alert_threshold(prefs.daily_cost_alert_usd)
+}
+
+#[tauri::command]
+fn tokbar_set_cost_alert(app: AppHandle, threshold: Option<f64>) -> Result<(), String> {
+	if threshold.is_some() && format::daily_cost_alert_threshold(threshold).is_none() {
+		return Err("请输入大于 0 的金额。".to_string());
+	}
+	let Some(state) = app.try_state::<AppState>() else {
+		return Err("应用尚未就绪，请稍后重试。".to_string());
+	};
+
+	{
+		let mut prefs = lock_recover(&state.prefs);
+		prefs.daily_cost_alert_usd = threshold;
+		app_settings::save_settings(prefs.clone()).map_err(|e| format!("保存失败：{e}"))?;
+	}
+	// 阈值变了就允许今天按新阈值再提醒一次。
+	lock_last_ui(&state.last_ui).cost_alert_fired_on = None;
+
+	let settings = *lock_recover(&state.settings);
+	let app = app.clone();
+	std::thread::spawn(move || update_tray_title(&app, settings));
+	Ok(())
+}
+
+#[tauri::command]
+fn tokbar_invalidate_caches(app: AppHandle) {
+	usage::invalidate_all_time_caches();
+	if let Some(state) = app.try_state::<AppState>() {
+		let settings = *lock_recover(&state.settings);
+		update_tray_title(&app, settings);
+	}
+}
+
 #[tauri::command]
 fn tokbar_rightcodes_login(app: AppHandle, username: String, password: String) -> Result<RightcodesLoginResult, String> {
 	let user = username.trim();
@@ -615,9 +1492,12 @@ fn tokbar_rightcodes_login(app: AppHandle, username: String, password: String) -
 		format!("保存 token 失败：{e}")
 	})?;
 
+	// 新 token 可能对应另一个账号：丢弃缓存的套餐列表。
+	rightcodes_api::invalidate_subscriptions_cache();
+
 	// 登录成功后立即刷新一次，确保状态栏/菜单立刻更新（而不是等 30s 刷新线程）。
 	if let Some(state) = app.try_state::<AppState>() {
-		let settings = *state.settings.lock().expect("settings lock poisoned");
+		let settings = *lock_recover(&state.settings);
 		update_tray_title(&app, settings);
 	}
 
@@ -635,6 +1515,19 @@ fn tokbar_rightcodes_login(app: AppHandle, username: String, password: String) -
 pub fn run() {
 	tauri::Builder::default()
 		.plugin(tauri_plugin_opener::init())
+		.plugin(tauri_plugin_notification::init())
+		.plugin(tauri_plugin_clipboard_manager::init())
+		.plugin(
+			tauri_plugin_global_shortcut::Builder::new()
+				.with_handler(|app, _shortcut, event| {
+					use tauri_plugin_global_shortcut::ShortcutState;
+					// 只注册了切换周期这一个快捷键。
+					if event.state() == ShortcutState::Pressed {
+						cycle_period(app);
+					}
+				})
+				.build(),
+		)
 		.plugin(tauri_plugin_autostart::init(
 			tauri_plugin_autostart::MacosLauncher::LaunchAgent,
 			None,
@@ -642,12 +1535,24 @@ pub fn run() {
 		.invoke_handler(tauri::generate_handler![
 			tokbar_get_proxy_config,
 			tokbar_set_proxy_config,
+			tokbar_proxy_env_exports,
+			tokbar_test_proxy,
+			tokbar_get_pricing_status,
+			tokbar_lookup_pricing,
+			tokbar_get_usage,
+			tokbar_get_report,
+			tokbar_get_project_breakdown,
+			tokbar_get_diagnostics,
+			tokbar_export_csv,
+			tokbar_invalidate_caches,
+			tokbar_get_cost_alert,
+			tokbar_set_cost_alert,
 			tokbar_rightcodes_login
 		])
 		.setup(|app| {
 			use tauri_plugin_autostart::ManagerExt as _;
 
-			let settings = Settings::default();
+			let settings = load_tray_settings();
 			let prefs = app_settings::load_settings();
 
 			apply_dock_icon_preference(&app.handle(), prefs.show_dock_icon);
@@ -658,16 +1563,23 @@ pub fn run() {
 			}
 
 			let (menu, menu_handles) = build_menu(&app.handle(), settings, &prefs)?;
+			let title = compute_title(
+				&app.handle(),
+				settings,
+				prefs.source_labels(),
+				&prefs.tray_style(),
+				&prefs.scan_settings(),
+			);
 
 			let state = AppState {
 				settings: Arc::new(Mutex::new(settings)),
 				prefs: Arc::new(Mutex::new(prefs)),
 				menu: menu_handles,
 				last_ui: Arc::new(Mutex::new(LastUiState::default())),
+				session_started_at: Arc::new(Mutex::new(Local::now())),
 			};
 			app.manage(state.clone());
 
-			let title = compute_title(&app.handle(), settings);
 			let mut tray_builder = TrayIconBuilder::with_id("tokbar-tray")
 				.menu(&menu)
 				.title(&title);
@@ -680,21 +1592,57 @@ pub fn run() {
 					let Some(state) = app.try_state::<AppState>() else {
 						return;
 					};
-					let mut settings = state.settings.lock().expect("settings lock poisoned");
+					let mut settings = lock_recover(&state.settings);
+					let before = *settings;
 
 					match event.id().as_ref() {
 						"rightcodes.login" => {
 							open_rightcodes_login_window(app);
 							return;
 						}
+						"rightcodes.paste_token" => {
+							// 从剪贴板读取 token 并保存；token 不写日志，也不出现在任何提示里。
+							let app = app.clone();
+							let settings = *settings;
+							std::thread::spawn(move || {
+								use tauri_plugin_clipboard_manager::ClipboardExt as _;
+								let raw = app.clipboard().read_text().unwrap_or_default();
+								let store = rightcodes_token_store::RightcodesTokenStore::new();
+								match store.save_pasted_token(&raw) {
+									Ok(_) => {
+										rightcodes_api::invalidate_subscriptions_cache();
+										update_tray_title(&app, settings);
+									}
+									Err(err) => show_rightcodes_status(&app, format!("rc：{err}")),
+								}
+							});
+							return;
+						}
+						"rightcodes.retry" => {
+							// 跳过 rc 的限流退避放行一次，并立即刷新（成功后标题里的 rc 片段随之恢复）。
+							lock_recover(rightcodes::rc_backoff()).request_retry();
+							let app = app.clone();
+							let settings = *settings;
+							std::thread::spawn(move || update_tray_title(&app, settings));
+							return;
+						}
 						"refresh" => {
 							let app = app.clone();
 							let settings = *settings;
 							std::thread::spawn(move || update_tray_title(&app, settings));
 							return;
 						}
+						"totals.recompute" => {
+							let app = app.clone();
+							let settings = *settings;
+							std::thread::spawn(move || {
+								usage::invalidate_all_time_caches();
+								update_tray_title(&app, settings);
+							});
+							return;
+						}
 						"dock.icon" => {
-							let mut prefs = state.prefs.lock().expect("prefs lock poisoned");
+							let mut prefs = lock_recover(&state.prefs);
 							prefs.show_dock_icon = !prefs.show_dock_icon;
 							let _ = app_settings::save_settings(prefs.clone());
 							apply_dock_icon_preference(app, prefs.show_dock_icon);
@@ -703,7 +1651,7 @@ pub fn run() {
 						}
 						"autostart" => {
 							use tauri_plugin_autostart::ManagerExt as _;
-							let mut prefs = state.prefs.lock().expect("prefs lock poisoned");
+							let mut prefs = lock_recover(&state.prefs);
 							let next = !prefs.autostart;
 							let result = if next {
 								app.autolaunch().enable()
@@ -717,15 +1665,56 @@ pub fn run() {
 							}
 							return;
 						}
+						"rightcodes.in_title" => {
+							// 只切换偏好；随后走通用刷新路径让标题立即生效。
+							let mut prefs = lock_recover(&state.prefs);
+							prefs.rc_in_title = !prefs.rc_in_title;
+							let _ = app_settings::save_settings(prefs.clone());
+							let _ = state.menu.rc_in_title.set_checked(prefs.rc_in_title);
+						}
+						"pricing.refresh" => {
+							// 手动刷新忽略退避；拉取是同步网络请求，放到后台线程避免卡住菜单。
+							let app = app.clone();
+							let settings = *settings;
+							std::thread::spawn(move || {
+								let _ = litellm::force_refresh_pricing();
+								update_tray_title(&app, settings);
+							});
+							return;
+						}
 						"pricing.status" | "proxy.open" => {
 							open_proxy_window(app);
 							return;
 						}
+						"cost_alert.open" => {
+							open_cost_alert_window(app);
+							return;
+						}
+						"period.shortcut" => {
+							// 勾选时启用默认快捷键，取消勾选时清除（自定义写法可在 settings.json 里改）。
+							let mut prefs = lock_recover(&state.prefs);
+							prefs.period_shortcut = match prefs.period_shortcut {
+								Some(_) => None,
+								None => Some(app_settings::DEFAULT_PERIOD_SHORTCUT.to_string()),
+							};
+							let _ = app_settings::save_settings(prefs.clone());
+							let shortcut = prefs.period_shortcut.as_deref();
+							apply_period_shortcut(app, &state.menu, shortcut);
+							return;
+						}
 						"quit" => app.exit(0),
 						"period.today" => settings.period = Period::Today,
+						"period.yesterday" => settings.period = Period::Yesterday,
 						"period.week" => settings.period = Period::Week,
+						"period.last7d" => settings.period = Period::Last7d,
 						"period.month" => settings.period = Period::Month,
+						"period.last30d" => settings.period = Period::Last30d,
 						"period.year" => settings.period = Period::Year,
+						"period.session" => settings.period = Period::Session,
+						"period.session_reset" => {
+							*lock_recover(&state.session_started_at) = Local::now();
+							settings.period = Period::Session;
+						}
 						"source.cx" => settings.source = Source::Cx,
 						"source.cc" => settings.source = Source::Cc,
 						"source.both" => settings.source = Source::Both,
@@ -734,6 +1723,9 @@ pub fn run() {
 
 					let updated = *settings;
 					drop(settings);
+					if updated != before {
+						save_tray_settings(updated);
+					}
 					sync_menu_checks(&state.menu, updated);
 					let app = app.clone();
 					std::thread::spawn(move || update_tray_title(&app, updated));
@@ -744,11 +1736,40 @@ pub fn run() {
 				std::thread::spawn(move || update_tray_title(&app, settings));
 			}
 			sync_menu_checks(&state.menu, settings);
-
-			spawn_refresh_loop(app.handle().clone(), state.settings.clone());
+			apply_period_shortcut(
+				app.handle(),
+				&state.menu,
+				lock_recover(&state.prefs).period_shortcut.as_deref(),
+			);
+
+			let watching = lock_recover(&state.prefs).watch_logs
+				&& start_log_watcher(app.handle(), state.settings.clone());
+			let interval_secs = if watching {
+				WATCHED_REFRESH_INTERVAL_SECS
+			} else {
+				REFRESH_INTERVAL_SECS
+			};
+			spawn_refresh_loop(app.handle().clone(), state.settings.clone(), interval_secs);
 
 			Ok(())
 		})
 		.run(tauri::generate_context!())
 		.expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn period_shortcut_cycles_today_week_month_year() {
+		assert_eq!(Period::Today.next_in_cycle(), Period::Week);
+		assert_eq!(Period::Week.next_in_cycle(), Period::Month);
+		assert_eq!(Period::Month.next_in_cycle(), Period::Year);
+		assert_eq!(Period::Year.next_in_cycle(), Period::Today);
+		// 不在循环里的周期从“今天”重新开始。
+		for period in [Period::Yesterday, Period::Last7d, Period::Last30d, Period::Session] {
+			assert_eq!(period.next_in_cycle(), Period::Today);
+		}
+	}
+}
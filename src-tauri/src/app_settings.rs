@@ -1,12 +1,149 @@
 use std::fs;
 use std::path::PathBuf;
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
+use crate::claude;
+use crate::codex::{self, CodexAccounting, CodexInputConvention};
+use crate::config_dir;
+use crate::format::{
+	BudgetThresholds, DisplayCurrency, ReasoningDisplay, TokenStyle, TrayMetric, TrayStyle,
+	DEFAULT_COST_DECIMALS, MAX_COST_DECIMALS,
+};
+use crate::raw_format::{CostFormat, SourceLabels};
+use crate::rightcodes;
+use crate::status::DEFAULT_STATUS_LOG_MAX_BYTES;
+use crate::time_parse::BusinessHours;
+use crate::usage::ScanSettings;
+
+/// 默认的切换周期快捷键（macOS 上是 Cmd+Shift+P，其它平台是 Ctrl+Shift+P）。
+pub const DEFAULT_PERIOD_SHORTCUT: &str = "CmdOrCtrl+Shift+P";
+
+// 说明：`serde(default)` 保证旧版本写下的 settings.json（缺少新字段）仍能被读取，
+// 而不是整体解析失败后回退成默认值、丢掉用户已有的偏好。
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
 	pub show_dock_icon: bool,
 	pub autostart: bool,
+	pub budget_thresholds: BudgetThresholds,
+	/// 是否在状态栏标题里追加 rc 额度片段（关闭后 rc 只在菜单中展示）。
+	pub rc_in_title: bool,
+	/// 状态栏 rc 额度金额保留的小数位数（菜单始终保留完整精度）。
+	pub rc_title_decimals: usize,
+	/// 离线缓存的价格超过多少天未更新就视为不可用（0 表示不限制）。
+	pub pricing_max_stale_days: u32,
+	/// Codex 日志 `input_tokens` 是否包含缓存命中部分（默认按记录自动判断）。
+	pub codex_input_convention: CodexInputConvention,
+	/// Codex 用量按逐条增量累加，还是按每个会话最后的累计快照统计。
+	pub codex_accounting: CodexAccounting,
+	/// 菜单“按模型”最多展示多少行，其余合并成一行（0 表示不折叠）。
+	pub menu_model_rows: usize,
+	/// 状态栏 cx 标题是否把推理 token 单独标注出来（`folded` 默认计入总量，`annotated`/`suffix` 单独标注）。
+	pub codex_reasoning_display: ReasoningDisplay,
+	/// 标题/菜单里 Codex 的来源缩写（默认 `cx`）。
+	pub cx_label: String,
+	/// 标题/菜单里 Claude Code 的来源缩写（默认 `cc`）。
+	pub cc_label: String,
+	/// cx+cc 同时展示时把 cc 放在前面（默认 cx 在前）。
+	pub cc_first: bool,
+	/// 设置后，托盘每次定时刷新都把状态快照以 NDJSON 追加到该文件（默认关闭）。
+	pub status_log_path: Option<String>,
+	/// 状态日志超过该大小后轮转为 `<file>.1`（0 表示不限制）。
+	pub status_log_max_bytes: u64,
+	/// “全部”统计的起始日期（`YYYY-MM-DD`，含当天）；早于该日期的历史记录不计入。
+	pub all_time_since: Option<NaiveDate>,
+	/// 今天 cx+cc 的花费（USD）首次达到该值时发送系统通知；None 表示不提醒。
+	pub daily_cost_alert_usd: Option<f64>,
+	/// “今天”按最近一条日志所在的时区划分，而不是系统时区（出差/VPN 切换时区时更稳定）。
+	pub anchor_today_to_latest_entry: bool,
+	/// “全部”统计最多扫描多少个日志文件（按修改时间取最近的）；None 表示不限制。
+	pub max_files_scanned: Option<usize>,
+	/// 工作时间过滤（如周一到周五 09:00–18:00）：只统计窗口内或窗口外的记录；默认关闭。
+	pub business_hours: BusinessHours,
+	/// 金额的展示币种与固定汇率（默认 USD），如 `{"code":"EUR","symbol":"€","usd_rate":0.92}`。
+	pub currency: DisplayCurrency,
+	/// 金额保留的小数位数（默认 2；日用量只有几美分时可调到 4）。
+	pub cost_decimals: usize,
+	/// 不计入 Claude 统计的项目目录名（`~/.claude/projects/` 下的目录名，精确匹配）。
+	pub exclude_projects: Vec<String>,
+	/// Claude 数据目录下要扫描的日志子目录（默认 `["projects", "history"]`）。
+	pub claude_log_subdirs: Vec<String>,
+	/// 状态栏标题里 token 数的写法：`compact`（默认，`1.2m`）或 `full`（`1,234,567`）；菜单不受影响。
+	pub tray_token_style: TokenStyle,
+	/// 状态栏标题展示的数字：`tokens`、`cost`（如 `Today cx $1.23`）或 `both`（默认）；菜单不受影响。
+	pub tray_metric: TrayMetric,
+	/// 监听日志目录，日志变更约 2 秒后就刷新托盘（定时刷新退为兜底）；关闭后每 30 秒轮询。
+	pub watch_logs: bool,
+	/// 循环切换统计周期（今天→本周→本月→本年）的全局快捷键；默认 null 不注册，
+	/// 在菜单里勾选后启用 [`DEFAULT_PERIOD_SHORTCUT`]（避免抢占其它应用的快捷键）。
+	pub period_shortcut: Option<String>,
+	/// 计价时只按模型名及其前缀形式精确匹配价格表，不做子串兜底（避免 `gpt-4` 套用别的模型的价）。
+	pub strict_model_matching: bool,
+}
+
+/// 来源缩写最多保留的字符数，避免状态栏标题过长被系统截断。
+const MAX_SOURCE_LABEL_CHARS: usize = 12;
+
+fn sanitize_source_label<'a>(raw: &'a str, default: &'a str) -> &'a str {
+	let trimmed = raw.trim();
+	if trimmed.is_empty() {
+		return default;
+	}
+	match trimmed.char_indices().nth(MAX_SOURCE_LABEL_CHARS) {
+		Some((end, _)) => &trimmed[..end],
+		None => trimmed,
+	}
+}
+
+impl AppSettings {
+	/// 校验后的来源缩写（空白回退到默认值，过长则截断）与 both 形式的先后顺序。
+	pub fn source_labels(&self) -> SourceLabels<'_> {
+		let defaults = SourceLabels::default();
+		SourceLabels {
+			cx: sanitize_source_label(&self.cx_label, defaults.cx),
+			cc: sanitize_source_label(&self.cc_label, defaults.cc),
+			cc_first: self.cc_first,
+		}
+	}
+
+	/// 扫描日志用到的那部分设置（口径、工作时间等），由调用方传给 `usage::load_*`。
+	pub fn scan_settings(&self) -> ScanSettings {
+		ScanSettings {
+			cx: codex::ScanOptions::new(
+				self.codex_input_convention,
+				self.codex_accounting,
+				self.business_hours.clone(),
+				self.strict_model_matching,
+			),
+			cc: claude::ScanOptions {
+				business_hours: self.business_hours.clone(),
+				exclude_projects: self.exclude_projects.clone(),
+				log_subdirs: self.claude_log_subdirs.clone(),
+				strict_model_matching: self.strict_model_matching,
+			},
+			all_time_since: self.all_time_since,
+			max_files_scanned: self.max_files_scanned,
+		}
+	}
+
+	/// 金额的写法：展示币种 + 小数位数（超过上限时截断）。
+	pub fn cost_format(&self) -> CostFormat {
+		CostFormat {
+			currency: self.currency.clone(),
+			decimals: self.cost_decimals.min(MAX_COST_DECIMALS),
+		}
+	}
+
+	/// 状态栏标题的写法：金额格式 + token 写法 + 展示的数字。
+	pub fn tray_style(&self) -> TrayStyle {
+		TrayStyle {
+			cost: self.cost_format(),
+			tokens: self.tray_token_style,
+			metric: self.tray_metric,
+		}
+	}
 }
 
 impl Default for AppSettings {
@@ -14,16 +151,48 @@ impl Default for AppSettings {
 		Self {
 			show_dock_icon: true,
 			autostart: false,
+			budget_thresholds: BudgetThresholds::default(),
+			rc_in_title: true,
+			rc_title_decimals: rightcodes::DEFAULT_QUOTA_DECIMALS,
+			pricing_max_stale_days: 30,
+			codex_input_convention: CodexInputConvention::Auto,
+			codex_accounting: CodexAccounting::Deltas,
+			menu_model_rows: 8,
+			codex_reasoning_display: ReasoningDisplay::Folded,
+			cx_label: "cx".to_string(),
+			cc_label: "cc".to_string(),
+			cc_first: false,
+			status_log_path: None,
+			status_log_max_bytes: DEFAULT_STATUS_LOG_MAX_BYTES,
+			all_time_since: None,
+			daily_cost_alert_usd: None,
+			anchor_today_to_latest_entry: false,
+			max_files_scanned: None,
+			business_hours: BusinessHours::default(),
+			currency: DisplayCurrency::default(),
+			cost_decimals: DEFAULT_COST_DECIMALS,
+			exclude_projects: Vec::new(),
+			claude_log_subdirs: claude::DEFAULT_LOG_SUBDIRS.iter().map(|d| d.to_string()).collect(),
+			tray_token_style: TokenStyle::Compact,
+			tray_metric: TrayMetric::Both,
+			watch_logs: true,
+			period_shortcut: None,
+			strict_model_matching: false,
 		}
 	}
 }
 
 fn default_config_path() -> Option<PathBuf> {
-	let home = std::env::var("HOME").ok()?;
-	if home.trim().is_empty() {
-		return None;
-	}
-	Some(PathBuf::from(home).join(".tokbar").join("settings.json"))
+	tokbar_file("settings.json")
+}
+
+/// 托盘菜单状态（上次选择的统计周期与数据来源）的保存位置，与 settings.json 同目录。
+pub(crate) fn tray_state_path() -> Option<PathBuf> {
+	tokbar_file("tray.json")
+}
+
+fn tokbar_file(name: &str) -> Option<PathBuf> {
+	Some(config_dir::tokbar_data_dir()?.join(name))
 }
 
 pub fn load_settings() -> AppSettings {
@@ -40,13 +209,46 @@ pub fn save_settings(settings: AppSettings) -> Result<(), String> {
 	let Some(path) = default_config_path() else {
 		return Err("HOME is not set".to_string());
 	};
-	let Some(parent) = path.parent() else {
-		return Err("invalid settings path".to_string());
-	};
 
 	let body = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-	fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+	config_dir::ensure_parent_dir(&path)?;
 	fs::write(path, body).map_err(|e| e.to_string())?;
 	Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn source_labels_fall_back_and_truncate() {
+		let settings = AppSettings {
+			cx_label: "  Codex  ".to_string(),
+			cc_label: "   ".to_string(),
+			..Default::default()
+		};
+		assert_eq!(
+			settings.source_labels(),
+			SourceLabels {
+				cx: "Codex",
+				cc: "cc",
+				cc_first: false,
+			}
+		);
+
+		let long = AppSettings {
+			cx_label: "Codex CLI by OpenAI".to_string(),
+			..Default::default()
+		};
+		assert_eq!(long.source_labels().cx, "Codex CLI by");
+	}
+
+	#[test]
+	fn period_shortcut_is_opt_in() {
+		let missing: AppSettings = serde_json::from_str("{}").expect("parse");
+		assert_eq!(missing.period_shortcut, None);
+		let enabled: AppSettings =
+			serde_json::from_str(r#"{"period_shortcut":"CmdOrCtrl+Shift+P"}"#).expect("parse");
+		assert_eq!(enabled.period_shortcut.as_deref(), Some(DEFAULT_PERIOD_SHORTCUT));
+	}
+}
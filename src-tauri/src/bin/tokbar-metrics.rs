@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use tokbar_lib::litellm;
+use tokbar_lib::status;
+use tokbar_lib::time_range;
+use tokbar_lib::usage;
+
+struct Args {
+	/// 写入的 textfile 路径；为 None 时输出到 stdout。
+	output: Option<PathBuf>,
+}
+
+fn usage_and_exit() -> ! {
+	eprintln!(
+		"Usage: tokbar-metrics [--output <path>]\n\
+Examples:\n\
+  tokbar-metrics\n\
+  tokbar-metrics --output /var/lib/node_exporter/textfile/tokbar.prom"
+	);
+	std::process::exit(2);
+}
+
+fn parse_args() -> Args {
+	let mut output = None;
+	let mut args = std::env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--output" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				output = Some(PathBuf::from(value));
+			}
+			_ => usage_and_exit(),
+		}
+	}
+	Args { output }
+}
+
+fn main() {
+	if let Err(err) = time_range::check_tz_override() {
+		eprintln!("ERR: {err}");
+		std::process::exit(1);
+	}
+	usage::apply_day_anchor_from_settings();
+	let scan = usage::ScanSettings::from_settings();
+	let Args { output } = parse_args();
+
+	let pricing = litellm::get_pricing_context();
+	let body = status::MetricsSnapshot::collect(&pricing, &scan).to_prometheus_text();
+	let Some(path) = output else {
+		print!("{body}");
+		return;
+	};
+	if let Err(err) = status::write_textfile(&path, &body) {
+		eprintln!("ERR: {err}");
+		std::process::exit(1);
+	}
+}
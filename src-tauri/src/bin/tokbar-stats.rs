@@ -1,4 +1,15 @@
-use tokbar_lib::raw_format::{format_both_title_raw, format_single_title_raw};
+use std::io::{BufRead, IsTerminal, Write};
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tokbar_lib::app_settings;
+use tokbar_lib::ccusage;
+use tokbar_lib::raw_format::{
+	format_both_menu_line, format_both_title_raw, format_single_title_raw,
+	format_single_title_raw_with_cache, format_u64_with_commas, CostFormat, SourceLabels,
+};
+use tokbar_lib::status;
 use tokbar_lib::time_range;
 use tokbar_lib::usage;
 use tokbar_lib::litellm;
@@ -6,11 +17,28 @@ use tokbar_lib::litellm;
 #[derive(Debug, Clone, Copy)]
 enum Period {
 	Today,
+	Yesterday,
 	Week,
+	Last7d,
 	Month,
+	Last30d,
 	Year,
 }
 
+/// ccusage 兼容导出的分组方式（只导出 cc 数据）。
+#[derive(Debug, Clone, Copy)]
+enum CcusageFormat {
+	Daily,
+	Monthly,
+}
+
+/// `--from-stdin` 时按哪种日志格式解析每一行。
+#[derive(Debug, Clone, Copy)]
+enum LineFormat {
+	Cx,
+	Cc,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Source {
 	Cx,
@@ -18,22 +46,134 @@ enum Source {
 	Both,
 }
 
+struct Args {
+	/// `--period` 预设，或 `--since/--until` 指定的自定义区间。
+	range: time_range::DateRange,
+	/// 区间来自哪个 `--period` 预设；自定义区间时为 None（`--watch` 跨天时据此重算区间）。
+	period: Option<Period>,
+	source: Source,
+	/// 只统计指定模型（跨来源按归一化后的模型名汇总）。
+	model: Option<String>,
+	/// 输出本期实际用到的模型的价格覆盖情况。
+	coverage: bool,
+	/// 以 JSON 对象输出总计（便于脚本/jq 消费）。
+	json: bool,
+	ccusage: Option<CcusageFormat>,
+	/// 输出当前状态快照（JSON）。
+	status: bool,
+	/// 把状态快照以 NDJSON 追加到指定文件（隐含 `--status`）。
+	status_log: Option<String>,
+	/// `--bench pricing`：测量价格查找耗时。
+	bench_pricing: bool,
+	/// `--price <model>`：输出该模型解析到的价格与命中的 key。
+	price: Option<String>,
+	/// 输出一次性的指标快照（JSON，覆盖今天/本周/本月/全部）。
+	metrics: bool,
+	/// 列出全量日志里出现过的模型及其最后使用日期。
+	models: bool,
+	/// 只输出解析后的统计区间（不扫描日志）。
+	show_range: bool,
+	/// 在总计后追加输入里缓存读取/写入 token 的拆分。
+	cache_split: bool,
+	/// `--watch [秒]`：按间隔在同一行刷新统计，直到 Ctrl-C。
+	watch: Option<u64>,
+	/// `--from-stdin --format cx|cc`：从 stdin 读 JSONL，按对应格式解析后输出总计。
+	from_stdin: Option<LineFormat>,
+	/// 是否显式给了 `--period` 或 `--since/--until`（`--from-stdin` 只在给了时按区间过滤）。
+	range_given: bool,
+	/// 把解析出的日志目录与参与统计的文件列表输出到 stderr（不影响 stdout）。
+	verbose: bool,
+}
+
+/// `--bench pricing` 使用的固定模型名：前半是常见的命中项，后半是价格表里大概率没有的名字
+/// （未命中会走线性子串回退，是主要的开销来源）。
+const BENCH_CX_MODELS: [&str; 6] = [
+	"gpt-5",
+	"gpt-5-codex",
+	"gpt-5-mini",
+	"o9-preview",
+	"my-local-model",
+	"codex-internal-2099",
+];
+const BENCH_CC_MODELS: [&str; 6] = [
+	"claude-sonnet-4-20250514",
+	"claude-opus-4-1-20250805",
+	"claude-3-5-haiku-20241022",
+	"qwen3-coder-plus",
+	"kimi-k2-0905-preview",
+	"unknown",
+];
+const BENCH_ITERATIONS: u32 = 200;
+/// `--watch` 不带秒数时的刷新间隔。
+const DEFAULT_WATCH_SECS: u64 = 5;
+
+/// `--json` 的输出结构：只包含所选来源；`both` 时本机没有 cc 日志目录则 `cc` 为 null。
+#[derive(Serialize)]
+struct JsonReport<'a> {
+	period: &'a str,
+	source: &'a str,
+	cost_available: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	cx: Option<usage::UsageTotals>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	cc: Option<Option<usage::UsageTotals>>,
+}
+
 fn usage_and_exit() -> ! {
 	eprintln!(
-		"Usage: tokbar-stats [--period today|week|month|year] [--source cx|cc|both]\n\
+		"Usage: tokbar-stats [--period today|yesterday|week|7d|month|30d|year] [--source cx|cc|both] [--model <name>] [--coverage] [--json]\n\
+  [--since YYYY-MM-DD --until YYYY-MM-DD]\n\
+  [--format ccusage|ccusage-monthly] [--from-stdin --format cx|cc]\n\
+  [--status] [--status-log <path>]\n\
+  [--bench pricing] [--price <model>] [--metrics] [--models] [--show-range]\n\
+  [--cache-split] [--watch [seconds]] [--verbose]\n\
 Examples:\n\
   tokbar-stats --source cx\n\
   tokbar-stats --source cc\n\
-  tokbar-stats --period week --source both"
+  tokbar-stats --period week --source both\n\
+  tokbar-stats --since 2026-01-01 --until 2026-01-31\n\
+  tokbar-stats --model gpt-5 --source both\n\
+  tokbar-stats --period month --coverage\n\
+  tokbar-stats --period week --json | jq .cx.total_tokens\n\
+  tokbar-stats --period month --format ccusage\n\
+  cat ~/logs/*.jsonl | tokbar-stats --from-stdin --format cc\n\
+  tokbar-stats --status --status-log ~/.tokbar/status.ndjson\n\
+  tokbar-stats --bench pricing\n\
+  tokbar-stats --price gpt-5 --source cx\n\
+  tokbar-stats --metrics\n\
+  tokbar-stats --models --source cx\n\
+  tokbar-stats --show-range --period week\n\
+  tokbar-stats --period month --source cc --cache-split\n\
+  tokbar-stats --watch 10 --source both\n\
+  tokbar-stats --period week --verbose 2>files.txt"
 	);
 	std::process::exit(2);
 }
 
-fn parse_args() -> (Period, Source) {
+fn parse_args() -> Args {
 	let mut period = Period::Today;
 	let mut source = Source::Both;
+	let mut model = None;
+	let mut coverage = false;
+	let mut json = false;
+	let mut ccusage = None;
+	let mut status = false;
+	let mut status_log = None;
+	let mut bench_pricing = false;
+	let mut price = None;
+	let mut metrics = false;
+	let mut models = false;
+	let mut show_range = false;
+	let mut cache_split = false;
+	let mut watch = None;
+	let mut from_stdin = false;
+	let mut line_format = None;
+	let mut verbose = false;
+	let mut period_given = false;
+	let mut since = None;
+	let mut until = None;
 
-	let mut args = std::env::args().skip(1);
+	let mut args = std::env::args().skip(1).peekable();
 	while let Some(arg) = args.next() {
 		match arg.as_str() {
 			"--period" => {
@@ -42,11 +182,15 @@ fn parse_args() -> (Period, Source) {
 				};
 				period = match value.as_str() {
 					"today" => Period::Today,
+					"yesterday" => Period::Yesterday,
 					"week" => Period::Week,
+					"7d" => Period::Last7d,
 					"month" => Period::Month,
+					"30d" => Period::Last30d,
 					"year" => Period::Year,
 					_ => usage_and_exit(),
 				};
+				period_given = true;
 			}
 			"--source" => {
 				let Some(value) = args.next() else {
@@ -59,47 +203,697 @@ fn parse_args() -> (Period, Source) {
 					_ => usage_and_exit(),
 				};
 			}
+			"--model" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				if value.trim().is_empty() {
+					usage_and_exit();
+				}
+				model = Some(value.trim().to_string());
+			}
+			"--since" | "--until" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				let Ok(date) = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") else {
+					usage_and_exit();
+				};
+				if arg == "--since" {
+					since = Some(date);
+				} else {
+					until = Some(date);
+				}
+			}
+			"--coverage" => coverage = true,
+			"--json" => json = true,
+			"--format" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				match value.as_str() {
+					"ccusage" => ccusage = Some(CcusageFormat::Daily),
+					"ccusage-monthly" => ccusage = Some(CcusageFormat::Monthly),
+					"cx" => line_format = Some(LineFormat::Cx),
+					"cc" => line_format = Some(LineFormat::Cc),
+					_ => usage_and_exit(),
+				}
+			}
+			"--from-stdin" => from_stdin = true,
+			"--status" => status = true,
+			"--status-log" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				if value.trim().is_empty() {
+					usage_and_exit();
+				}
+				status_log = Some(value.trim().to_string());
+			}
+			"--bench" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				bench_pricing = match value.as_str() {
+					"pricing" => true,
+					_ => usage_and_exit(),
+				};
+			}
+			"--price" => {
+				let Some(value) = args.next() else {
+					usage_and_exit();
+				};
+				if value.trim().is_empty() {
+					usage_and_exit();
+				}
+				price = Some(value.trim().to_string());
+			}
+			"--metrics" => metrics = true,
+			"--models" => models = true,
+			"--show-range" => show_range = true,
+			"--cache-split" => cache_split = true,
+			"--verbose" => verbose = true,
+			"--watch" => {
+				// 秒数可省略：下一个参数不是数字时按默认间隔，并把它留给后续解析。
+				let secs = match args.peek().map(|value| value.trim().parse::<u64>()) {
+					Some(Ok(secs)) => {
+						args.next();
+						secs
+					}
+					_ => DEFAULT_WATCH_SECS,
+				};
+				if secs == 0 {
+					usage_and_exit();
+				}
+				watch = Some(secs);
+			}
 			"-h" | "--help" => usage_and_exit(),
 			_ => usage_and_exit(),
 		}
 	}
 
-	(period, source)
+	// `--format` 的取值分两组：`cx|cc` 描述 stdin 的格式，只能与 `--from-stdin` 一起用；
+	// `--from-stdin` 时也必须给出其中之一，且不能再要 ccusage 导出。
+	if from_stdin && ccusage.is_some() {
+		usage_and_exit();
+	}
+	if from_stdin != line_format.is_some() {
+		usage_and_exit();
+	}
+	let range_given = period_given || since.is_some() || until.is_some();
+
+	// 自定义区间必须同时给出 since/until，且 since <= until（闭区间，本地日期）。
+	let (range, period) = match (since, until) {
+		(None, None) => (range_for_period(period), Some(period)),
+		(Some(since), Some(until)) => match time_range::range_between(since, until) {
+			Some(range) => (range, None),
+			None => usage_and_exit(),
+		},
+		_ => usage_and_exit(),
+	};
+
+	Args {
+		range,
+		period,
+		source,
+		model,
+		coverage,
+		json,
+		ccusage,
+		status: status || status_log.is_some(),
+		status_log,
+		bench_pricing,
+		price,
+		metrics,
+		models,
+		show_range,
+		cache_split,
+		watch,
+		from_stdin: line_format,
+		range_given,
+		verbose,
+	}
 }
 
 fn range_for_period(period: Period) -> time_range::DateRange {
 	match period {
 		Period::Today => time_range::range_today(),
+		Period::Yesterday => time_range::range_yesterday(),
 		Period::Week => time_range::range_week_monday(),
+		Period::Last7d => time_range::range_last_n_days(7),
 		Period::Month => time_range::range_month(),
+		Period::Last30d => time_range::range_last_n_days(30),
 		Period::Year => time_range::range_year(),
 	}
 }
 
+fn print_model_totals(
+	model: &str,
+	source: Source,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	labels: SourceLabels<'_>,
+	cost: &CostFormat,
+	scan: &usage::ScanSettings,
+) {
+	let dataset = &pricing.dataset;
+	let cx = match source {
+		Source::Cx | Source::Both => usage::load_cx_breakdown_with_pricing(range, dataset, scan),
+		Source::Cc => Default::default(),
+	};
+	let cc = match source {
+		Source::Cc => match usage::load_cc_breakdown_with_pricing(range, dataset, scan) {
+			Ok(v) => v,
+			Err(err) => {
+				eprintln!("ERR: {err}");
+				std::process::exit(1);
+			}
+		},
+		Source::Both => {
+			usage::load_cc_breakdown_with_pricing(range, dataset, scan).unwrap_or_default()
+		}
+		Source::Cx => Default::default(),
+	};
+
+	let source_abbr = match source {
+		Source::Cx => labels.cx.to_string(),
+		Source::Cc => labels.cc.to_string(),
+		Source::Both => {
+			let (first, second) = labels.ordered(labels.cx, labels.cc);
+			format!("{first}+{second}")
+		}
+	};
+	let totals = usage::sum_model_in_breakdowns(&[&cx, &cc], model);
+	let source_label = format!("{model} {source_abbr}");
+	println!(
+		"{}",
+		format_single_title_raw(&range.label, &source_label, totals, pricing.available, cost)
+	);
+}
+
+fn print_pricing_coverage(
+	source: Source,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	scan: &usage::ScanSettings,
+) {
+	if !pricing.available {
+		eprintln!("ERR: pricing unavailable");
+		std::process::exit(1);
+	}
+	let dataset = &pricing.dataset;
+	let cx = match source {
+		Source::Cx | Source::Both => usage::load_cx_breakdown_with_pricing(range, dataset, scan),
+		Source::Cc => Default::default(),
+	};
+	let cc = match source {
+		Source::Cc | Source::Both => {
+			usage::load_cc_breakdown_with_pricing(range, dataset, scan).unwrap_or_default()
+		}
+		Source::Cx => Default::default(),
+	};
+
+	let coverage = usage::pricing_coverage(&cx, &cc, dataset, scan);
+	let observed = coverage.matched.len() + coverage.unmatched.len();
+	println!(
+		"{} pricing coverage: {}/{} models",
+		&range.label,
+		coverage.matched.len(),
+		observed
+	);
+	println!("matched: {}", coverage.matched.join(", "));
+	println!("unmatched: {}", coverage.unmatched.join(", "));
+}
+
+fn print_json_totals(
+	source: Source,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	scan: &usage::ScanSettings,
+) {
+	let dataset = &pricing.dataset;
+	let (source_name, cx, cc) = match source {
+		Source::Cx => ("cx", Some(usage::load_cx_totals_with_pricing(range, dataset, scan)), None),
+		Source::Cc => match usage::load_cc_totals_with_pricing(range, dataset, scan) {
+			Ok(totals) => ("cc", None, Some(Some(totals))),
+			Err(err) => {
+				eprintln!("ERR: {err}");
+				std::process::exit(1);
+			}
+		},
+		Source::Both => (
+			"both",
+			Some(usage::load_cx_totals_with_pricing(range, dataset, scan)),
+			Some(usage::load_cc_totals_with_pricing(range, dataset, scan).ok()),
+		),
+	};
+
+	let report = JsonReport {
+		period: &range.label,
+		source: source_name,
+		cost_available: pricing.available,
+		cx,
+		cc,
+	};
+	match serde_json::to_string(&report) {
+		Ok(body) => println!("{body}"),
+		Err(err) => {
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn print_ccusage_report(
+	format: CcusageFormat,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	scan: &usage::ScanSettings,
+) {
+	let days = match usage::load_cc_daily_with_pricing(range, &pricing.dataset, scan) {
+		Ok(v) => v,
+		Err(err) => {
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	};
+	let body = match format {
+		CcusageFormat::Daily => serde_json::to_string_pretty(&ccusage::daily_report(&days)),
+		CcusageFormat::Monthly => serde_json::to_string_pretty(&ccusage::monthly_report(&days)),
+	};
+	match body {
+		Ok(body) => println!("{body}"),
+		Err(err) => {
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn print_status(
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	log_path: Option<&str>,
+	scan: &usage::ScanSettings,
+) {
+	let snapshot = status::StatusSnapshot::collect(range, pricing, scan);
+	if let Some(path) = log_path {
+		let path = std::path::Path::new(path);
+		if let Err(err) =
+			status::append_snapshot(path, &snapshot, status::DEFAULT_STATUS_LOG_MAX_BYTES)
+		{
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	}
+	match serde_json::to_string_pretty(&snapshot) {
+		Ok(body) => println!("{body}"),
+		Err(err) => {
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn print_pricing_bench(pricing: &litellm::PricingContext, scan: &usage::ScanSettings) {
+	if !pricing.available {
+		eprintln!("ERR: pricing unavailable");
+		std::process::exit(1);
+	}
+	let timings = usage::time_pricing_lookups(
+		&BENCH_CX_MODELS,
+		&BENCH_CC_MODELS,
+		BENCH_ITERATIONS,
+		&pricing.dataset,
+		scan,
+	);
+
+	println!(
+		"pricing lookup bench: {} models in dataset, {} iterations per name",
+		pricing.dataset.len(),
+		BENCH_ITERATIONS
+	);
+	for timing in &timings {
+		println!(
+			"{} {:<28} {:<4} {:>10.2}µs",
+			timing.source,
+			timing.model,
+			if timing.found { "hit" } else { "miss" },
+			timing.per_lookup.as_secs_f64() * 1e6
+		);
+	}
+	for (label, found) in [("hit", true), ("miss", false)] {
+		let group: Vec<_> = timings.iter().filter(|t| t.found == found).collect();
+		if group.is_empty() {
+			continue;
+		}
+		let avg = group.iter().map(|t| t.per_lookup.as_secs_f64()).sum::<f64>() / group.len() as f64;
+		println!("{label} avg: {:.2}µs over {} names", avg * 1e6, group.len());
+	}
+}
+
+fn print_model_pricing(
+	model: &str,
+	source: Source,
+	pricing: &litellm::PricingContext,
+	scan: &usage::ScanSettings,
+) {
+	if !pricing.available {
+		eprintln!("ERR: pricing unavailable");
+		std::process::exit(1);
+	}
+	let dataset = &pricing.dataset;
+	match source {
+		Source::Cx => println!("{}", usage::describe_cx_pricing(model, dataset, scan)),
+		Source::Cc => println!("{}", usage::describe_cc_pricing(model, dataset, scan)),
+		Source::Both => {
+			println!("{}", usage::describe_cx_pricing(model, dataset, scan));
+			println!("{}", usage::describe_cc_pricing(model, dataset, scan));
+		}
+	}
+}
+
+fn print_metrics(pricing: &litellm::PricingContext, scan: &usage::ScanSettings) {
+	let snapshot = status::MetricsSnapshot::collect(pricing, scan);
+	match serde_json::to_string(&snapshot) {
+		Ok(body) => println!("{body}"),
+		Err(err) => {
+			eprintln!("ERR: {err}");
+			std::process::exit(1);
+		}
+	}
+}
+
+fn print_stdin_totals(
+	format: LineFormat,
+	range: Option<&time_range::DateRange>,
+	pricing: &litellm::PricingContext,
+	labels: SourceLabels<'_>,
+	cost: &CostFormat,
+	scan: &usage::ScanSettings,
+) {
+	let lines = std::io::stdin().lock().lines().map_while(Result::ok);
+	let dataset = &pricing.dataset;
+	let (source_abbr, breakdown) = match format {
+		LineFormat::Cx => (
+			labels.cx,
+			usage::load_cx_breakdown_from_lines_with_pricing(lines, range, dataset, scan),
+		),
+		LineFormat::Cc => (
+			labels.cc,
+			usage::load_cc_breakdown_from_lines_with_pricing(lines, range, dataset, scan),
+		),
+	};
+	let totals = usage::sum_breakdown(&breakdown);
+	let label = range.map_or("All", |range| range.label.as_str());
+	println!(
+		"{}",
+		format_single_title_raw(label, source_abbr, totals, pricing.available, cost)
+	);
+}
+
+fn print_model_history(source: Source, scan: &usage::ScanSettings) {
+	let cx = match source {
+		Source::Cx | Source::Both => usage::load_cx_model_history(scan),
+		Source::Cc => Default::default(),
+	};
+	let cc = match source {
+		Source::Cc => match usage::load_cc_model_history(scan) {
+			Ok(v) => v,
+			Err(err) => {
+				eprintln!("ERR: {err}");
+				std::process::exit(1);
+			}
+		},
+		Source::Both => usage::load_cc_model_history(scan).unwrap_or_default(),
+		Source::Cx => Default::default(),
+	};
+
+	for (abbr, history) in [("cx", cx), ("cc", cc)] {
+		for (model, entry) in usage::sorted_model_history(history) {
+			let last_used = entry
+				.last_used
+				.map(|date| date.format("%Y-%m-%d").to_string())
+				.unwrap_or_else(|| "-".to_string());
+			println!(
+				"{abbr} {model:<36} {:>16} tokens  last used {last_used}",
+				format_u64_with_commas(entry.total_tokens)
+			);
+		}
+	}
+}
+
+/// `--verbose`：把所选来源解析出的日志目录与参与统计的文件逐行输出到 stderr。
+fn print_scanned_files(source: Source, scan: &usage::ScanSettings) {
+	let print = |abbr: &str, scanned: usage::ScannedFiles| {
+		for dir in &scanned.base_dirs {
+			eprintln!("{abbr} dir: {}", dir.display());
+		}
+		for file in &scanned.files {
+			eprintln!("{abbr} file: {}", file.display());
+		}
+		eprintln!("{abbr}: {} files", scanned.files.len());
+	};
+	if matches!(source, Source::Cx | Source::Both) {
+		print("cx", usage::cx_scanned_files());
+	}
+	if matches!(source, Source::Cc | Source::Both) {
+		match usage::cc_scanned_files(scan) {
+			Ok(scanned) => print("cc", scanned),
+			Err(err) => eprintln!("cc: {err}"),
+		}
+	}
+}
+
+/// `--cache-split`：每个来源一行，总计后面附上缓存读取/写入的 token 数。
+fn print_cache_split(
+	source: Source,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	labels: SourceLabels<'_>,
+	cost: &CostFormat,
+	scan: &usage::ScanSettings,
+) {
+	let dataset = &pricing.dataset;
+	let line = |label: &str, totals| {
+		format_single_title_raw_with_cache(&range.label, label, totals, pricing.available, cost)
+	};
+	match source {
+		Source::Cx => {
+			let totals = usage::load_cx_totals_with_pricing(range, dataset, scan);
+			println!("{}", line(labels.cx, totals));
+		}
+		Source::Cc => match usage::load_cc_totals_with_pricing(range, dataset, scan) {
+			Ok(totals) => println!("{}", line(labels.cc, totals)),
+			Err(err) => {
+				eprintln!("ERR: {err}");
+				std::process::exit(1);
+			}
+		},
+		Source::Both => {
+			let cx = line(labels.cx, usage::load_cx_totals_with_pricing(range, dataset, scan));
+			let cc_totals =
+				usage::load_cc_totals_with_pricing(range, dataset, scan).unwrap_or_default();
+			let cc = line(labels.cc, cc_totals);
+			let (first, second) = labels.ordered(cx, cc);
+			println!("{first}\n{second}");
+		}
+	}
+}
+
+/// `--watch` 每轮输出的单行统计（both 用菜单里的单行双列形式，才能原地重绘）。
+fn watch_line(
+	source: Source,
+	range: &time_range::DateRange,
+	pricing: &litellm::PricingContext,
+	labels: SourceLabels<'_>,
+	cost: &CostFormat,
+	scan: &usage::ScanSettings,
+) -> String {
+	let dataset = &pricing.dataset;
+	let show_cost = pricing.available;
+	let period = range.label.as_str();
+	match source {
+		Source::Cx => {
+			let totals = usage::load_cx_totals_with_pricing(range, dataset, scan);
+			format_single_title_raw(period, labels.cx, totals, show_cost, cost)
+		}
+		Source::Cc => match usage::load_cc_totals_with_pricing(range, dataset, scan) {
+			Ok(totals) => format_single_title_raw(period, labels.cc, totals, show_cost, cost),
+			Err(err) => format!("{period} cc ERR: {err}"),
+		},
+		Source::Both => {
+			let cx = usage::load_cx_totals_with_pricing(range, dataset, scan);
+			let cc = usage::load_cc_totals_with_pricing(range, dataset, scan).unwrap_or_default();
+			format_both_menu_line(period, labels, cx, cc, show_cost, cost)
+		}
+	}
+}
+
+/// `--watch`：每 `interval_secs` 秒重绘一次统计，直到 Ctrl-C。
+///
+/// 说明：
+/// - 价格上下文每轮都取，但它自带 TTL 缓存，只有过期时才会真正重新拉取；
+/// - 终端里用 `\r` + 清行原地重绘，不隐藏光标；Ctrl-C 时先换行再以 0 退出，
+///   shell 提示符不会接在统计行后面。输出被重定向时改为每轮一行；
+/// - 预设周期每轮重算区间，跨过零点后 Today 会自动切到新的一天。
+fn watch_totals(
+	source: Source,
+	period: Option<Period>,
+	range: time_range::DateRange,
+	interval_secs: u64,
+	labels: SourceLabels<'_>,
+	cost: &CostFormat,
+	scan: &usage::ScanSettings,
+) -> ! {
+	let mut stdout = std::io::stdout();
+	let redraw = stdout.is_terminal();
+	// 注册失败时保留默认的 Ctrl-C 行为（直接终止），不影响统计本身。
+	let _ = ctrlc::set_handler(move || {
+		if redraw {
+			println!();
+		}
+		std::process::exit(0);
+	});
+	loop {
+		let range = period.map_or_else(|| range.clone(), range_for_period);
+		let pricing = litellm::get_pricing_context();
+		let line = watch_line(source, &range, &pricing, labels, cost, scan);
+		let _ = if redraw {
+			write!(stdout, "\r\x1b[2K{line}")
+		} else {
+			writeln!(stdout, "{line}")
+		};
+		let _ = stdout.flush();
+		std::thread::sleep(Duration::from_secs(interval_secs));
+	}
+}
+
 fn main() {
-	let (period, source) = parse_args();
-	let range = range_for_period(period);
-	let period_label = range.label;
+	if let Err(err) = time_range::check_tz_override() {
+		eprintln!("ERR: {err}");
+		std::process::exit(1);
+	}
+	// “今天”的划分要在解析 `--period` 之前确定。
+	usage::apply_day_anchor_from_settings();
+	let settings = app_settings::load_settings();
+	let scan = settings.scan_settings();
+	let cost = settings.cost_format();
+	let labels = settings.source_labels();
+	let Args {
+		range,
+		period,
+		source,
+		model,
+		coverage,
+		json,
+		ccusage,
+		status,
+		status_log,
+		bench_pricing,
+		price,
+		metrics,
+		models,
+		show_range,
+		cache_split,
+		watch,
+		from_stdin,
+		range_given,
+		verbose,
+	} = parse_args();
+	if show_range {
+		println!("{}", time_range::describe_range(&range));
+		return;
+	}
+	// `--from-stdin` 不扫描本机日志，没有文件可列。
+	if verbose && from_stdin.is_none() {
+		print_scanned_files(source, &scan);
+	}
+	if let Some(interval_secs) = watch {
+		watch_totals(source, period, range, interval_secs, labels, &cost, &scan);
+	}
+	let period_label = range.label.as_str();
 	let pricing = litellm::get_pricing_context();
 	let show_cost = pricing.available;
 	let dataset = &pricing.dataset;
 
+	if let Some(format) = from_stdin {
+		let range = range_given.then_some(&range);
+		print_stdin_totals(format, range, &pricing, labels, &cost, &scan);
+		return;
+	}
+
+	if bench_pricing {
+		print_pricing_bench(&pricing, &scan);
+		return;
+	}
+
+	if let Some(model) = price {
+		print_model_pricing(&model, source, &pricing, &scan);
+		return;
+	}
+
+	if metrics {
+		print_metrics(&pricing, &scan);
+		return;
+	}
+
+	if models {
+		print_model_history(source, &scan);
+		return;
+	}
+
+	if status {
+		print_status(&range, &pricing, status_log.as_deref(), &scan);
+		return;
+	}
+
+	if coverage {
+		print_pricing_coverage(source, &range, &pricing, &scan);
+		return;
+	}
+
+	if let Some(model) = model {
+		print_model_totals(&model, source, &range, &pricing, labels, &cost, &scan);
+		return;
+	}
+
+	if let Some(format) = ccusage {
+		print_ccusage_report(format, &range, &pricing, &scan);
+		return;
+	}
+
+	if json {
+		print_json_totals(source, &range, &pricing, &scan);
+		return;
+	}
+
+	if cache_split {
+		print_cache_split(source, &range, &pricing, labels, &cost, &scan);
+		return;
+	}
+
 	match source {
 		Source::Cx => {
-			let totals = usage::load_cx_totals_with_pricing(&range, dataset);
-			println!("{}", format_single_title_raw(period_label, "cx", totals, show_cost));
+			let totals = usage::load_cx_totals_with_pricing(&range, dataset, &scan);
+			let line = format_single_title_raw(period_label, labels.cx, totals, show_cost, &cost);
+			println!("{line}");
 		}
-		Source::Cc => match usage::load_cc_totals_with_pricing(&range, dataset) {
-			Ok(totals) => println!("{}", format_single_title_raw(period_label, "cc", totals, show_cost)),
+		Source::Cc => match usage::load_cc_totals_with_pricing(&range, dataset, &scan) {
+			Ok(totals) => {
+				let line =
+					format_single_title_raw(period_label, labels.cc, totals, show_cost, &cost);
+				println!("{line}");
+			}
 			Err(err) => {
 				eprintln!("ERR: {err}");
 				std::process::exit(1);
 			}
 		},
 		Source::Both => {
-			let cx = usage::load_cx_totals_with_pricing(&range, dataset);
-			let cc = usage::load_cc_totals_with_pricing(&range, dataset).unwrap_or_default();
-			println!("{}", format_both_title_raw(period_label, cx, cc, show_cost));
+			let cx = usage::load_cx_totals_with_pricing(&range, dataset, &scan);
+			let cc = usage::load_cc_totals_with_pricing(&range, dataset, &scan).unwrap_or_default();
+			println!("{}", format_both_title_raw(period_label, labels, cx, cc, show_cost, &cost));
 		}
 	}
 }
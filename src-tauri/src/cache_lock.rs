@@ -0,0 +1,38 @@
+//! 进程内缓存的取锁方式：托盘每次刷新都会经过这些锁，某次计算中途 panic 不能让它们永久失效。
+
+use std::sync::{Mutex, MutexGuard};
+
+/// 取缓存锁；锁中毒（持锁时 panic）时把缓存重置为空并清除中毒标记，
+/// 下一次访问按“未缓存”重新计算，而不是沿用写到一半的内容或跟着 panic。
+pub(crate) fn lock_or_reset<T: Default>(cache: &Mutex<T>) -> MutexGuard<'_, T> {
+	match cache.lock() {
+		Ok(guard) => guard,
+		Err(poisoned) => {
+			cache.clear_poison();
+			let mut guard = poisoned.into_inner();
+			*guard = T::default();
+			guard
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn poisoned_cache_is_reset_and_usable_again() {
+		let cache = Mutex::new(vec![1, 2, 3]);
+		let _ = std::panic::catch_unwind(|| {
+			let mut guard = cache.lock().expect("lock");
+			guard.push(4);
+			panic!("half-written cache");
+		});
+		assert!(cache.is_poisoned());
+
+		assert!(lock_or_reset(&cache).is_empty());
+		assert!(!cache.is_poisoned());
+		lock_or_reset(&cache).push(5);
+		assert_eq!(*lock_or_reset(&cache), vec![5]);
+	}
+}
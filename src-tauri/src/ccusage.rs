@@ -0,0 +1,196 @@
+//! 与 ccusage（`ccusage daily --json` / `ccusage monthly --json`）输出结构兼容的导出，便于直接 diff 对账。
+//!
+//! 字段对应关系（ccusage ← tokbar）：
+//! - `date` / `month` ← 本地日期 `YYYY-MM-DD` / `YYYY-MM`
+//! - `inputTokens` ← `input_tokens`
+//! - `outputTokens` ← `output_tokens`
+//! - `cacheCreationTokens` ← `cache_creation_input_tokens`
+//! - `cacheReadTokens` ← `cache_read_input_tokens`
+//! - `totalTokens` ← 以上四项之和
+//! - `totalCost` ← 日志自带的 `costUSD`，否则按 LiteLLM 价格计算（价格不可用时为 0）
+//! - `modelsUsed` ← 当天/当月出现过的 `message.model`（排序去重）
+//!
+//! 说明：ccusage 只统计 Claude Code，因此这里也只导出 cc 数据；不输出 `modelBreakdowns`。
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::claude::ClaudeDailyUsage;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CcusageTotals {
+	pub input_tokens: u64,
+	pub output_tokens: u64,
+	pub cache_creation_tokens: u64,
+	pub cache_read_tokens: u64,
+	pub total_tokens: u64,
+	pub total_cost: f64,
+}
+
+impl CcusageTotals {
+	fn add(&mut self, usage: &ClaudeDailyUsage) {
+		self.input_tokens += usage.input_tokens;
+		self.output_tokens += usage.output_tokens;
+		self.cache_creation_tokens += usage.cache_creation_tokens;
+		self.cache_read_tokens += usage.cache_read_tokens;
+		self.total_tokens += usage.total_tokens();
+		self.total_cost += usage.cost_usd;
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CcusageDailyRow {
+	pub date: String,
+	#[serde(flatten)]
+	pub totals: CcusageTotals,
+	pub models_used: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CcusageMonthlyRow {
+	pub month: String,
+	#[serde(flatten)]
+	pub totals: CcusageTotals,
+	pub models_used: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CcusageDailyReport {
+	pub daily: Vec<CcusageDailyRow>,
+	pub totals: CcusageTotals,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CcusageMonthlyReport {
+	pub monthly: Vec<CcusageMonthlyRow>,
+	pub totals: CcusageTotals,
+}
+
+pub fn daily_report(days: &BTreeMap<NaiveDate, ClaudeDailyUsage>) -> CcusageDailyReport {
+	let mut totals = CcusageTotals::default();
+	let daily = days
+		.iter()
+		.map(|(date, usage)| {
+			totals.add(usage);
+			let mut row_totals = CcusageTotals::default();
+			row_totals.add(usage);
+			CcusageDailyRow {
+				date: date.format("%Y-%m-%d").to_string(),
+				totals: row_totals,
+				models_used: usage.models.iter().cloned().collect(),
+			}
+		})
+		.collect();
+	CcusageDailyReport { daily, totals }
+}
+
+pub fn monthly_report(days: &BTreeMap<NaiveDate, ClaudeDailyUsage>) -> CcusageMonthlyReport {
+	let mut totals = CcusageTotals::default();
+	let mut months: BTreeMap<String, (CcusageTotals, BTreeSet<String>)> = BTreeMap::new();
+	for (date, usage) in days {
+		totals.add(usage);
+		let (month_totals, models) = months.entry(date.format("%Y-%m").to_string()).or_default();
+		month_totals.add(usage);
+		models.extend(usage.models.iter().cloned());
+	}
+
+	let monthly = months
+		.into_iter()
+		.map(|(month, (totals, models))| CcusageMonthlyRow {
+			month,
+			totals,
+			models_used: models.into_iter().collect(),
+		})
+		.collect();
+	CcusageMonthlyReport { monthly, totals }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util;
+	use crate::claude::{load_claude_daily_from_files_with_pricing, ScanOptions};
+	use chrono::{Local, TimeZone};
+	use std::collections::HashMap;
+
+	#[test]
+	fn ccusage_reports_have_expected_keys() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file = tmp.path().join("session.jsonl");
+		let at = |day: u32| {
+			Local
+				.with_ymd_and_hms(2026, 2, day, 12, 0, 0)
+				.single()
+				.expect("local dt")
+				.to_rfc3339()
+		};
+		let lines = [
+			serde_json::json!({
+				"timestamp": at(5),
+				"requestId": "r1",
+				"costUSD": 0.5,
+				"message": {
+					"id": "m1",
+					"model": "claude-sonnet-4",
+					"usage": {
+						"input_tokens": 10,
+						"output_tokens": 20,
+						"cache_creation_input_tokens": 30,
+						"cache_read_input_tokens": 40
+					}
+				}
+			}),
+			serde_json::json!({
+				"timestamp": at(6),
+				"requestId": "r2",
+				"costUSD": 0.25,
+				"message": {
+					"id": "m2",
+					"model": "claude-opus-4",
+					"usage": { "input_tokens": 1, "output_tokens": 2 }
+				}
+			}),
+		];
+		std::fs::write(
+			&file,
+			lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write");
+
+		let range = test_util::span_range("20260201", "20260228", "Month");
+		let options = ScanOptions::default();
+		let days =
+			load_claude_daily_from_files_with_pricing(&[file], &range, &HashMap::new(), &options);
+
+		let daily = serde_json::to_value(daily_report(&days)).expect("daily json");
+		let first = &daily["daily"][0];
+		assert_eq!(first["date"], "2026-02-05");
+		for key in [
+			"inputTokens",
+			"outputTokens",
+			"cacheCreationTokens",
+			"cacheReadTokens",
+			"totalTokens",
+			"totalCost",
+			"modelsUsed",
+		] {
+			assert!(first.get(key).is_some(), "daily row missing {key}");
+		}
+		assert_eq!(first["totalTokens"], 100);
+		assert_eq!(daily["totals"]["totalTokens"], 103);
+		assert_eq!(daily["totals"]["totalCost"], 0.75);
+
+		let monthly = serde_json::to_value(monthly_report(&days)).expect("monthly json");
+		assert_eq!(monthly["monthly"][0]["month"], "2026-02");
+		assert_eq!(
+			monthly["monthly"][0]["modelsUsed"],
+			serde_json::json!(["claude-opus-4", "claude-sonnet-4"])
+		);
+		assert_eq!(monthly["totals"]["cacheReadTokens"], 40);
+	}
+}
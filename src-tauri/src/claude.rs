@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
@@ -6,15 +6,20 @@ use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
 use chrono::{NaiveDate};
-use glob::glob;
+use glob::{glob, Pattern};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::cache_lock;
 use crate::pricing::{
-	calculate_claude_cost_from_pricing, find_model_pricing, ClaudeTokens, LiteLLMModelPricing,
+	calculate_claude_cost_from_pricing, dataset_fingerprint, find_model_pricing,
+	find_model_pricing_with_key, ClaudeTokens, LiteLLMModelPricing,
 };
-use crate::time_parse::parse_js_timestamp;
+use crate::offset_cache::{self, FileCheckpoint, FileStamp};
+use crate::parallel;
+use crate::time_parse::{parse_js_timestamp, BusinessHours};
 use crate::time_range::DateRange;
-use crate::usage::UsageTotals;
+use crate::usage::{LineOutcome, ModelHistory, ScanDiagnostics, UsageTotals};
 
 const CLAUDE_PROVIDER_PREFIXES: [&str; 7] = [
 	"anthropic/",
@@ -26,12 +31,47 @@ const CLAUDE_PROVIDER_PREFIXES: [&str; 7] = [
 	"openrouter/openai/",
 ];
 const CLAUDE_FILES_TTL: Duration = Duration::from_secs(60 * 5);
+/// Claude 数据目录下默认扫描的日志子目录：`projects`，以及新版 Claude Code 写入的 `history`。
+pub(crate) const DEFAULT_LOG_SUBDIRS: [&str; 2] = ["projects", "history"];
+/// 每批并行解析的文件数：解析完一批就去重合并，避免全量扫描时所有条目同时留在内存里。
+const PARSE_BATCH_FILES: usize = 256;
+
+/// 扫描时使用的过滤选项（调用方按设置构造一次后传入，测试里可直接构造）。
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+	pub business_hours: BusinessHours,
+	/// 不参与统计的项目目录名（`projects/` 下的目录名）。
+	pub exclude_projects: Vec<String>,
+	/// base dir 下要扫描的日志子目录；为空时用 [`DEFAULT_LOG_SUBDIRS`]。
+	pub log_subdirs: Vec<String>,
+	/// 计价时只做精确/前缀匹配，不做子串兜底。
+	pub strict_model_matching: bool,
+}
+
+impl ScanOptions {
+	/// `log_subdirs` 去掉空白项；一个有效值都没有时用 [`DEFAULT_LOG_SUBDIRS`]。
+	fn log_subdirs(&self) -> Vec<String> {
+		let configured: Vec<String> = self
+			.log_subdirs
+			.iter()
+			.map(|dir| dir.trim().to_string())
+			.filter(|dir| !dir.is_empty())
+			.collect();
+		if configured.is_empty() {
+			return DEFAULT_LOG_SUBDIRS.iter().map(|dir| dir.to_string()).collect();
+		}
+		configured
+	}
+}
 
 #[derive(Debug, Default)]
 struct ClaudeFilesCache {
 	base_dirs: Vec<PathBuf>,
+	subdirs: Vec<String>,
 	scanned_at: Option<Instant>,
 	files: Vec<PathBuf>,
+	/// 上次扫描中 glob 出错（模式非法或目录读不了）的日志目录。
+	failed_dirs: Vec<PathBuf>,
 }
 
 static CLAUDE_FILES_CACHE: OnceLock<Mutex<ClaudeFilesCache>> = OnceLock::new();
@@ -97,6 +137,9 @@ struct ClaudeUsageEntry {
 	timestamp: String,
 	message_id: Option<String>,
 	request_id: Option<String>,
+	uuid: Option<String>,
+	/// Batch API 结果行的标识（`batch_id/custom_id` 或仅 `custom_id`）；非 batch 行为 None。
+	batch_key: Option<String>,
 	model: Option<String>,
 	input_tokens: u64,
 	output_tokens: u64,
@@ -108,7 +151,12 @@ struct ClaudeUsageEntry {
 fn parse_usage_entry(value: &Value) -> Option<ClaudeUsageEntry> {
 	let timestamp = as_non_empty_string(value.get("timestamp"))?;
 
-	let message = value.get("message")?.as_object()?;
+	// 兼容 Batch API 结果行：`{"custom_id": ..., "result": {"type": "succeeded", "message": {...}}}`。
+	let batch_result = value.get("result").and_then(|v| v.as_object());
+	let message = value
+		.get("message")
+		.or_else(|| batch_result.and_then(|r| r.get("message")))?
+		.as_object()?;
 
 	// 说明：
 	// - Claude Code 的 usage 形态可能随“接入不同提供商模型”而变化。
@@ -128,6 +176,13 @@ fn parse_usage_entry(value: &Value) -> Option<ClaudeUsageEntry> {
 
 	let message_id = as_non_empty_string(message.get("id"));
 	let request_id = as_non_empty_string(value.get("requestId"));
+	let uuid = as_non_empty_string(value.get("uuid")).or_else(|| as_non_empty_string(value.get("leafUuid")));
+	let batch_key = batch_result.and(as_non_empty_string(value.get("custom_id"))).map(|custom_id| {
+		match as_non_empty_string(value.get("batch_id")) {
+			Some(batch_id) => format!("{batch_id}/{custom_id}"),
+			None => custom_id,
+		}
+	});
 	let model = as_non_empty_string(message.get("model")).or_else(|| as_non_empty_string(value.get("model")));
 	let cost_usd = as_f64(value.get("costUSD"));
 
@@ -135,6 +190,8 @@ fn parse_usage_entry(value: &Value) -> Option<ClaudeUsageEntry> {
 		timestamp,
 		message_id,
 		request_id,
+		uuid,
+		batch_key,
 		model,
 		input_tokens,
 		output_tokens,
@@ -153,12 +210,25 @@ fn first_u64_token(usage: &serde_json::Map<String, Value>, keys: &[&str]) -> Opt
 	None
 }
 
+/// 生成去重 key：按优先级依次尝试 `message.id + requestId`、`message.id + batch custom_id`、
+/// `message.id + uuid/leafUuid`。
+///
+/// 说明：
+/// - 缺少 requestId 的条目在重复读取（例如会话恢复后被复制到新文件）时会被重复计数；
+///   uuid 对同一条日志是稳定的，因此可作为次优的去重依据。
+/// - 不同组合使用不同前缀，避免 requestId 与 uuid 恰好同值时互相误判为重复。
 fn unique_hash(entry: &ClaudeUsageEntry) -> Option<String> {
-	Some(format!(
-		"{}:{}",
-		entry.message_id.as_deref()?,
-		entry.request_id.as_deref()?
-	))
+	let message_id = entry.message_id.as_deref()?;
+	if let Some(request_id) = entry.request_id.as_deref() {
+		return Some(format!("{message_id}:{request_id}"));
+	}
+	if let Some(batch_key) = entry.batch_key.as_deref() {
+		return Some(format!("{message_id}:batch:{batch_key}"));
+	}
+	if let Some(uuid) = entry.uuid.as_deref() {
+		return Some(format!("{message_id}:uuid:{uuid}"));
+	}
+	None
 }
 
 fn earliest_timestamp_millis(file_path: &Path) -> Option<i64> {
@@ -189,14 +259,9 @@ fn earliest_timestamp_millis(file_path: &Path) -> Option<i64> {
 }
 
 fn sort_files_by_timestamp(files: &[PathBuf]) -> Vec<PathBuf> {
-	let mut enriched: Vec<(PathBuf, Option<i64>)> = files
-		.iter()
-		.cloned()
-		.map(|path| {
-			let ts = earliest_timestamp_millis(&path);
-			(path, ts)
-		})
-		.collect();
+	let earliest = parallel::map_files(files, |path| earliest_timestamp_millis(path));
+	let mut enriched: Vec<(PathBuf, Option<i64>)> =
+		files.iter().cloned().zip(earliest).collect();
 
 	enriched.sort_by(|a, b| match (a.1, b.1) {
 		(None, None) => std::cmp::Ordering::Equal,
@@ -208,16 +273,81 @@ fn sort_files_by_timestamp(files: &[PathBuf]) -> Vec<PathBuf> {
 	enriched.into_iter().map(|(path, _)| path).collect()
 }
 
-pub fn usage_files_from_claude_base_dirs(base_dirs: &[PathBuf]) -> Vec<PathBuf> {
+/// 让下一次 [`usage_files_from_claude_base_dirs`] 重新扫描目录（例如新增了项目目录之后）。
+pub(crate) fn invalidate_usage_files_cache() {
+	cache_lock::lock_or_reset(claude_files_cache())
+		.scanned_at = None;
+}
+
+/// 解析符号链接/`..` 后的真实路径；解析失败（例如路径不存在）时原样返回。
+fn canonical_or_self(path: &Path) -> PathBuf {
+	std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// 日志文件所属的项目目录名（`<base>/projects/<项目>/...` 里的 `<项目>`）。
+fn project_dir_name(path: &Path) -> Option<&str> {
+	let mut components = path.components().map(|c| c.as_os_str());
+	let mut project = None;
+	while let Some(component) = components.next() {
+		if component == "projects" {
+			project = components.next();
+		}
+	}
+	project?.to_str()
+}
+
+fn is_excluded_project(path: &Path, exclude_projects: &[String]) -> bool {
+	project_dir_name(path)
+		.is_some_and(|name| exclude_projects.iter().any(|excluded| excluded.trim() == name))
+}
+
+/// 去掉属于排除项目的日志文件（在解析之前按路径过滤，比逐条过滤便宜）。
+fn exclude_project_files(files: Vec<PathBuf>, exclude_projects: &[String]) -> Vec<PathBuf> {
+	if exclude_projects.iter().all(|name| name.trim().is_empty()) {
+		return files;
+	}
+	files
+		.into_iter()
+		.filter(|path| !is_excluded_project(path, exclude_projects))
+		.collect()
+}
+
+/// 是否是要统计的日志文件：`*.jsonl`，且不属于 `options.exclude_projects` 排除的项目。
+pub(crate) fn is_usage_file(path: &Path, options: &ScanOptions) -> bool {
+	path.extension().is_some_and(|ext| ext == "jsonl")
+		&& !is_excluded_project(path, &options.exclude_projects)
+}
+
+/// base dir 下的全部日志文件，去掉 `options.exclude_projects` 排除的项目。
+pub fn usage_files_from_claude_base_dirs(
+	base_dirs: &[PathBuf],
+	options: &ScanOptions,
+) -> Vec<PathBuf> {
+	let files = usage_files_in_subdirs(base_dirs, &options.log_subdirs());
+	exclude_project_files(files, &options.exclude_projects)
+}
+
+/// 各 base dir 下实际存在的日志子目录（监听日志变更用）。
+pub(crate) fn claude_log_dirs(base_dirs: &[PathBuf], options: &ScanOptions) -> Vec<PathBuf> {
+	let subdirs = options.log_subdirs();
+	base_dirs
+		.iter()
+		.flat_map(|base| subdirs.iter().map(move |sub| base.join(sub)))
+		.filter(|dir| dir.is_dir())
+		.collect()
+}
+
+/// 扫描每个 base dir 下各子目录里的 `**/*.jsonl`。
+///
+/// 说明：同一条记录同时出现在多个子目录时由按 hash 去重兜底，这里只保证同一个文件不被扫两次。
+fn usage_files_in_subdirs(base_dirs: &[PathBuf], subdirs: &[String]) -> Vec<PathBuf> {
 	if base_dirs.is_empty() {
 		return Vec::new();
 	}
 
 	{
-		let guard = claude_files_cache()
-			.lock()
-			.expect("claude_files_cache lock poisoned");
-		if guard.base_dirs == base_dirs {
+		let guard = cache_lock::lock_or_reset(claude_files_cache());
+		if guard.base_dirs == base_dirs && guard.subdirs == subdirs {
 			if let Some(scanned_at) = guard.scanned_at {
 				if Instant::now().duration_since(scanned_at) < CLAUDE_FILES_TTL {
 					return guard.files.clone();
@@ -226,205 +356,695 @@ pub fn usage_files_from_claude_base_dirs(base_dirs: &[PathBuf]) -> Vec<PathBuf>
 		}
 	}
 
+	// 多个 base dir 可能是彼此的符号链接/拷贝路径：按真实路径去重，避免同一文件被扫描两次
+	// （缺少 id 的条目无法靠 hash 去重，会被重复计数）。
+	let mut seen_dirs = HashSet::<PathBuf>::new();
+	let mut seen_files = HashSet::<PathBuf>::new();
 	let mut files = Vec::new();
+	let mut failed_dirs = Vec::new();
 	for base_dir in base_dirs {
-		let pattern = base_dir
-			.join("projects")
-			.join("**")
-			.join("*.jsonl")
-			.to_string_lossy()
-			.to_string();
-		for entry in glob(&pattern).unwrap_or_else(|_| glob("").expect("glob fallback failed")) {
-			if let Ok(path) = entry {
-				files.push(path);
+		if !seen_dirs.insert(canonical_or_self(base_dir)) {
+			continue;
+		}
+		for subdir in subdirs {
+			let dir = base_dir.join(subdir);
+			// 目录名里的 `[`、`*` 等要转义，否则会被当成 glob 语法。
+			let escaped = PathBuf::from(Pattern::escape(&dir.to_string_lossy()));
+			let pattern = escaped.join("**").join("*.jsonl").to_string_lossy().to_string();
+			let Ok(paths) = glob(&pattern) else {
+				failed_dirs.push(dir);
+				continue;
+			};
+			let mut failed = false;
+			for entry in paths {
+				match entry {
+					Ok(path) => {
+						if seen_files.insert(canonical_or_self(&path)) {
+							files.push(path);
+						}
+					}
+					Err(_) => failed = true,
+				}
+			}
+			if failed {
+				failed_dirs.push(dir);
 			}
 		}
 	}
 
 	{
-		let mut guard = claude_files_cache()
-			.lock()
-			.expect("claude_files_cache lock poisoned");
+		let mut guard = cache_lock::lock_or_reset(claude_files_cache());
 		guard.base_dirs = base_dirs.to_vec();
+		guard.subdirs = subdirs.to_vec();
 		guard.scanned_at = Some(Instant::now());
 		guard.files = files.clone();
+		guard.failed_dirs = failed_dirs;
 	}
 	files
 }
 
-pub fn load_claude_totals_from_files_with_pricing(
+/// 最近一次扫描日志文件时失败的目录；为空表示“没数据”就是真的没数据。
+pub(crate) fn usage_scan_failures() -> Vec<PathBuf> {
+	cache_lock::lock_or_reset(claude_files_cache())
+		.failed_dirs
+		.clone()
+}
+
+/// 没有模型名的条目在按模型拆分时归入该 key。
+pub const UNKNOWN_MODEL_KEY: &str = "unknown";
+
+/// 该模型能否在价格表里找到（与计费时的匹配规则一致；`unknown` 永远视为未匹配）。
+pub(crate) fn has_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
+) -> bool {
+	resolve_pricing(model, dataset, strict).is_some()
+}
+
+/// 按 Claude 的计费规则（前缀）解析价格，并返回命中的价格表 key；`unknown` 永远不匹配。
+pub(crate) fn resolve_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
+) -> Option<(String, LiteLLMModelPricing)> {
+	if model == UNKNOWN_MODEL_KEY {
+		return None;
+	}
+	find_model_pricing_with_key(dataset, model, &CLAUDE_PROVIDER_PREFIXES, strict)
+}
+
+/// 遍历 Claude 日志文件中去重后的 usage 条目，并把每条的成本一起交给 `visit`。
+///
+/// 说明：
+/// - 去重（`unique_hash`）在整个文件集合上全局生效，因此同一条目只会计入一次、只归属一个模型。
+/// - 有日期范围时先按文件最早时间戳排序，保证重复条目总是保留“最早出现”的那一份；
+///   all-time 不排序（排序需要整读每个文件，对全量扫描代价过高）。
+fn visit_claude_entries(
 	files: &[PathBuf],
-	range: &DateRange,
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
-) -> UsageTotals {
-	let Some(since) = parse_yyyymmdd(&range.since_yyyymmdd) else {
-		return UsageTotals::default();
+	mut visit: impl FnMut(&Path, ClaudeUsageEntry, f64),
+) {
+	let mut processed_hashes: HashSet<String> = HashSet::new();
+
+	let sorted_files;
+	let files = if bounds.is_some() {
+		sorted_files = sort_files_by_timestamp(files);
+		sorted_files.as_slice()
+	} else {
+		files
 	};
-	let Some(until) = parse_yyyymmdd(&range.until_yyyymmdd) else {
-		return UsageTotals::default();
+
+	// 解析/计价在多个线程里按文件并行；去重必须按上面确定的文件顺序串行进行，
+	// 这样保留下来的总是同一份条目，结果与逐个文件顺序扫描一致。
+	for batch in files.chunks(PARSE_BATCH_FILES) {
+		let parsed = parallel::map_files(batch, |path| {
+			parse_claude_file(path, bounds, options, dataset)
+		});
+		for (path, entries) in batch.iter().zip(parsed) {
+			for (entry, cost_usd) in entries {
+				if let Some(hash) = unique_hash(&entry) {
+					if !processed_hashes.insert(hash) {
+						continue;
+					}
+				}
+				visit(path, entry, cost_usd);
+			}
+		}
+	}
+}
+
+/// 解析单个日志文件里通过日期/工作时间过滤的条目，并算好每条的成本（尚未去重）。
+fn parse_claude_file(
+	file_path: &Path,
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> Vec<(ClaudeUsageEntry, f64)> {
+	let Ok(file) = File::open(file_path) else {
+		return Vec::new();
 	};
+	let reader = BufReader::new(file);
+	reader
+		.lines()
+		.flatten()
+		.filter_map(|line| parse_claude_line(&line, bounds, options, dataset))
+		.collect()
+}
 
-	let mut processed_hashes: HashSet<String> = HashSet::new();
-	let mut totals = UsageTotals::default();
+/// 解析一行日志：不是 usage 条目或被日期/工作时间过滤掉时返回 None。
+fn parse_claude_line(
+	line: &str,
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> Option<(ClaudeUsageEntry, f64)> {
+	classify_claude_line(line, bounds, options, dataset).ok()
+}
 
-	let sorted_files = sort_files_by_timestamp(files);
-	for file_path in &sorted_files {
-		let Ok(file) = File::open(file_path) else {
-			continue;
+/// 同 [`parse_claude_line`]，但跳过时说明原因（诊断用）。
+fn classify_claude_line(
+	line: &str,
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> Result<(ClaudeUsageEntry, f64), LineOutcome> {
+	let trimmed = line.trim();
+	if trimmed.is_empty() || !trimmed.contains("\"usage\"") {
+		return Err(LineOutcome::Ignored);
+	}
+
+	let value = serde_json::from_str::<Value>(trimmed).map_err(|_| LineOutcome::Unparseable)?;
+	let entry = parse_usage_entry(&value).ok_or(LineOutcome::Ignored)?;
+
+	if let Some((since, until)) = bounds {
+		if !date_in_range_local(&entry.timestamp, since, until) {
+			return Err(LineOutcome::OutOfRange);
+		}
+	}
+	if !options.business_hours.allows_timestamp(Some(&entry.timestamp)) {
+		return Err(LineOutcome::OutOfRange);
+	}
+
+	let cost_usd = entry_cost_usd(&entry, dataset, options.strict_model_matching);
+	Ok((entry, cost_usd))
+}
+
+/// 条目自带 `costUSD` 时直接使用，否则按模型价格计算（找不到价格时为 0）。
+fn entry_cost_usd(
+	entry: &ClaudeUsageEntry,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
+) -> f64 {
+	if let Some(cost) = entry.cost_usd {
+		return cost;
+	}
+	let Some(model) = entry.model.as_deref() else {
+		return 0.0;
+	};
+	let Some(mut pricing) = find_model_pricing(dataset, model, &CLAUDE_PROVIDER_PREFIXES, strict)
+	else {
+		return 0.0;
+	};
+	if entry.batch_key.is_some() {
+		pricing = pricing.for_batch();
+	}
+	calculate_claude_cost_from_pricing(
+		ClaudeTokens {
+			input_tokens: entry.input_tokens,
+			output_tokens: entry.output_tokens,
+			cache_creation_input_tokens: entry.cache_creation_input_tokens,
+			cache_read_input_tokens: entry.cache_read_input_tokens,
+		},
+		&pricing,
+	)
+}
+
+/// “全部”增量缓存的文件名（`~/.tokbar/cache/claude-all-time.json`）。
+const OFFSET_CACHE_NAME: &str = "claude-all-time";
+
+/// 增量缓存里单个日志文件的状态。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CachedClaudeFile {
+	checkpoint: FileCheckpoint,
+	/// 已解析部分里（全局去重后）计入的合计。
+	totals: UsageTotals,
+	/// 已解析部分里计入的条目的去重 key（FNV 指纹）；续读时据此继续全局去重。
+	hashes: Vec<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ClaudeOffsetCache {
+	version: u32,
+	/// 工作时间设置与价格表内容的指纹：成本是逐条计算的，价格变了缓存就得作废。
+	key: u64,
+	files: HashMap<PathBuf, CachedClaudeFile>,
+}
+
+pub(crate) fn all_time_offset_cache_path() -> Option<PathBuf> {
+	offset_cache::default_cache_path(OFFSET_CACHE_NAME)
+}
+
+fn offset_cache_key(
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> u64 {
+	let mut material = format!("{:?}\n", options.business_hours);
+	// 只在开启时写入，旧缓存在默认设置下仍然有效。
+	if options.strict_model_matching {
+		material.push_str("strict_model_matching\n");
+	}
+	material.push_str(&format!("{:016x}", dataset_fingerprint(dataset)));
+	offset_cache::fnv1a64(material.as_bytes())
+}
+
+/// 某个文件本次从哪里开始解析、解析出的条目（已确认的行与末尾未写完的行分开）。
+struct ParsedTail {
+	offset: u64,
+	committed: Vec<(ClaudeUsageEntry, f64)>,
+	partial: Option<(ClaudeUsageEntry, f64)>,
+}
+
+/// 全量扫描，但每个文件从上次解析到的位置继续。
+///
+/// 说明：去重是跨文件的，单个文件被改写或从列表里消失都会影响其它文件里哪些条目该计入，
+/// 这两种情况下整份缓存作废、从头解析；只是新增文件或文件变长时才能续读。
+fn scan_claude_files_incremental(
+	files: &[PathBuf],
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	cache_path: Option<&Path>,
+) -> UsageTotals {
+	let key = offset_cache_key(options, dataset);
+	let mut cache: ClaudeOffsetCache = offset_cache::load_cache(cache_path);
+	if cache.version != offset_cache::CACHE_VERSION || cache.key != key {
+		cache = ClaudeOffsetCache {
+			version: offset_cache::CACHE_VERSION,
+			key,
+			files: HashMap::new(),
 		};
-		let reader = BufReader::new(file);
-		for line in reader.lines().flatten() {
-			let trimmed = line.trim();
-			if trimmed.is_empty() {
-				continue;
-			}
-			if !trimmed.contains("\"usage\"") {
-				continue;
+	}
+
+	let listed: HashSet<&PathBuf> = files.iter().collect();
+	let stamps: Vec<Option<FileStamp>> = files.iter().map(|path| FileStamp::of(path)).collect();
+	let mut offsets: Vec<u64> = Vec::with_capacity(files.len());
+	let mut reset = cache.files.keys().any(|path| !listed.contains(path));
+	for (path, stamp) in files.iter().zip(&stamps) {
+		let offset = match (cache.files.get(path), stamp) {
+			(None, _) => Some(0),
+			(Some(cached), Some(stamp)) => {
+				offset_cache::resume_offset(path, &cached.checkpoint, *stamp)
 			}
+			(Some(_), None) => None,
+		};
+		reset |= offset.is_none();
+		offsets.push(offset.unwrap_or(0));
+	}
+	if reset {
+		cache.files.clear();
+		offsets.iter_mut().for_each(|offset| *offset = 0);
+	}
 
-			let Ok(value) = serde_json::from_str::<Value>(trimmed) else {
-				continue;
-			};
+	let mut seen: HashSet<u64> = cache
+		.files
+		.values()
+		.flat_map(|cached| cached.hashes.iter().copied())
+		.collect();
+	let mut transient_seen: HashSet<u64> = HashSet::new();
+	let mut totals = UsageTotals::default();
+	let indexed: Vec<usize> = (0..files.len()).collect();
+	for batch in indexed.chunks(PARSE_BATCH_FILES) {
+		let batch_files: Vec<PathBuf> = batch.iter().map(|&index| files[index].clone()).collect();
+		let start_offsets: HashMap<&PathBuf, u64> =
+			batch.iter().map(|&index| (&files[index], offsets[index])).collect();
+		let parsed = parallel::map_files(&batch_files, |path| {
+			parse_claude_tail(path, start_offsets[path], options, dataset)
+		});
 
-			let Some(entry) = parse_usage_entry(&value) else {
+		for (&index, tail) in batch.iter().zip(parsed) {
+			let path = &files[index];
+			let Some(tail) = tail else {
+				cache.files.remove(path);
 				continue;
 			};
-
-			if !date_in_range_local(&entry.timestamp, since, until) {
+			let Some(stamp) = stamps[index] else {
 				continue;
-			}
-
-			if let Some(hash) = unique_hash(&entry) {
-				if processed_hashes.contains(&hash) {
-					continue;
+			};
+			let cached = cache.files.entry(path.clone()).or_default();
+			for (entry, cost_usd) in tail.committed {
+				if let Some(hash) = unique_hash(&entry) {
+					let hash = offset_cache::fnv1a64(hash.as_bytes());
+					if !seen.insert(hash) {
+						continue;
+					}
+					cached.hashes.push(hash);
 				}
-				processed_hashes.insert(hash);
+				cached.totals += entry_totals(&entry, cost_usd);
 			}
-
-			let input = entry.input_tokens;
-			let output = entry.output_tokens;
-			let cache_creation = entry.cache_creation_input_tokens;
-			let cache_read = entry.cache_read_input_tokens;
-
-			totals.total_tokens = totals
-				.total_tokens
-				.saturating_add(input + output + cache_creation + cache_read);
-
-			if let Some(cost_usd) = entry.cost_usd {
-				totals.cost_usd += cost_usd;
-			} else if let Some(model) = entry.model {
-				if let Some(pricing) = find_model_pricing(dataset, &model, &CLAUDE_PROVIDER_PREFIXES) {
-					totals.cost_usd += calculate_claude_cost_from_pricing(
-						ClaudeTokens {
-							input_tokens: input,
-							output_tokens: output,
-							cache_creation_input_tokens: cache_creation,
-							cache_read_input_tokens: cache_read,
-						},
-						&pricing,
-					);
+			cached.checkpoint = offset_cache::checkpoint_at(path, stamp, tail.offset);
+			totals += cached.totals;
+
+			// 还没写完的最后一行本次照常计入，但不进缓存：下次会从它的开头重新读。
+			if let Some((entry, cost_usd)) = tail.partial {
+				let fresh = unique_hash(&entry).is_none_or(|hash| {
+					let hash = offset_cache::fnv1a64(hash.as_bytes());
+					!seen.contains(&hash) && transient_seen.insert(hash)
+				});
+				if fresh {
+					totals += entry_totals(&entry, cost_usd);
 				}
 			}
 		}
 	}
 
+	offset_cache::save_cache(cache_path, &cache);
 	totals
 }
 
-pub fn load_claude_totals_from_files_all_time_with_pricing(
+fn parse_claude_tail(
+	path: &Path,
+	offset: u64,
+	options: &ScanOptions,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> Option<ParsedTail> {
+	let mut committed = Vec::new();
+	let (offset, partial) = offset_cache::read_lines_from(path, offset, |line| {
+		committed.extend(parse_claude_line(line, None, options, dataset));
+	})?;
+	let partial = partial.and_then(|line| parse_claude_line(&line, None, options, dataset));
+	Some(ParsedTail {
+		offset,
+		committed,
+		partial,
+	})
+}
+
+fn entry_totals(entry: &ClaudeUsageEntry, cost_usd: f64) -> UsageTotals {
+	UsageTotals {
+		total_tokens: entry.input_tokens
+			+ entry.output_tokens
+			+ entry.cache_creation_input_tokens
+			+ entry.cache_read_input_tokens,
+		cost_usd,
+		cache_read_tokens: entry.cache_read_input_tokens,
+		cache_creation_tokens: entry.cache_creation_input_tokens,
+		..Default::default()
+	}
+}
+
+/// 区间的起止日期；日期无法解析时返回 None。
+fn range_bounds(range: &DateRange) -> Option<(NaiveDate, NaiveDate)> {
+	let since = parse_yyyymmdd(&range.since_yyyymmdd)?;
+	let until = parse_yyyymmdd(&range.until_yyyymmdd)?;
+	Some((since, until))
+}
+
+/// 扫描 Claude 日志文件，按模型累计 token/成本；`range` 为 None 时统计全部。
+fn scan_claude_files(
 	files: &[PathBuf],
+	range: Option<&DateRange>,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
-) -> UsageTotals {
-	let mut processed_hashes: HashSet<String> = HashSet::new();
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let mut breakdown: HashMap<String, UsageTotals> = HashMap::new();
+	let bounds = match range {
+		Some(range) => match range_bounds(range) {
+			Some(bounds) => Some(bounds),
+			None => return breakdown,
+		},
+		None => None,
+	};
+	visit_claude_entries(files, bounds, options, dataset, |_path, entry, cost_usd| {
+		if range.is_some_and(|range| !range.allows_timestamp(Some(&entry.timestamp))) {
+			return;
+		}
+		let totals = entry_totals(&entry, cost_usd);
+		let key = entry.model.unwrap_or_else(|| UNKNOWN_MODEL_KEY.to_string());
+		*breakdown.entry(key).or_default() += totals;
+	});
+	breakdown
+}
+
+/// 某个本地日期的 Claude 用量（token 按类别拆开），用于与 ccusage 的日报对账。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClaudeDailyUsage {
+	pub input_tokens: u64,
+	pub output_tokens: u64,
+	pub cache_creation_tokens: u64,
+	pub cache_read_tokens: u64,
+	pub cost_usd: f64,
+	pub models: BTreeSet<String>,
+}
+
+impl ClaudeDailyUsage {
+	pub fn total_tokens(&self) -> u64 {
+		self.input_tokens + self.output_tokens + self.cache_creation_tokens + self.cache_read_tokens
+	}
+}
+
+/// 按本地日期汇总 Claude 用量（与总计使用同一套过滤/去重/计价规则）。
+pub fn load_claude_daily_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> BTreeMap<NaiveDate, ClaudeDailyUsage> {
+	let mut days: BTreeMap<NaiveDate, ClaudeDailyUsage> = BTreeMap::new();
+	let Some(bounds) = range_bounds(range) else {
+		return days;
+	};
+
+	visit_claude_entries(files, Some(bounds), options, dataset, |_path, entry, cost_usd| {
+		if !range.allows_timestamp(Some(&entry.timestamp)) {
+			return;
+		}
+		let Some(parsed) = parse_js_timestamp(&entry.timestamp) else {
+			return;
+		};
+		let day = days.entry(parsed.local_date).or_default();
+		day.input_tokens += entry.input_tokens;
+		day.output_tokens += entry.output_tokens;
+		day.cache_creation_tokens += entry.cache_creation_input_tokens;
+		day.cache_read_tokens += entry.cache_read_input_tokens;
+		day.cost_usd += cost_usd;
+		if let Some(model) = entry.model {
+			day.models.insert(model);
+		}
+	});
+	days
+}
+
+pub fn load_claude_daily_from_base_dirs_with_pricing(
+	base_dirs: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> BTreeMap<NaiveDate, ClaudeDailyUsage> {
+	let files = usage_files_from_claude_base_dirs(base_dirs, options);
+	load_claude_daily_from_files_with_pricing(&files, range, dataset, options)
+}
+
+fn sum_breakdown(breakdown: HashMap<String, UsageTotals>) -> UsageTotals {
 	let mut totals = UsageTotals::default();
+	for model_totals in breakdown.into_values() {
+		totals += model_totals;
+	}
+	totals
+}
 
-	for file_path in files {
-		let Ok(file) = File::open(file_path) else {
+/// 按模型拆分的 token/成本统计（key 为 `message.model`；缺失时归入 `unknown`）。
+pub fn load_claude_breakdown_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	scan_claude_files(files, Some(range), dataset, options)
+}
+
+/// 把任意来源的行（如 stdin）按 Claude 日志解析，按模型拆分（同样去重）；
+/// `range` 为 None 时不按日期过滤。
+pub fn load_claude_breakdown_from_lines_with_pricing(
+	lines: impl IntoIterator<Item = String>,
+	range: Option<&DateRange>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let mut breakdown: HashMap<String, UsageTotals> = HashMap::new();
+	let bounds = match range {
+		Some(range) => match range_bounds(range) {
+			Some(bounds) => Some(bounds),
+			None => return breakdown,
+		},
+		None => None,
+	};
+
+	let mut processed_hashes = HashSet::new();
+	for line in lines {
+		let Some((entry, cost_usd)) = parse_claude_line(&line, bounds, options, dataset)
+		else {
 			continue;
 		};
-		let reader = BufReader::new(file);
-		for line in reader.lines().flatten() {
-			let trimmed = line.trim();
-			if trimmed.is_empty() {
-				continue;
-			}
-			if !trimmed.contains("\"usage\"") {
+		if range.is_some_and(|range| !range.allows_timestamp(Some(&entry.timestamp))) {
+			continue;
+		}
+		if let Some(hash) = unique_hash(&entry) {
+			if !processed_hashes.insert(hash) {
 				continue;
 			}
+		}
+		let totals = entry_totals(&entry, cost_usd);
+		let key = entry.model.unwrap_or_else(|| UNKNOWN_MODEL_KEY.to_string());
+		*breakdown.entry(key).or_default() += totals;
+	}
+	breakdown
+}
 
-			let Ok(value) = serde_json::from_str::<Value>(trimmed) else {
-				continue;
-			};
+/// 与 [`load_claude_totals_from_files_with_pricing`] 同口径，额外统计每行的处理结果（诊断用）。
+///
+/// 说明：顺序扫描、不走并行，只在用户主动查看诊断时调用；读不出的行（如非 UTF-8）算作解析失败。
+pub fn load_claude_totals_with_diagnostics_from_files(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> (UsageTotals, ScanDiagnostics) {
+	let mut totals = UsageTotals::default();
+	let mut diagnostics = ScanDiagnostics::default();
+	let Some(bounds) = range_bounds(range) else {
+		return (totals, diagnostics);
+	};
 
-			let Some(entry) = parse_usage_entry(&value) else {
+	let mut processed_hashes = HashSet::new();
+	for path in sort_files_by_timestamp(files) {
+		let Ok(file) = File::open(&path) else {
+			continue;
+		};
+		diagnostics.files += 1;
+		for line in BufReader::new(file).lines() {
+			let Ok(line) = line else {
+				diagnostics.record(LineOutcome::Unparseable);
 				continue;
 			};
-
-			if let Some(hash) = unique_hash(&entry) {
-				if processed_hashes.contains(&hash) {
-					continue;
-				}
-				processed_hashes.insert(hash);
-			}
-
-			let input = entry.input_tokens;
-			let output = entry.output_tokens;
-			let cache_creation = entry.cache_creation_input_tokens;
-			let cache_read = entry.cache_read_input_tokens;
-
-			totals.total_tokens = totals
-				.total_tokens
-				.saturating_add(input + output + cache_creation + cache_read);
-
-			if let Some(cost_usd) = entry.cost_usd {
-				totals.cost_usd += cost_usd;
-			} else if let Some(model) = entry.model {
-				if let Some(pricing) = find_model_pricing(dataset, &model, &CLAUDE_PROVIDER_PREFIXES) {
-					totals.cost_usd += calculate_claude_cost_from_pricing(
-						ClaudeTokens {
-							input_tokens: input,
-							output_tokens: output,
-							cache_creation_input_tokens: cache_creation,
-							cache_read_input_tokens: cache_read,
-						},
-						&pricing,
-					);
-				}
-			}
+			let outcome =
+				match classify_claude_line(&line, Some(bounds), options, dataset) {
+					Ok((entry, _)) if !range.allows_timestamp(Some(&entry.timestamp)) => {
+						LineOutcome::OutOfRange
+					}
+					Ok((entry, cost_usd)) => {
+						let is_duplicate = unique_hash(&entry)
+							.is_some_and(|hash| !processed_hashes.insert(hash));
+						if is_duplicate {
+							LineOutcome::Duplicate
+						} else {
+							totals += entry_totals(&entry, cost_usd);
+							LineOutcome::Counted
+						}
+					}
+					Err(outcome) => outcome,
+				};
+			diagnostics.record(outcome);
 		}
 	}
+	(totals, diagnostics)
+}
 
-	totals
+pub fn load_claude_totals_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> UsageTotals {
+	sum_breakdown(load_claude_breakdown_from_files_with_pricing(files, range, dataset, options))
+}
+
+pub fn load_claude_totals_from_files_all_time_with_pricing(
+	files: &[PathBuf],
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> UsageTotals {
+	sum_breakdown(scan_claude_files(files, None, dataset, options))
+}
+
+/// 同 [`load_claude_totals_from_files_all_time_with_pricing`]，但借助 `cache_path` 处的增量缓存
+/// 只解析上次之后新追加的内容；`cache_path` 为 None 时等同于全量解析。
+pub fn load_claude_totals_all_time_incremental(
+	files: &[PathBuf],
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+	cache_path: Option<&Path>,
+) -> UsageTotals {
+	scan_claude_files_incremental(files, options, dataset, cache_path)
+}
+
+/// 全量扫描：每个模型（缺失时为 `unknown`）的 token 合计与最后使用日期（不计价）。
+pub fn load_claude_model_history_from_files(
+	files: &[PathBuf],
+	options: &ScanOptions,
+) -> HashMap<String, ModelHistory> {
+	let mut history: HashMap<String, ModelHistory> = HashMap::new();
+	let dataset = HashMap::new();
+	visit_claude_entries(files, None, options, &dataset, |_path, entry, _cost_usd| {
+		let date = parse_js_timestamp(&entry.timestamp).map(|parsed| parsed.local_date);
+		let key = entry.model.unwrap_or_else(|| UNKNOWN_MODEL_KEY.to_string());
+		history.entry(key).or_default().record(
+			entry.input_tokens
+				+ entry.output_tokens
+				+ entry.cache_creation_input_tokens
+				+ entry.cache_read_input_tokens,
+			date,
+		);
+	});
+	history
 }
 
 pub fn load_claude_totals_from_base_dirs_with_pricing(
 	base_dirs: &[PathBuf],
 	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
 ) -> UsageTotals {
-	let files = usage_files_from_claude_base_dirs(base_dirs);
-	load_claude_totals_from_files_with_pricing(&files, range, dataset)
+	let files = usage_files_from_claude_base_dirs(base_dirs, options);
+	load_claude_totals_from_files_with_pricing(&files, range, dataset, options)
 }
 
-pub fn load_claude_totals_from_base_dirs_all_time_with_pricing(
+pub fn load_claude_breakdown_from_base_dirs_with_pricing(
 	base_dirs: &[PathBuf],
+	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
-) -> UsageTotals {
-	let files = usage_files_from_claude_base_dirs(base_dirs);
-	load_claude_totals_from_files_all_time_with_pricing(&files, dataset)
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let files = usage_files_from_claude_base_dirs(base_dirs, options);
+	load_claude_breakdown_from_files_with_pricing(&files, range, dataset, options)
+}
+
+/// 不在 `projects/<项目>/` 下的日志（如 `history/`）在按项目拆分时归入的 key。
+pub const UNKNOWN_PROJECT_KEY: &str = "unknown";
+
+/// 按项目拆分的 token/成本统计（key 为 `projects/` 下的目录名）。
+///
+/// 跨文件去重仍是全局的：同一条目只计一次，归入按时间排序后最先出现它的那个文件所属的项目，
+/// 所以各项目之和与 [`load_claude_totals_from_files_with_pricing`] 一致。
+pub fn load_claude_breakdown_by_project_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let mut breakdown: HashMap<String, UsageTotals> = HashMap::new();
+	let Some(bounds) = range_bounds(range) else {
+		return breakdown;
+	};
+
+	visit_claude_entries(files, Some(bounds), options, dataset, |path, entry, cost_usd| {
+		if !range.allows_timestamp(Some(&entry.timestamp)) {
+			return;
+		}
+		let key = project_dir_name(path).unwrap_or(UNKNOWN_PROJECT_KEY);
+		*breakdown.entry(key.to_string()).or_default() += entry_totals(&entry, cost_usd);
+	});
+	breakdown
 }
 
-pub fn default_claude_base_dirs() -> Result<Vec<PathBuf>, ClaudePathError> {
+pub fn load_claude_breakdown_by_project_with_pricing(
+	base_dirs: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let files = usage_files_from_claude_base_dirs(base_dirs, options);
+	load_claude_breakdown_by_project_from_files_with_pricing(&files, range, dataset, options)
+}
+
+pub fn default_claude_base_dirs(options: &ScanOptions) -> Result<Vec<PathBuf>, ClaudePathError> {
 	const ENV: &str = "CLAUDE_CONFIG_DIR";
 
 	fn is_dir(path: &Path) -> bool {
 		std::fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
 	}
 
-	fn has_projects_dir(base: &Path) -> bool {
-		is_dir(&base.join("projects"))
-	}
-
 	fn resolve_like_node(raw: &str) -> PathBuf {
 		let base = PathBuf::from(raw);
 		if base.is_absolute() {
@@ -435,16 +1055,20 @@ pub fn default_claude_base_dirs() -> Result<Vec<PathBuf>, ClaudePathError> {
 			.join(base)
 	}
 
+	// 只要有任意一个日志子目录（如只有 `history/`）就算有效的数据目录。
+	let subdirs = options.log_subdirs();
+	let has_log_dir = |base: &Path| subdirs.iter().any(|sub| is_dir(&base.join(sub)));
+
 	let env_paths = std::env::var(ENV).unwrap_or_default();
 	if !env_paths.trim().is_empty() {
 		let mut out = Vec::new();
 		let mut seen = HashSet::<PathBuf>::new();
 		for raw in env_paths.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
 			let base = resolve_like_node(raw);
-			if !is_dir(&base) || !has_projects_dir(&base) {
+			if !is_dir(&base) || !has_log_dir(&base) {
 				continue;
 			}
-			if seen.insert(base.clone()) {
+			if seen.insert(canonical_or_self(&base)) {
 				out.push(base);
 			}
 		}
@@ -456,20 +1080,15 @@ pub fn default_claude_base_dirs() -> Result<Vec<PathBuf>, ClaudePathError> {
 		return Ok(out);
 	}
 
-	let home = std::env::var("HOME").unwrap_or_default();
-	if home.is_empty() {
+	let candidates = default_candidate_dirs();
+	if candidates.is_empty() {
 		return Err(ClaudePathError::NoValidDefaultPaths);
 	}
 
-	let xdg_config = std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{home}/.config"));
-	let candidates = [
-		PathBuf::from(format!("{xdg_config}/claude")),
-		PathBuf::from(format!("{home}/.claude")),
-	];
-
 	let mut out = Vec::new();
+	let mut seen = HashSet::<PathBuf>::new();
 	for base in candidates {
-		if is_dir(&base) && has_projects_dir(&base) {
+		if is_dir(&base) && has_log_dir(&base) && seen.insert(canonical_or_self(&base)) {
 			out.push(base);
 		}
 	}
@@ -481,9 +1100,39 @@ pub fn default_claude_base_dirs() -> Result<Vec<PathBuf>, ClaudePathError> {
 	Ok(out)
 }
 
+/// 没有设置 `CLAUDE_CONFIG_DIR` 时依次尝试的数据目录。
+fn default_candidate_dirs() -> Vec<PathBuf> {
+	let mut candidates = Vec::new();
+	let home = std::env::var("HOME").unwrap_or_default();
+	if !home.is_empty() {
+		let xdg_config =
+			std::env::var("XDG_CONFIG_HOME").unwrap_or_else(|_| format!("{home}/.config"));
+		candidates.push(PathBuf::from(format!("{xdg_config}/claude")));
+		candidates.push(PathBuf::from(format!("{home}/.claude")));
+	}
+	// Windows 上通常没有 HOME/XDG_CONFIG_HOME；与上面指向同一目录时由调用方按真实路径去重。
+	#[cfg(windows)]
+	candidates.extend(windows_candidate_dirs(
+		std::env::var("APPDATA").ok().as_deref(),
+		std::env::var("USERPROFILE").ok().as_deref(),
+	));
+	candidates
+}
+
+/// Windows 的默认数据目录：`%APPDATA%\Claude` 与 `%USERPROFILE%\.claude`（空值跳过）。
+#[cfg(any(windows, test))]
+fn windows_candidate_dirs(appdata: Option<&str>, user_profile: Option<&str>) -> Vec<PathBuf> {
+	let non_empty = |value: Option<&str>| value.filter(|v| !v.trim().is_empty()).map(PathBuf::from);
+	let mut candidates = Vec::new();
+	candidates.extend(non_empty(appdata).map(|dir| dir.join("Claude")));
+	candidates.extend(non_empty(user_profile).map(|dir| dir.join(".claude")));
+	candidates
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::test_util;
 	use chrono::Local;
 	use chrono::TimeZone;
 
@@ -536,11 +1185,7 @@ mod tests {
 		std::fs::create_dir_all(&projects).expect("mkdir");
 
 		let file_path = projects.join("session.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 		let other_day = Local
 			.with_ymd_and_hms(2026, 2, 5, 12, 0, 0)
 			.single()
@@ -583,16 +1228,141 @@ mod tests {
 			.join("\n");
 		std::fs::write(&file_path, content).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
-		let totals =
-			load_claude_totals_from_base_dirs_with_pricing(&[base], &range, &HashMap::new());
+		let options = ScanOptions::default();
+		let totals = load_claude_totals_from_base_dirs_with_pricing(
+			&[base],
+			&range,
+			&HashMap::new(),
+			&options,
+		);
 		assert_eq!(totals.total_tokens, 150 + (10 + 5 + 2 + 3));
 		assert!((totals.cost_usd - (0.10 + 0.01)).abs() < 1e-9);
+		assert_eq!(totals.cache_read_tokens, 3);
+		assert_eq!(totals.cache_creation_tokens, 2);
+	}
+
+	#[test]
+	fn overlapping_base_dirs_are_scanned_once() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let base = tmp.path().join(".claude");
+		let projects = base.join("projects").join("p1");
+		std::fs::create_dir_all(&projects).expect("mkdir");
+
+		let day = test_util::local_noon_20260206();
+		// 没有 message.id/requestId：只能靠文件去重来避免重复计数。
+		let line = serde_json::json!({
+			"timestamp": day,
+			"message": { "usage": { "input_tokens": 100, "output_tokens": 50 } }
+		});
+		std::fs::write(projects.join("session.jsonl"), line.to_string()).expect("write");
+
+		// 第二个 base dir 通过 `..` 指向同一个真实目录。
+		let alias = base.join("projects").join("..");
+		let range = test_util::day_range("20260206");
+
+		let base_dirs = [base, alias];
+		let options = ScanOptions::default();
+		assert_eq!(usage_files_from_claude_base_dirs(&base_dirs, &options).len(), 1);
+		let totals = load_claude_totals_from_base_dirs_with_pricing(
+			&base_dirs,
+			&range,
+			&HashMap::new(),
+			&options,
+		);
+		assert_eq!(totals.total_tokens, 150);
+	}
+
+	#[test]
+	fn base_dirs_with_glob_characters_are_scanned_literally() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let base = tmp.path().join("claude [work]");
+		let projects = base.join("projects").join("p1");
+		std::fs::create_dir_all(&projects).expect("mkdir");
+		std::fs::write(projects.join("session.jsonl"), "{}").expect("write");
+
+		let files = usage_files_from_claude_base_dirs(&[base], &ScanOptions::default());
+		assert_eq!(files.len(), 1);
+	}
+
+	#[test]
+	fn excluded_projects_are_dropped_before_parsing() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let base = tmp.path().join(".claude");
+		let line = |id: &str, tokens: u64| {
+			serde_json::json!({
+				"timestamp": "2026-02-06T10:00:00Z",
+				"requestId": id,
+				"message": { "id": id, "usage": { "input_tokens": tokens, "output_tokens": 0 } }
+			})
+			.to_string()
+		};
+		for (project, id, tokens) in [("-work-app", "r1", 100), ("-tmp-scratch", "r2", 7)] {
+			let dir = base.join("projects").join(project);
+			std::fs::create_dir_all(&dir).expect("mkdir");
+			std::fs::write(dir.join("s.jsonl"), line(id, tokens)).expect("write");
+		}
+
+		let base_dirs = std::slice::from_ref(&base);
+		let options = ScanOptions::default();
+		let files = usage_files_from_claude_base_dirs(base_dirs, &options);
+		assert_eq!(files.len(), 2);
+		let excluding = ScanOptions {
+			exclude_projects: vec![" -tmp-scratch ".to_string()],
+			..ScanOptions::default()
+		};
+		let kept = usage_files_from_claude_base_dirs(base_dirs, &excluding);
+		assert_eq!(kept.len(), 1);
+		assert_eq!(project_dir_name(&kept[0]), Some("-work-app"));
+
+		let dataset = HashMap::new();
+		let all = load_claude_totals_from_files_all_time_with_pricing(&files, &dataset, &options);
+		let filtered =
+			load_claude_totals_from_files_all_time_with_pricing(&kept, &dataset, &options);
+		assert_eq!(all.total_tokens, 107);
+		assert_eq!(filtered.total_tokens, 100);
+	}
+
+	#[test]
+	fn history_subdir_is_scanned_and_accepted_as_a_data_dir() {
+		let _lock = crate::test_util::env_cwd_lock()
+			.lock()
+			.expect("env/cwd lock poisoned");
+		let _restore_env = RestoreEnvVar::new("CLAUDE_CONFIG_DIR");
+
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let base = tmp.path().join(".claude");
+		let line = serde_json::json!({
+			"timestamp": "2026-02-06T10:00:00Z",
+			"requestId": "r1",
+			"message": { "id": "m1", "usage": { "input_tokens": 100, "output_tokens": 0 } }
+		})
+		.to_string();
+		let history = base.join("history");
+		std::fs::create_dir_all(&history).expect("mkdir");
+		std::fs::write(history.join("s.jsonl"), &line).expect("write");
+
+		// 只有 `history/`、没有 `projects/` 的目录也是有效的数据目录。
+		std::env::set_var("CLAUDE_CONFIG_DIR", base.to_string_lossy().to_string());
+		let options = ScanOptions::default();
+		assert_eq!(default_claude_base_dirs(&options).expect("dirs"), vec![base.clone()]);
+
+		let subdirs: Vec<String> = DEFAULT_LOG_SUBDIRS.iter().map(|d| d.to_string()).collect();
+		let base_dirs = [base.clone()];
+		assert_eq!(usage_files_in_subdirs(&base_dirs, &subdirs).len(), 1);
+		assert!(usage_files_in_subdirs(&base_dirs, &["projects".to_string()]).is_empty());
+
+		// 同一条记录同时出现在 projects 与 history 下时只计一次。
+		let project = base.join("projects").join("p1");
+		std::fs::create_dir_all(&project).expect("mkdir");
+		std::fs::write(project.join("s.jsonl"), &line).expect("write");
+		let files = usage_files_in_subdirs(&base_dirs, &subdirs);
+		assert_eq!(files.len(), 2);
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_all_time_with_pricing(&files, &HashMap::new(), &options);
+		assert_eq!(totals.total_tokens, 100);
 	}
 
 	#[test]
@@ -639,18 +1409,394 @@ mod tests {
 		)
 		.expect("write b");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
-		let totals =
-			load_claude_totals_from_base_dirs_with_pricing(&[base], &range, &HashMap::new());
+		let options = ScanOptions::default();
+		let totals = load_claude_totals_from_base_dirs_with_pricing(
+			&[base],
+			&range,
+			&HashMap::new(),
+			&options,
+		);
 		assert_eq!(totals.total_tokens, 150);
 		assert!((totals.cost_usd - 0.10).abs() < 1e-9);
 	}
 
+	#[test]
+	fn lines_are_parsed_and_deduped_like_files() {
+		let line = |day: &str, request_id: &str| {
+			serde_json::json!({
+				"timestamp": format!("2026-02-{day}T12:00:00Z"),
+				"requestId": request_id,
+				"message": {
+					"id": request_id,
+					"model": "claude-sonnet-4",
+					"usage": { "input_tokens": 100, "output_tokens": 10 }
+				}
+			})
+			.to_string()
+		};
+		let lines = vec![
+			line("05", "r1"),
+			line("05", "r1"),
+			"not json".to_string(),
+			line("20", "r2"),
+		];
+		let dataset = HashMap::new();
+
+		let options = ScanOptions::default();
+		let all =
+			load_claude_breakdown_from_lines_with_pricing(lines.clone(), None, &dataset, &options);
+		assert_eq!(all["claude-sonnet-4"].total_tokens, 220);
+
+		let range = test_util::span_range("20260210", "20260228", "Custom");
+		let ranged =
+			load_claude_breakdown_from_lines_with_pricing(lines, Some(&range), &dataset, &options);
+		assert_eq!(ranged["claude-sonnet-4"].total_tokens, 110);
+	}
+
+	#[test]
+	fn session_start_filters_earlier_entries_of_the_same_day() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("session.jsonl");
+		let at = |hour| {
+			Local
+				.with_ymd_and_hms(2026, 2, 6, hour, 0, 0)
+				.single()
+				.expect("local dt")
+		};
+		let lines = [(9, "r1", 100), (12, "r2", 7)].map(|(hour, request_id, input_tokens)| {
+			serde_json::json!({
+				"timestamp": at(hour).to_rfc3339(),
+				"requestId": request_id,
+				"message": {
+					"id": request_id,
+					"usage": { "input_tokens": input_tokens, "output_tokens": 0 }
+				}
+			})
+			.to_string()
+		});
+		std::fs::write(&file_path, lines.join("\n")).expect("write");
+
+		let mut range = test_util::span_range("20260206", "20260206", "Session");
+		let files = [file_path];
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let day = load_claude_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+		assert_eq!(day.total_tokens, 107);
+
+		range.started_at_millis = Some(at(11).timestamp_millis());
+		let session =
+			load_claude_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+		assert_eq!(session.total_tokens, 7);
+	}
+
+	#[test]
+	fn diagnostics_count_skipped_and_deduped_lines() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("session.jsonl");
+		let line = |day, request_id: &str| {
+			let timestamp = Local
+				.with_ymd_and_hms(2026, 2, day, 10, 0, 0)
+				.single()
+				.expect("local dt")
+				.to_rfc3339();
+			serde_json::json!({
+				"timestamp": timestamp,
+				"requestId": request_id,
+				"message": {
+					"id": request_id,
+					"usage": { "input_tokens": 100, "output_tokens": 0 }
+				}
+			})
+			.to_string()
+		};
+		let lines = [
+			line(6, "r1"),
+			line(6, "r1"),
+			line(1, "r2"),
+			r#"{"message":{"usage":{"input_tokens":"#.to_string(),
+			r#"{"type":"summary"}"#.to_string(),
+		];
+		std::fs::write(&file_path, lines.join("\n")).expect("write");
+
+		let range = test_util::day_range("20260206");
+		let files = [file_path];
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let (totals, diagnostics) =
+			load_claude_totals_with_diagnostics_from_files(&files, &range, &dataset, &options);
+		let plain = load_claude_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+		assert_eq!(totals.total_tokens, plain.total_tokens);
+		assert_eq!(totals.total_tokens, 100);
+		assert_eq!(
+			diagnostics,
+			ScanDiagnostics {
+				files: 1,
+				lines_total: 5,
+				lines_skipped_parse: 1,
+				lines_skipped_range: 1,
+				entries_deduped: 1,
+			}
+		);
+	}
+
+	#[test]
+	fn project_breakdown_attributes_shared_entries_once() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let at = |hour| {
+			Local
+				.with_ymd_and_hms(2026, 2, 6, hour, 0, 0)
+				.single()
+				.expect("local dt")
+				.to_rfc3339()
+		};
+		let line = |hour, request_id: &str, input_tokens: u64| {
+			serde_json::json!({
+				"timestamp": at(hour),
+				"message": {
+					"id": request_id,
+					"usage": { "input_tokens": input_tokens, "output_tokens": 0 }
+				},
+				"requestId": request_id
+			})
+			.to_string()
+		};
+		let write = |path: PathBuf, lines: &[String]| {
+			std::fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+			std::fs::write(path, lines.join("\n")).expect("write");
+		};
+
+		let base = tmp.path().join(".claude");
+		let file_a = base.join("projects").join("a").join("s.jsonl");
+		let file_b = base.join("projects").join("b").join("s.jsonl");
+		let file_h = base.join("history").join("s.jsonl");
+		// r1 同时出现在 a 与 b 里；a 的最早时间戳更早，所以 r1 归 a。
+		write(file_a.clone(), &[line(8, "r0", 1), line(9, "r1", 100)]);
+		write(file_b.clone(), &[line(9, "r1", 100), line(11, "r2", 7)]);
+		write(file_h.clone(), &[line(12, "r3", 3)]);
+
+		let range = test_util::day_range("20260206");
+		let files = vec![file_b, file_h, file_a];
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let by_project = load_claude_breakdown_by_project_from_files_with_pricing(
+			&files,
+			&range,
+			&dataset,
+			&options,
+		);
+		assert_eq!(by_project.len(), 3);
+		assert_eq!(by_project["a"].total_tokens, 101);
+		assert_eq!(by_project["b"].total_tokens, 7);
+		assert_eq!(by_project[UNKNOWN_PROJECT_KEY].total_tokens, 3);
+
+		let totals = load_claude_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+		let summed: u64 = by_project.values().map(|t| t.total_tokens).sum();
+		assert_eq!(summed, totals.total_tokens);
+	}
+
+	#[test]
+	fn dedupes_by_uuid_when_request_id_missing() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("session.jsonl");
+		let day = test_util::local_noon_20260206();
+
+		let lines = vec![
+			serde_json::json!({
+				"timestamp": day,
+				"uuid": "u1",
+				"message": { "id": "m1", "usage": { "input_tokens": 100, "output_tokens": 50 } }
+			}),
+			// 同一条日志被重复写入（无 requestId，但 uuid 相同）=> 应被去重
+			serde_json::json!({
+				"timestamp": day,
+				"uuid": "u1",
+				"message": { "id": "m1", "usage": { "input_tokens": 100, "output_tokens": 50 } }
+			}),
+			// uuid 不同 => 正常计数
+			serde_json::json!({
+				"timestamp": day,
+				"uuid": "u2",
+				"message": { "id": "m1", "usage": { "input_tokens": 1, "output_tokens": 2 } }
+			}),
+		];
+		let content = lines
+			.into_iter()
+			.map(|v| v.to_string())
+			.collect::<Vec<_>>()
+			.join("\n");
+		std::fs::write(&file_path, content).expect("write");
+
+		let range = test_util::day_range("20260206");
+
+		let files = vec![file_path];
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_with_pricing(&files, &range, &HashMap::new(), &options);
+		assert_eq!(totals.total_tokens, 150 + 3);
+		let all_time =
+			load_claude_totals_from_files_all_time_with_pricing(&files, &HashMap::new(), &options);
+		assert_eq!(all_time.total_tokens, 150 + 3);
+	}
+
+	#[test]
+	fn breakdown_keys_by_model_and_dedupes_across_files() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let day = test_util::local_noon_20260206();
+
+		let first = tmp.path().join("a.jsonl");
+		let first_lines = [
+			serde_json::json!({
+				"timestamp": day,
+				"requestId": "r1",
+				"message": { "id": "m1", "model": "claude-sonnet-4", "usage": { "input_tokens": 100, "output_tokens": 50 } }
+			}),
+			serde_json::json!({
+				"timestamp": day,
+				"message": { "usage": { "input_tokens": 7, "output_tokens": 3 } }
+			}),
+		];
+		// 另一个文件里重复出现同一条请求（例如会话被复制/续写）=> 只计一次。
+		let second = tmp.path().join("b.jsonl");
+		let second_lines = [
+			serde_json::json!({
+				"timestamp": day,
+				"requestId": "r1",
+				"message": { "id": "m1", "model": "claude-sonnet-4", "usage": { "input_tokens": 100, "output_tokens": 50 } }
+			}),
+			serde_json::json!({
+				"timestamp": day,
+				"requestId": "r2",
+				"message": { "id": "m2", "model": "claude-opus-4", "usage": { "input_tokens": 20, "output_tokens": 10 } }
+			}),
+		];
+		for (path, lines) in [(&first, &first_lines), (&second, &second_lines)] {
+			let content = lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n");
+			std::fs::write(path, content).expect("write");
+		}
+
+		let range = test_util::day_range("20260206");
+		let files = vec![first, second];
+		let options = ScanOptions::default();
+		let breakdown = load_claude_breakdown_from_files_with_pricing(
+			&files,
+			&range,
+			&HashMap::new(),
+			&options,
+		);
+
+		assert_eq!(breakdown.len(), 3);
+		assert_eq!(breakdown["claude-sonnet-4"].total_tokens, 150);
+		assert_eq!(breakdown["claude-opus-4"].total_tokens, 30);
+		assert_eq!(breakdown[UNKNOWN_MODEL_KEY].total_tokens, 10);
+
+		let totals =
+			load_claude_totals_from_files_with_pricing(&files, &range, &HashMap::new(), &options);
+		assert_eq!(totals.total_tokens, 150 + 30 + 10);
+	}
+
+	#[test]
+	fn parallel_scan_dedupes_like_sequential_file_order() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		// 足够多的文件才会走并行路径；每个文件都带一条相同的请求，但 token 数不同，
+		// 据此能看出最终保留的是哪个文件里的那一份。
+		let mut files = Vec::new();
+		for hour in 0..20u32 {
+			let at = Local
+				.with_ymd_and_hms(2026, 2, 6, hour, 0, 0)
+				.single()
+				.expect("local dt")
+				.to_rfc3339();
+			let lines = [
+				serde_json::json!({
+					"timestamp": at,
+					"requestId": "dup",
+					"message": {
+						"id": "dup",
+						"model": "claude-opus-4",
+						"usage": { "input_tokens": 1000 + hour, "output_tokens": 0 }
+					}
+				}),
+				serde_json::json!({
+					"timestamp": at,
+					"requestId": format!("r{hour}"),
+					"message": {
+						"id": format!("m{hour}"),
+						"model": "claude-sonnet-4",
+						"usage": { "input_tokens": 1, "output_tokens": 0 }
+					}
+				}),
+			];
+			let path = tmp.path().join(format!("s{hour:02}.jsonl"));
+			let content = lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n");
+			std::fs::write(&path, content).expect("write");
+			files.push(path);
+		}
+		// 倒序传入：有日期范围时按文件最早时间戳排序，保留最早的那份；
+		// all-time 不排序，保留传入顺序里的第一份。
+		files.reverse();
+
+		let range = test_util::day_range("20260206");
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		for _ in 0..3 {
+			let ranged =
+				load_claude_breakdown_from_files_with_pricing(&files, &range, &dataset, &options);
+			assert_eq!(ranged["claude-opus-4"].total_tokens, 1000);
+			assert_eq!(ranged["claude-sonnet-4"].total_tokens, 20);
+
+			let all_time = scan_claude_files(&files, None, &dataset, &options);
+			assert_eq!(all_time["claude-opus-4"].total_tokens, 1019);
+			assert_eq!(all_time["claude-sonnet-4"].total_tokens, 20);
+		}
+	}
+
+	#[test]
+	fn counts_batch_api_result_lines_once_with_batch_pricing() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("batch.jsonl");
+		let day = test_util::local_noon_20260206();
+
+		let batch_line = serde_json::json!({
+			"timestamp": day,
+			"custom_id": "req-1",
+			"batch_id": "msgbatch_1",
+			"result": {
+				"type": "succeeded",
+				"message": {
+					"id": "m1",
+					"model": "claude-sonnet-4-20250514",
+					"usage": { "input_tokens": 1000, "output_tokens": 200 }
+				}
+			}
+		});
+		// 同一批结果被重复导入，应只计一次。
+		let content = [batch_line.to_string(), batch_line.to_string()].join("\n");
+		std::fs::write(&file_path, content).expect("write");
+
+		let range = test_util::day_range("20260206");
+
+		let mut dataset = HashMap::new();
+		dataset.insert(
+			"claude-sonnet-4-20250514".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(3e-6),
+				output_cost_per_token: Some(1.5e-5),
+				input_cost_per_token_batches: Some(1.5e-6),
+				output_cost_per_token_batches: Some(7.5e-6),
+				..Default::default()
+			},
+		);
+
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
+		assert_eq!(totals.total_tokens, 1200);
+		let expected = 1000.0 * 1.5e-6 + 200.0 * 7.5e-6;
+		assert!((totals.cost_usd - expected).abs() < 1e-12);
+	}
+
 	#[test]
 	fn skips_invalid_entries_that_fail_schema_validation() {
 		let tmp = tempfile::tempdir().expect("tempdir");
@@ -659,11 +1805,7 @@ mod tests {
 		std::fs::create_dir_all(&projects).expect("mkdir");
 
 		let file_path = projects.join("session.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 
 		let line = serde_json::json!({
 			"timestamp": day,
@@ -673,14 +1815,15 @@ mod tests {
 		});
 		std::fs::write(&file_path, line.to_string()).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
-		let totals =
-			load_claude_totals_from_base_dirs_with_pricing(&[base], &range, &HashMap::new());
+		let options = ScanOptions::default();
+		let totals = load_claude_totals_from_base_dirs_with_pricing(
+			&[base],
+			&range,
+			&HashMap::new(),
+			&options,
+		);
 		assert_eq!(totals.total_tokens, 0);
 		assert!((totals.cost_usd - 0.0).abs() < 1e-12);
 	}
@@ -693,11 +1836,7 @@ mod tests {
 		std::fs::create_dir_all(&projects).expect("mkdir");
 
 		let file_path = projects.join("session.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 
 		let line = serde_json::json!({
 			"timestamp": day,
@@ -707,16 +1846,14 @@ mod tests {
 		});
 		std::fs::write(&file_path, line.to_string()).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
+		let options = ScanOptions::default();
 		let totals = load_claude_totals_from_base_dirs_with_pricing(
 			&[base],
 			&range,
 			&HashMap::new(),
+			&options,
 		);
 		assert_eq!(totals.total_tokens, 150);
 		assert!((totals.cost_usd - 0.10).abs() < 1e-9);
@@ -730,11 +1867,7 @@ mod tests {
 		std::fs::create_dir_all(&projects).expect("mkdir");
 
 		let file_path = projects.join("session.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 
 		let line = serde_json::json!({
 			"timestamp": day,
@@ -748,11 +1881,7 @@ mod tests {
 
 		std::fs::write(&file_path, line.to_string()).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
 		let mut dataset = HashMap::new();
 		dataset.insert(
@@ -764,7 +1893,9 @@ mod tests {
 			},
 		);
 
-		let totals = load_claude_totals_from_files_with_pricing(&[file_path], &range, &dataset);
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
 		assert_eq!(totals.total_tokens, 150);
 		let expected = 100.0 * 3e-6 + 50.0 * 1.5e-5;
 		assert!((totals.cost_usd - expected).abs() < 1e-12);
@@ -778,11 +1909,7 @@ mod tests {
 		std::fs::create_dir_all(&projects).expect("mkdir");
 
 		let file_path = projects.join("session.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 
 		let line = serde_json::json!({
 			"timestamp": day,
@@ -795,11 +1922,7 @@ mod tests {
 		});
 		std::fs::write(&file_path, line.to_string()).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
 		let mut dataset = HashMap::new();
 		dataset.insert(
@@ -811,7 +1934,9 @@ mod tests {
 			},
 		);
 
-		let totals = load_claude_totals_from_files_with_pricing(&[file_path], &range, &dataset);
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
 		assert_eq!(totals.total_tokens, 150);
 		let expected = 100.0 * 1e-6 + 50.0 * 2e-6;
 		assert!((totals.cost_usd - expected).abs() < 1e-12);
@@ -832,7 +1957,7 @@ mod tests {
 		std::fs::create_dir_all(relative.join("projects")).expect("mkdir");
 		std::env::set_var("CLAUDE_CONFIG_DIR", relative.to_string_lossy().to_string());
 
-		let dirs = default_claude_base_dirs();
+		let dirs = default_claude_base_dirs(&ScanOptions::default());
 		let dirs = dirs.expect("dirs");
 		assert_eq!(dirs.len(), 1);
 		assert!(dirs[0].is_absolute());
@@ -843,6 +1968,19 @@ mod tests {
 		assert_eq!(dirs[0], expected);
 	}
 
+	#[test]
+	fn windows_candidates_use_appdata_and_user_profile() {
+		assert_eq!(
+			windows_candidate_dirs(Some("/appdata"), Some("/users/me")),
+			vec![PathBuf::from("/appdata/Claude"), PathBuf::from("/users/me/.claude")]
+		);
+		assert_eq!(
+			windows_candidate_dirs(Some(" "), Some("/users/me")),
+			vec![PathBuf::from("/users/me/.claude")]
+		);
+		assert!(windows_candidate_dirs(None, None).is_empty());
+	}
+
 	#[test]
 	fn claude_config_dir_errors_when_set_but_invalid() {
 		let _lock = crate::test_util::env_cwd_lock()
@@ -851,7 +1989,7 @@ mod tests {
 		let _restore_env = RestoreEnvVar::new("CLAUDE_CONFIG_DIR");
 
 		std::env::set_var("CLAUDE_CONFIG_DIR", "/nonexistent/claude");
-		let err = default_claude_base_dirs().expect_err("should error");
+		let err = default_claude_base_dirs(&ScanOptions::default()).expect_err("should error");
 		let message = err.to_string();
 		assert!(message.contains("CLAUDE_CONFIG_DIR"));
 	}
@@ -884,7 +2022,59 @@ mod tests {
 		std::fs::write(&file_path, content).expect("write");
 
 		let dataset = HashMap::<String, LiteLLMModelPricing>::new();
-		let totals = load_claude_totals_from_files_all_time_with_pricing(&[file_path], &dataset);
+		let options = ScanOptions::default();
+		let totals =
+			load_claude_totals_from_files_all_time_with_pricing(&[file_path], &dataset, &options);
 		assert_eq!(totals.total_tokens, 3);
 	}
+
+	#[test]
+	fn incremental_scan_dedupes_appended_lines_like_a_full_scan() {
+		use std::io::Write;
+
+		let line = |id: &str, tokens: u64| {
+			serde_json::json!({
+				"timestamp": "2026-02-06T10:00:00Z",
+				"requestId": id,
+				"message": {
+					"id": id,
+					"model": "claude-sonnet-4",
+					"usage": { "input_tokens": tokens, "output_tokens": 0 }
+				}
+			})
+			.to_string()
+		};
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let cache_path = tmp.path().join("cache").join("claude.json");
+		let first = tmp.path().join("a.jsonl");
+		let second = tmp.path().join("b.jsonl");
+		std::fs::write(&first, format!("{}\n", line("r1", 10))).expect("write");
+		std::fs::write(&second, format!("{}\n{}", line("r2", 20), line("r3", 30))).expect("write");
+		let files = vec![first.clone(), second.clone()];
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let scan = || scan_claude_files_incremental(&files, &options, &dataset, Some(&cache_path));
+		let full = || -> u64 {
+			let breakdown = scan_claude_files(&files, None, &dataset, &options);
+			breakdown.values().map(|t| t.total_tokens).sum()
+		};
+
+		// b.jsonl 的最后一行还没写完：计入本次结果，但不进缓存。
+		assert_eq!(scan().total_tokens, 60);
+		let cache: ClaudeOffsetCache = offset_cache::load_cache(Some(&cache_path));
+		assert_eq!(cache.files[&second].hashes.len(), 1);
+
+		// 追加：补完的行只计一次，另一个文件里已经出现过的请求不会被重复计入。
+		let mut file = std::fs::OpenOptions::new().append(true).open(&second).expect("open");
+		let appended = format!("\n{}\n{}\n", line("r1", 10), line("r4", 40));
+		file.write_all(appended.as_bytes()).expect("append");
+		let incremental = scan().total_tokens;
+		assert_eq!(incremental, 100);
+		assert_eq!(incremental, full());
+
+		// 某个文件被改写：整份缓存作废，从头解析。
+		std::fs::write(&first, format!("{}\n", line("r5", 5))).expect("rewrite");
+		assert_eq!(scan().total_tokens, full());
+		assert_eq!(full(), 5 + 20 + 30 + 10 + 40);
+	}
 }
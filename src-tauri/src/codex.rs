@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
@@ -6,27 +6,128 @@ use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant};
 
 use chrono::NaiveDate;
-use glob::glob;
+use flate2::read::GzDecoder;
+use glob::{glob, Pattern};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::cache_lock;
 use crate::pricing::{
-	calculate_codex_cost_from_pricing, find_model_pricing, CodexTokens, LiteLLMModelPricing,
+	calculate_codex_cost_with_tiers, find_model_pricing_with_key, tier_thresholds, CodexTokens,
+	LiteLLMModelPricing,
 };
-use crate::time_parse::parse_js_timestamp;
+use crate::offset_cache::{self, FileCheckpoint, FileStamp};
+use crate::parallel;
+use crate::time_parse::{parse_js_timestamp, BusinessHours};
 use crate::time_range::DateRange;
-use crate::usage::UsageTotals;
+use crate::usage::{LineOutcome, ModelHistory, ScanDiagnostics, UsageTotals};
 
 const CODEX_HOME_ENV: &str = "CODEX_HOME";
+/// 额外的 Codex 目录（逗号分隔），与默认目录一起统计（如工作/个人两套 Codex home）。
+const EXTRA_CODEX_DIRS_ENV: &str = "TOKBAR_CODEX_DIRS";
 const DEFAULT_CODEX_DIR: &str = ".codex";
 const DEFAULT_SESSION_SUBDIR: &str = "sessions";
 const LEGACY_FALLBACK_MODEL: &str = "gpt-5";
 const CODEX_PROVIDER_PREFIXES: [&str; 3] = ["openai/", "azure/", "openrouter/openai/"];
 const SESSION_FILES_TTL: Duration = Duration::from_secs(60 * 5);
+/// 会话文件的匹配模式：归档后被 gzip 压缩的旧会话（`.jsonl.gz`）也要计入。
+const SESSION_FILE_PATTERNS: [&str; 2] = ["*.jsonl", "*.jsonl.gz"];
+
+/// Codex 日志里 `input_tokens` 是否已包含 `cached_input_tokens`。
+///
+/// 说明：不同版本的 Codex 口径不一致；计费时需要的是“未命中缓存的 input”，
+/// 若把已扣除缓存的 input 再减一次 cached，会把缓存折扣算两遍。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodexInputConvention {
+	/// 按单条记录推断：cached > input 时只可能是“不含缓存”的口径，否则按包含处理。
+	#[default]
+	Auto,
+	Inclusive,
+	Exclusive,
+}
 
-#[derive(Debug, Clone, Copy, Default)]
+/// Codex 用量的统计口径。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodexAccounting {
+	/// 逐条累加 `last_token_usage`（缺失时用 `total_token_usage` 差分）。
+	#[default]
+	Deltas,
+	/// 每个会话文件只取最后一次 `total_token_usage` 快照作为整场会话的用量。
+	///
+	/// 说明：对事件缺失/乱序不敏感，适合对账；代价是整场会话按最后一条快照的日期与模型归属。
+	FinalSnapshot,
+}
+
+/// 扫描时使用的口径选项（调用方按设置构造一次后传入，测试里可直接构造）。
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+	input_convention: CodexInputConvention,
+	accounting: CodexAccounting,
+	business_hours: BusinessHours,
+	/// 计价时只做精确/前缀匹配，不做子串兜底。
+	strict_model_matching: bool,
+	/// 区间的精确起点（[`DateRange::started_at_millis`]），早于它的记录不计入。
+	started_at_millis: Option<i64>,
+	/// 价格表里出现的分档分界点：解析时按每个分界点分别累计超出部分，计价时取命中模型的那一份。
+	tier_thresholds: Vec<u64>,
+}
+
+impl ScanOptions {
+	pub fn new(
+		input_convention: CodexInputConvention,
+		accounting: CodexAccounting,
+		business_hours: BusinessHours,
+		strict_model_matching: bool,
+	) -> Self {
+		Self {
+			input_convention,
+			accounting,
+			business_hours,
+			strict_model_matching,
+			started_at_millis: None,
+			tier_thresholds: Vec::new(),
+		}
+	}
+
+	pub(crate) fn strict_model_matching(&self) -> bool {
+		self.strict_model_matching
+	}
+
+	/// 再按区间的精确起点过滤（`Session`）；整天区间与 `self` 相同。
+	fn for_range(&self, range: &DateRange) -> Self {
+		Self {
+			started_at_millis: range.started_at_millis,
+			..self.clone()
+		}
+	}
+
+	/// 按计价用的价格表补上分档分界点（不计价的扫描不需要）。
+	fn with_tier_thresholds(mut self, dataset: &HashMap<String, LiteLLMModelPricing>) -> Self {
+		self.tier_thresholds = tier_thresholds(dataset);
+		self
+	}
+
+	/// 工作时间与精确起点两项时刻过滤（日期范围另由 `bounds` 判断）。
+	fn allows_timestamp(&self, timestamp: Option<&str>) -> bool {
+		if !self.business_hours.allows_timestamp(timestamp) {
+			return false;
+		}
+		match self.started_at_millis {
+			Some(started_at) => timestamp
+				.and_then(parse_js_timestamp)
+				.is_some_and(|parsed| parsed.millis >= started_at),
+			None => true,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 struct RawUsage {
 	input_tokens: u64,
 	cached_input_tokens: u64,
+	cache_creation_input_tokens: u64,
 	output_tokens: u64,
 	reasoning_output_tokens: u64,
 	total_tokens: u64,
@@ -36,6 +137,7 @@ struct RawUsage {
 struct DeltaUsage {
 	input_tokens: u64,
 	cached_input_tokens: u64,
+	cache_creation_input_tokens: u64,
 	output_tokens: u64,
 	reasoning_output_tokens: u64,
 	total_tokens: u64,
@@ -45,6 +147,12 @@ fn parse_yyyymmdd(value: &str) -> Option<NaiveDate> {
 	NaiveDate::parse_from_str(value, "%Y%m%d").ok()
 }
 
+fn range_bounds(range: &DateRange) -> Option<(NaiveDate, NaiveDate)> {
+	let since = parse_yyyymmdd(&range.since_yyyymmdd)?;
+	let until = parse_yyyymmdd(&range.until_yyyymmdd)?;
+	Some((since, until))
+}
+
 fn parse_local_date_if_in_range(
 	timestamp_rfc3339: &str,
 	since: NaiveDate,
@@ -127,6 +235,7 @@ fn normalize_raw_usage(value: Option<&Value>) -> Option<RawUsage> {
 
 	let input = ensure_u64(obj.get("input_tokens"));
 	let cached = ensure_u64(obj.get("cached_input_tokens").or(obj.get("cache_read_input_tokens")));
+	let cache_creation = ensure_u64(obj.get("cache_creation_input_tokens"));
 	let output = ensure_u64(obj.get("output_tokens"));
 	let reasoning = ensure_u64(obj.get("reasoning_output_tokens"));
 	let total = ensure_u64(obj.get("total_tokens"));
@@ -134,9 +243,14 @@ fn normalize_raw_usage(value: Option<&Value>) -> Option<RawUsage> {
 	Some(RawUsage {
 		input_tokens: input,
 		cached_input_tokens: cached,
+		cache_creation_input_tokens: cache_creation,
 		output_tokens: output,
 		reasoning_output_tokens: reasoning,
-		total_tokens: if total > 0 { total } else { input + output },
+		total_tokens: if total > 0 {
+			total
+		} else {
+			input + cache_creation + output
+		},
 	})
 }
 
@@ -148,6 +262,9 @@ fn subtract_raw_usage(current: RawUsage, previous: Option<RawUsage>) -> RawUsage
 		cached_input_tokens: current
 			.cached_input_tokens
 			.saturating_sub(previous.map(|p| p.cached_input_tokens).unwrap_or(0)),
+		cache_creation_input_tokens: current
+			.cache_creation_input_tokens
+			.saturating_sub(previous.map(|p| p.cache_creation_input_tokens).unwrap_or(0)),
 		output_tokens: current
 			.output_tokens
 			.saturating_sub(previous.map(|p| p.output_tokens).unwrap_or(0)),
@@ -160,25 +277,54 @@ fn subtract_raw_usage(current: RawUsage, previous: Option<RawUsage>) -> RawUsage
 	}
 }
 
-fn convert_to_delta(raw: RawUsage) -> DeltaUsage {
+fn add_raw_usage(base: Option<RawUsage>, delta: RawUsage) -> RawUsage {
+	let base = base.unwrap_or_default();
+	RawUsage {
+		input_tokens: base.input_tokens.saturating_add(delta.input_tokens),
+		cached_input_tokens: base.cached_input_tokens.saturating_add(delta.cached_input_tokens),
+		cache_creation_input_tokens: base
+			.cache_creation_input_tokens
+			.saturating_add(delta.cache_creation_input_tokens),
+		output_tokens: base.output_tokens.saturating_add(delta.output_tokens),
+		reasoning_output_tokens: base
+			.reasoning_output_tokens
+			.saturating_add(delta.reasoning_output_tokens),
+		total_tokens: base.total_tokens.saturating_add(delta.total_tokens),
+	}
+}
+
+/// 统一换算成“input 包含 cached”的口径，后续计费只需 input - cached。
+fn convert_to_delta(raw: RawUsage, convention: CodexInputConvention) -> DeltaUsage {
+	let exclusive = match convention {
+		CodexInputConvention::Inclusive => false,
+		CodexInputConvention::Exclusive => true,
+		CodexInputConvention::Auto => raw.cached_input_tokens > raw.input_tokens,
+	};
+	let input = if exclusive {
+		raw.input_tokens.saturating_add(raw.cached_input_tokens)
+	} else {
+		raw.input_tokens
+	};
+
 	let total = if raw.total_tokens > 0 {
 		raw.total_tokens
 	} else {
-		raw.input_tokens + raw.output_tokens
+		input + raw.cache_creation_input_tokens + raw.output_tokens
 	};
 
-	let cached = std::cmp::min(raw.cached_input_tokens, raw.input_tokens);
+	let cached = std::cmp::min(raw.cached_input_tokens, input);
 
 	DeltaUsage {
-		input_tokens: raw.input_tokens,
+		input_tokens: input,
 		cached_input_tokens: cached,
+		cache_creation_input_tokens: raw.cache_creation_input_tokens,
 		output_tokens: raw.output_tokens,
 		reasoning_output_tokens: raw.reasoning_output_tokens,
 		total_tokens: total,
 	}
 }
 
-fn model_alias(model: &str) -> Option<&'static str> {
+pub(crate) fn model_alias(model: &str) -> Option<&'static str> {
 	match model {
 		"gpt-5-codex" => Some("gpt-5"),
 		_ => None,
@@ -188,24 +334,48 @@ fn model_alias(model: &str) -> Option<&'static str> {
 fn pricing_for_model(
 	dataset: &HashMap<String, LiteLLMModelPricing>,
 	model: &str,
+	strict: bool,
 ) -> Option<LiteLLMModelPricing> {
-	find_model_pricing(dataset, model, &CODEX_PROVIDER_PREFIXES).or_else(|| {
-		model_alias(model)
-			.and_then(|alias| find_model_pricing(dataset, alias, &CODEX_PROVIDER_PREFIXES))
+	resolve_pricing(model, dataset, strict).map(|(_, pricing)| pricing)
+}
+
+/// 按 Codex 的计费规则（前缀 + 别名）解析价格，并返回命中的价格表 key。
+pub(crate) fn resolve_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
+) -> Option<(String, LiteLLMModelPricing)> {
+	find_model_pricing_with_key(dataset, model, &CODEX_PROVIDER_PREFIXES, strict).or_else(|| {
+		model_alias(model).and_then(|alias| {
+			find_model_pricing_with_key(dataset, alias, &CODEX_PROVIDER_PREFIXES, strict)
+		})
 	})
 }
 
-fn cost_for_tokens(
-	tokens: CodexTokens,
+/// 该模型能否在价格表里找到（与计费时的匹配规则一致）。
+pub(crate) fn has_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
+) -> bool {
+	pricing_for_model(dataset, model, strict).is_some()
+}
+
+fn cost_for_usage(
+	usage: &ModelUsage,
 	model: &str,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	strict: bool,
 ) -> f64 {
-	let pricing = pricing_for_model(dataset, model);
+	let pricing = pricing_for_model(dataset, model, strict);
 	let Some(pricing) = pricing else {
 		return 0.0;
 	};
 
-	calculate_codex_cost_from_pricing(tokens, &pricing)
+	// 分界点与计价用的是同一份价格表，带高档单价的模型一定有对应的累计；
+	// 没有高档单价的模型不需要拆分，按 0 处理即可。
+	let above_tier = usage.above_tier.get(&pricing.tier_threshold()).copied().unwrap_or_default();
+	calculate_codex_cost_with_tiers(usage.tokens, above_tier, &pricing)
 }
 
 #[derive(Debug, Default)]
@@ -213,6 +383,8 @@ struct SessionFilesCache {
 	session_dirs: Vec<PathBuf>,
 	scanned_at: Option<Instant>,
 	files: Vec<PathBuf>,
+	/// 上次扫描中 glob 出错（模式非法或目录读不了）的会话目录。
+	failed_dirs: Vec<PathBuf>,
 }
 
 static SESSION_FILES_CACHE: OnceLock<Mutex<SessionFilesCache>> = OnceLock::new();
@@ -221,15 +393,19 @@ fn session_files_cache() -> &'static Mutex<SessionFilesCache> {
 	SESSION_FILES_CACHE.get_or_init(|| Mutex::new(SessionFilesCache::default()))
 }
 
+/// 让下一次 [`session_files_from_dirs`] 重新扫描目录（例如删除/新增了会话文件之后）。
+pub(crate) fn invalidate_session_files_cache() {
+	cache_lock::lock_or_reset(session_files_cache())
+		.scanned_at = None;
+}
+
 pub fn session_files_from_dirs(session_dirs: &[PathBuf]) -> Vec<PathBuf> {
 	if session_dirs.is_empty() {
 		return Vec::new();
 	}
 
 	{
-		let guard = session_files_cache()
-			.lock()
-			.expect("session_files_cache lock poisoned");
+		let guard = cache_lock::lock_or_reset(session_files_cache());
 		if guard.session_dirs == session_dirs {
 			if let Some(scanned_at) = guard.scanned_at {
 				if Instant::now().duration_since(scanned_at) < SESSION_FILES_TTL {
@@ -240,30 +416,66 @@ pub fn session_files_from_dirs(session_dirs: &[PathBuf]) -> Vec<PathBuf> {
 	}
 
 	let mut files = Vec::new();
+	let mut failed_dirs = Vec::new();
 	for dir in session_dirs {
-		let pattern = dir
-			.join("**")
-			.join("*.jsonl")
-			.to_string_lossy()
-			.to_string();
-		for entry in glob(&pattern).unwrap_or_else(|_| glob("").expect("glob fallback failed")) {
-			if let Ok(path) = entry {
-				files.push(path);
+		let mut failed = false;
+		for file_pattern in SESSION_FILE_PATTERNS {
+			// 目录名里的 `[`、`*` 等要转义，否则会被当成 glob 语法。
+			let escaped = PathBuf::from(Pattern::escape(&dir.to_string_lossy()));
+			let pattern = escaped.join("**").join(file_pattern).to_string_lossy().to_string();
+			let Ok(paths) = glob(&pattern) else {
+				failed = true;
+				continue;
+			};
+			for entry in paths {
+				match entry {
+					Ok(path) => files.push(path),
+					Err(_) => failed = true,
+				}
 			}
 		}
+		if failed {
+			failed_dirs.push(dir.clone());
+		}
 	}
 
 	{
-		let mut guard = session_files_cache()
-			.lock()
-			.expect("session_files_cache lock poisoned");
+		let mut guard = cache_lock::lock_or_reset(session_files_cache());
 		guard.session_dirs = session_dirs.to_vec();
 		guard.scanned_at = Some(Instant::now());
 		guard.files = files.clone();
+		guard.failed_dirs = failed_dirs;
 	}
 	files
 }
 
+/// 是否是会话日志文件（`*.jsonl` 或压缩过的 `*.jsonl.gz`）。
+pub(crate) fn is_session_file(path: &Path) -> bool {
+	path.file_name()
+		.and_then(|name| name.to_str())
+		.is_some_and(|name| name.ends_with(".jsonl") || name.ends_with(".jsonl.gz"))
+}
+
+/// 最近一次 [`session_files_from_dirs`] 扫描失败的目录；为空表示“没数据”就是真的没数据。
+pub(crate) fn session_scan_failures() -> Vec<PathBuf> {
+	cache_lock::lock_or_reset(session_files_cache())
+		.failed_dirs
+		.clone()
+}
+
+/// 逐行读取会话文件；`.gz` 结尾的文件先解压。
+///
+/// 说明：普通文件跳过无法读取的行（与之前一致）；压缩文件一旦解压出错就停止，
+/// 因为损坏的压缩流会一直返回同一个错误，继续读只会空转。
+pub(crate) fn session_file_lines(path: &Path) -> Option<Box<dyn Iterator<Item = String>>> {
+	let file = File::open(path).ok()?;
+	if path.extension().is_some_and(|ext| ext == "gz") {
+		let reader = BufReader::new(GzDecoder::new(file));
+		return Some(Box::new(reader.lines().map_while(Result::ok)));
+	}
+	Some(Box::new(BufReader::new(file).lines().flatten()))
+}
+
 pub fn default_codex_session_dirs() -> Vec<PathBuf> {
 	fn is_dir(path: &Path) -> bool {
 		std::fs::metadata(path).map(|m| m.is_dir()).unwrap_or(false)
@@ -279,306 +491,640 @@ pub fn default_codex_session_dirs() -> Vec<PathBuf> {
 			.join(base)
 	}
 
+	let mut candidates = Vec::new();
 	let home = std::env::var("HOME").unwrap_or_default();
-	if home.is_empty() {
-		return Vec::new();
+	if !home.is_empty() {
+		let codex_home = std::env::var(CODEX_HOME_ENV)
+			.ok()
+			.map(|v| v.trim().to_string())
+			.filter(|v| !v.is_empty())
+			.map(|v| resolve_like_node(&v))
+			.unwrap_or_else(|| PathBuf::from(format!("{home}/{DEFAULT_CODEX_DIR}")));
+		candidates.push(codex_home.join(DEFAULT_SESSION_SUBDIR));
 	}
 
-	let codex_home = std::env::var(CODEX_HOME_ENV)
-		.ok()
-		.map(|v| v.trim().to_string())
-		.filter(|v| !v.is_empty())
-		.map(|v| resolve_like_node(&v))
-		.unwrap_or_else(|| PathBuf::from(format!("{home}/{DEFAULT_CODEX_DIR}")));
+	// 额外目录可以是 Codex home（取其下的 `sessions`），也可以直接是 sessions 目录。
+	let extra = std::env::var(EXTRA_CODEX_DIRS_ENV).unwrap_or_default();
+	for entry in extra.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+		let base = resolve_like_node(entry);
+		let sessions = base.join(DEFAULT_SESSION_SUBDIR);
+		candidates.push(if is_dir(&sessions) { sessions } else { base });
+	}
 
-	let default_sessions = codex_home.join(DEFAULT_SESSION_SUBDIR);
-	if is_dir(&default_sessions) {
-		vec![default_sessions]
-	} else {
-		Vec::new()
+	// 同一目录可能以不同写法出现（相对/绝对路径、符号链接），按规范化路径去重，避免重复计数。
+	let mut seen = HashSet::new();
+	candidates
+		.into_iter()
+		.filter(|dir| is_dir(dir))
+		.filter(|dir| seen.insert(std::fs::canonicalize(dir).unwrap_or_else(|_| dir.clone())))
+		.collect()
+}
+
+/// 单个模型累计的 token（用于按模型计价与输出明细）。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ModelUsage {
+	tokens: CodexTokens,
+	/// 按分界点（[`ScanOptions::tier_thresholds`]）逐条请求累加的超出部分
+	/// （[`CodexTokens::above_tier`]），用于分档计价。
+	///
+	/// 说明：解析时还不知道命中的价格，所以每个分界点各存一份，计价时再按模型的分界点取用。
+	above_tier: BTreeMap<u64, CodexTokens>,
+	total_tokens: u64,
+	reasoning_tokens: u64,
+	/// 计入的记录里最晚的本地日期（全量扫描时也会记录，用于 `--models`）。
+	last_used: Option<NaiveDate>,
+	/// 有记录因为日志没写模型而记在了 [`LEGACY_FALLBACK_MODEL`] 名下。
+	is_estimated: bool,
+}
+
+impl ModelUsage {
+	fn merge(&mut self, other: ModelUsage) {
+		self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+		self.reasoning_tokens = self.reasoning_tokens.saturating_add(other.reasoning_tokens);
+		self.tokens = self.tokens.saturating_add(other.tokens);
+		for (threshold, above) in other.above_tier {
+			let entry = self.above_tier.entry(threshold).or_default();
+			*entry = entry.saturating_add(above);
+		}
+		self.last_used = self.last_used.max(other.last_used);
+		self.is_estimated |= other.is_estimated;
+	}
+
+	fn touch(&mut self, timestamp: Option<&str>) {
+		let date = timestamp.and_then(parse_js_timestamp).map(|parsed| parsed.local_date);
+		self.last_used = self.last_used.max(date);
+	}
+
+	/// 计入一次请求；`thresholds` 为空时不做分档拆分。
+	fn add_delta(&mut self, delta: DeltaUsage, thresholds: &[u64]) {
+		self.total_tokens = self.total_tokens.saturating_add(delta.total_tokens);
+		self.reasoning_tokens = self.reasoning_tokens.saturating_add(delta.reasoning_output_tokens);
+		let request = CodexTokens {
+			input_tokens: delta.input_tokens,
+			cached_input_tokens: delta.cached_input_tokens,
+			output_tokens: delta.output_tokens,
+			cache_creation_input_tokens: delta.cache_creation_input_tokens,
+		};
+		self.tokens = self.tokens.saturating_add(request);
+		for &threshold in thresholds {
+			let entry = self.above_tier.entry(threshold).or_default();
+			*entry = entry.saturating_add(request.above_tier(threshold));
+		}
 	}
 }
 
-pub fn load_codex_totals_from_files_with_pricing(
+fn in_bounds(bounds: Option<(NaiveDate, NaiveDate)>, timestamp: Option<&str>) -> bool {
+	match (bounds, timestamp) {
+		(Some((since, until)), Some(timestamp)) => {
+			parse_local_date_if_in_range(timestamp, since, until).is_some()
+		}
+		(Some(_), None) => false,
+		(None, _) => true,
+	}
+}
+
+/// 扫描 Codex 会话文件，按模型累计 token。
+///
+/// 说明：
+/// - `bounds` 为 None 表示全量（all-time）统计：不要求事件带 timestamp。
+/// - 有日期范围时，缺少 timestamp 的 token_count 事件整体跳过（也不参与 `total_token_usage` 的差分基线），
+///   与 all-time 的口径差异是有意保留的。
+fn scan_codex_files(
 	files: &[PathBuf],
-	range: &DateRange,
-	dataset: &HashMap<String, LiteLLMModelPricing>,
-) -> UsageTotals {
-	let Some(since) = parse_yyyymmdd(&range.since_yyyymmdd) else {
-		return UsageTotals::default();
-	};
-	let Some(until) = parse_yyyymmdd(&range.until_yyyymmdd) else {
-		return UsageTotals::default();
-	};
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
+) -> HashMap<String, ModelUsage> {
+	// 每个文件的差分基线/当前模型互不相关：并行扫描后再按模型合并，结果与顺序扫描一致。
+	let mut model_usage: HashMap<String, ModelUsage> = HashMap::new();
+	for per_file in parallel::map_files(files, |path| scan_codex_file(path, bounds, options)) {
+		for (model, usage) in per_file {
+			model_usage.entry(model).or_default().merge(usage);
+		}
+	}
+	model_usage
+}
 
-	let should_calculate_cost = !dataset.is_empty();
+/// 单个会话文件的解析状态：逐行喂入，读完后调用 `finish` 得到按模型的累计。
+///
+/// 说明：全量统计的增量缓存会把未 `finish` 的状态落盘，下次从已解析的位置接着喂新追加的行。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CodexFileParser {
+	previous_totals: Option<RawUsage>,
+	current_model: Option<String>,
+	current_model_is_fallback: bool,
+	/// FinalSnapshot 口径：(快照, 时间戳, 模型, 是否为回退模型)，文件读完后才计入。
+	final_snapshot: Option<(RawUsage, Option<String>, String, bool)>,
+	model_usage: HashMap<String, ModelUsage>,
+}
 
-	let mut totals = UsageTotals::default();
-	let mut model_tokens: HashMap<String, CodexTokens> = HashMap::new();
+impl CodexFileParser {
+	/// 喂入一行；返回值只给诊断统计用，正常统计直接忽略。
+	fn feed(
+		&mut self,
+		line: &str,
+		bounds: Option<(NaiveDate, NaiveDate)>,
+		options: &ScanOptions,
+	) -> LineOutcome {
+		let trimmed = line.trim();
+		if trimmed.is_empty() {
+			return LineOutcome::Ignored;
+		}
+		if !trimmed.contains("\"event_msg\"")
+			&& !trimmed.contains("\"turn_context\"")
+			&& !trimmed.contains("_token_usage\"")
+		{
+			return LineOutcome::Ignored;
+		}
 
-	for file_path in files {
-		let Ok(file) = File::open(file_path) else {
-			continue;
+		let Ok(entry) = serde_json::from_str::<Value>(trimmed) else {
+			return LineOutcome::Unparseable;
 		};
-		let reader = BufReader::new(file);
 
-		let mut previous_totals: Option<RawUsage> = None;
-		let mut current_model: Option<String> = None;
-		let mut current_model_is_fallback = false;
+		let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
+		let nested_payload = entry.get("payload");
+		let timestamp = entry.get("timestamp").and_then(|v| v.as_str());
 
-		for line in reader.lines().flatten() {
-			let trimmed = line.trim();
-			if trimmed.is_empty() {
-				continue;
-			}
-			if !trimmed.contains("\"event_msg\"") && !trimmed.contains("\"turn_context\"") {
-				continue;
+		if entry_type == "turn_context" {
+			if let Some(model) = extract_model(nested_payload.unwrap_or(&Value::Null)) {
+				self.current_model = Some(model);
+				self.current_model_is_fallback = false;
 			}
+			return LineOutcome::Counted;
+		}
 
-			let Ok(entry) = serde_json::from_str::<Value>(trimmed) else {
-				continue;
-			};
-
-			let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
-			let payload = entry.get("payload").unwrap_or(&Value::Null);
-			let timestamp = entry.get("timestamp").and_then(|v| v.as_str());
-
-			if entry_type == "turn_context" {
-				if let Some(model) = extract_model(payload) {
-					current_model = Some(model);
-					current_model_is_fallback = false;
+		// 说明：部分版本没有 `payload` 包裹，`info` 直接平铺在条目根上；
+		// 只有在没有 `payload` 时才读根上的 `info`，避免同一条记录被读两次。
+		let payload = match nested_payload {
+			Some(payload) => {
+				if entry_type != "event_msg"
+					|| payload.get("type").and_then(|v| v.as_str()) != Some("token_count")
+				{
+					return LineOutcome::Ignored;
 				}
-				continue;
+				payload
 			}
-
-			if entry_type != "event_msg" {
-				continue;
+			None => {
+				if !entry.get("info").is_some_and(Value::is_object) {
+					return LineOutcome::Ignored;
+				}
+				&entry
 			}
+		};
 
-			if payload.get("type").and_then(|v| v.as_str()) != Some("token_count") {
-				continue;
-			}
+		if bounds.is_some() && timestamp.is_none() {
+			return LineOutcome::OutOfRange;
+		}
 
-			let Some(timestamp) = timestamp else {
-				continue;
-			};
+		let info = payload.get("info").unwrap_or(&Value::Null);
+		let last_usage = normalize_raw_usage(info.get("last_token_usage"));
+		let total_usage = normalize_raw_usage(info.get("total_token_usage"));
 
-			let info = payload.get("info").unwrap_or(&Value::Null);
-			let last_usage = normalize_raw_usage(info.get("last_token_usage"));
-			let total_usage = normalize_raw_usage(info.get("total_token_usage"));
+		let mut raw = last_usage;
+		if raw.is_none() {
+			if let Some(total_usage) = total_usage {
+				raw = Some(subtract_raw_usage(total_usage, self.previous_totals));
+			}
+		}
 
-			let mut raw = last_usage;
-			if raw.is_none() {
-				if let Some(total_usage) = total_usage {
-					raw = Some(subtract_raw_usage(total_usage, previous_totals));
-				}
+		// 差分基线要覆盖每一个已计入的回合：只有 `last` 的事件也要把基线往前推，
+		// 否则下一条只有 `total` 的事件会把这一回合再算一遍。
+		match (total_usage, last_usage) {
+			(Some(total_usage), _) => self.previous_totals = Some(total_usage),
+			(None, Some(last_usage)) => {
+				self.previous_totals = Some(add_raw_usage(self.previous_totals, last_usage));
 			}
+			(None, None) => {}
+		}
 
+		if options.accounting == CodexAccounting::FinalSnapshot {
+			if let Some(extracted_model) = extract_model(payload) {
+				self.current_model = Some(extracted_model);
+			}
 			if let Some(total_usage) = total_usage {
-				previous_totals = Some(total_usage);
+				let is_fallback_model = self.current_model.is_none();
+				let model = self
+					.current_model
+					.clone()
+					.unwrap_or_else(|| LEGACY_FALLBACK_MODEL.to_string());
+				let in_range = in_bounds(bounds, timestamp) && options.allows_timestamp(timestamp);
+				let timestamp = timestamp.map(str::to_string);
+				self.final_snapshot = Some((total_usage, timestamp, model, is_fallback_model));
+				// 快照仍然记下（后面的快照会覆盖它），但这一行本身没有计入统计。
+				if !in_range {
+					return LineOutcome::OutOfRange;
+				}
 			}
+			return LineOutcome::Counted;
+		}
 
-			let Some(raw) = raw else {
-				continue;
-			};
+		let Some(raw) = raw else {
+			return LineOutcome::Ignored;
+		};
 
-			let delta = convert_to_delta(raw);
-			if delta.input_tokens == 0
-				&& delta.cached_input_tokens == 0
-				&& delta.output_tokens == 0
-				&& delta.reasoning_output_tokens == 0
-			{
-				continue;
-			}
+		let delta = convert_to_delta(raw, options.input_convention);
+		if delta.input_tokens == 0
+			&& delta.cached_input_tokens == 0
+			&& delta.cache_creation_input_tokens == 0
+			&& delta.output_tokens == 0
+			&& delta.reasoning_output_tokens == 0
+		{
+			return LineOutcome::Ignored;
+		}
 
-			let extracted = extract_model(payload);
-			let extracted_is_none = extracted.is_none();
-			let mut is_fallback_model = false;
+		let extracted = extract_model(payload);
+		let extracted_is_none = extracted.is_none();
+		let mut is_fallback_model = false;
 
-			if let Some(extracted_model) = extracted.clone() {
-				current_model = Some(extracted_model);
-				current_model_is_fallback = false;
-			}
+		if let Some(extracted_model) = extracted.clone() {
+			self.current_model = Some(extracted_model);
+			self.current_model_is_fallback = false;
+		}
 
-			let mut model = extracted.or_else(|| current_model.clone());
-			if model.is_none() {
-				model = Some(LEGACY_FALLBACK_MODEL.to_string());
-				is_fallback_model = true;
-				current_model = model.clone();
-				current_model_is_fallback = true;
-			} else if extracted_is_none && current_model_is_fallback {
-				is_fallback_model = true;
-			}
+		let mut model = extracted.or_else(|| self.current_model.clone());
+		if model.is_none() {
+			model = Some(LEGACY_FALLBACK_MODEL.to_string());
+			is_fallback_model = true;
+			self.current_model = model.clone();
+			self.current_model_is_fallback = true;
+		} else if extracted_is_none && self.current_model_is_fallback {
+			is_fallback_model = true;
+		}
 
-				let model = model.unwrap_or_else(|| LEGACY_FALLBACK_MODEL.to_string());
-				let _ = is_fallback_model; // reserved for later surfacing/annotation
-				if parse_local_date_if_in_range(timestamp, since, until).is_none() {
-					continue;
-				}
+		let model = model.unwrap_or_else(|| LEGACY_FALLBACK_MODEL.to_string());
+		if !in_bounds(bounds, timestamp) || !options.allows_timestamp(timestamp) {
+			return LineOutcome::OutOfRange;
+		}
 
-				totals.total_tokens = totals.total_tokens.saturating_add(delta.total_tokens);
-				if should_calculate_cost {
-					let entry = model_tokens.entry(model.clone()).or_default();
-					entry.input_tokens = entry.input_tokens.saturating_add(delta.input_tokens);
-					entry.cached_input_tokens = entry
-						.cached_input_tokens
-						.saturating_add(delta.cached_input_tokens);
-					entry.output_tokens = entry.output_tokens.saturating_add(delta.output_tokens);
-				}
+		let usage = self.model_usage.entry(model).or_default();
+		usage.add_delta(delta, &options.tier_thresholds);
+		usage.touch(timestamp);
+		usage.is_estimated |= is_fallback_model;
+		LineOutcome::Counted
+	}
+
+	fn finish(
+		mut self,
+		bounds: Option<(NaiveDate, NaiveDate)>,
+		options: &ScanOptions,
+	) -> HashMap<String, ModelUsage> {
+		if let Some((snapshot, timestamp, model, is_fallback_model)) = self.final_snapshot {
+			if in_bounds(bounds, timestamp.as_deref())
+				&& options.allows_timestamp(timestamp.as_deref())
+			{
+				let delta = convert_to_delta(snapshot, options.input_convention);
+				let usage = self.model_usage.entry(model).or_default();
+				// 快照是整个会话的累计，分不出单次请求是否超过分界点，只按基础单价计。
+				usage.add_delta(delta, &[]);
+				usage.touch(timestamp.as_deref());
+				usage.is_estimated |= is_fallback_model;
 			}
 		}
 
-		if should_calculate_cost {
-			for (model, tokens) in model_tokens {
-				totals.cost_usd += cost_for_tokens(tokens, &model, dataset);
-		}
-	}
 
-		totals
+		self.model_usage
 	}
+}
 
-	pub fn load_codex_totals_from_files_all_time_with_pricing(
-		files: &[PathBuf],
-		dataset: &HashMap<String, LiteLLMModelPricing>,
-	) -> UsageTotals {
-		let should_calculate_cost = !dataset.is_empty();
-
-		let mut totals = UsageTotals::default();
-		let mut model_tokens: HashMap<String, CodexTokens> = HashMap::new();
+fn scan_codex_file(
+	file_path: &Path,
+	bounds: Option<(NaiveDate, NaiveDate)>,
+	options: &ScanOptions,
+) -> HashMap<String, ModelUsage> {
+	let Some(lines) = session_file_lines(file_path) else {
+		return HashMap::new();
+	};
+	let mut parser = CodexFileParser::default();
+	for line in lines {
+		parser.feed(&line, bounds, options);
+	}
+	parser.finish(bounds, options)
+}
 
-		for file_path in files {
-			let Ok(file) = File::open(file_path) else {
-				continue;
-			};
-			let reader = BufReader::new(file);
+/// “全部”增量缓存的文件名（`~/.tokbar/cache/codex-all-time.json`）。
+const OFFSET_CACHE_NAME: &str = "codex-all-time";
 
-			let mut previous_totals: Option<RawUsage> = None;
-			let mut current_model: Option<String> = None;
-			let mut current_model_is_fallback = false;
+/// 增量缓存里单个会话文件的状态：解析到哪里、以及解析器在那一刻的状态。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedCodexFile {
+	checkpoint: FileCheckpoint,
+	parser: CodexFileParser,
+}
 
-			for line in reader.lines().flatten() {
-				let trimmed = line.trim();
-				if trimmed.is_empty() {
-					continue;
-				}
-				if !trimmed.contains("\"event_msg\"") && !trimmed.contains("\"turn_context\"") {
-					continue;
-				}
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CodexOffsetCache {
+	version: u32,
+	/// 口径选项的指纹：切换累计口径/输入口径/工作时间后缓存作废。
+	options_key: u64,
+	files: HashMap<PathBuf, CachedCodexFile>,
+}
 
-				let Ok(entry) = serde_json::from_str::<Value>(trimmed) else {
-					continue;
-				};
+pub(crate) fn all_time_offset_cache_path() -> Option<PathBuf> {
+	offset_cache::default_cache_path(OFFSET_CACHE_NAME)
+}
 
-				let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
-				let payload = entry.get("payload").unwrap_or(&Value::Null);
+/// 全量扫描，但每个文件从上次解析到的位置继续；缓存里只存 token，计价在合并后进行。
+fn scan_codex_files_incremental(
+	files: &[PathBuf],
+	options: &ScanOptions,
+	cache_path: Option<&Path>,
+) -> HashMap<String, ModelUsage> {
+	let options_key = offset_cache::fnv1a64(format!("{options:?}").as_bytes());
+	let mut cache: CodexOffsetCache = offset_cache::load_cache(cache_path);
+	if cache.version != offset_cache::CACHE_VERSION || cache.options_key != options_key {
+		cache = CodexOffsetCache {
+			version: offset_cache::CACHE_VERSION,
+			options_key,
+			files: HashMap::new(),
+		};
+	}
 
-				if entry_type == "turn_context" {
-					if let Some(model) = extract_model(payload) {
-						current_model = Some(model);
-						current_model_is_fallback = false;
-					}
-					continue;
-				}
+	// 不在本次文件列表里的条目随之丢弃，缓存不会无限增长。
+	let previous = std::mem::take(&mut cache.files);
+	let results = parallel::map_files(files, |path| {
+		scan_codex_file_resuming(path, previous.get(path), options)
+	});
 
-				if entry_type != "event_msg" {
-					continue;
-				}
+	let mut model_usage: HashMap<String, ModelUsage> = HashMap::new();
+	for (path, (per_file, cached)) in files.iter().zip(results) {
+		if let Some(cached) = cached {
+			cache.files.insert(path.clone(), cached);
+		}
+		for (model, usage) in per_file {
+			model_usage.entry(model).or_default().merge(usage);
+		}
+	}
+	offset_cache::save_cache(cache_path, &cache);
+	model_usage
+}
 
-				if payload.get("type").and_then(|v| v.as_str()) != Some("token_count") {
-					continue;
-				}
+/// 解析单个文件（能续读就续读），返回本文件的累计与新的缓存条目。
+fn scan_codex_file_resuming(
+	path: &Path,
+	cached: Option<&CachedCodexFile>,
+	options: &ScanOptions,
+) -> (HashMap<String, ModelUsage>, Option<CachedCodexFile>) {
+	let Some(stamp) = FileStamp::of(path) else {
+		return (HashMap::new(), None);
+	};
 
-				let info = payload.get("info").unwrap_or(&Value::Null);
-				let last_usage = normalize_raw_usage(info.get("last_token_usage"));
-				let total_usage = normalize_raw_usage(info.get("total_token_usage"));
+	// 压缩文件没法按字节位置续读：没变就直接复用，变了整份重新解析。
+	if path.extension().is_some_and(|ext| ext == "gz") {
+		let unchanged = cached.filter(|cached| {
+			let checkpoint = cached.checkpoint;
+			checkpoint.len == stamp.len && checkpoint.modified_ms == stamp.modified_ms
+		});
+		if let Some(cached) = unchanged {
+			return (cached.parser.clone().finish(None, options), Some(cached.clone()));
+		}
+		let Some(lines) = session_file_lines(path) else {
+			return (HashMap::new(), None);
+		};
+		let mut parser = CodexFileParser::default();
+		for line in lines {
+			parser.feed(&line, None, options);
+		}
+		let checkpoint = FileCheckpoint {
+			len: stamp.len,
+			modified_ms: stamp.modified_ms,
+			..FileCheckpoint::default()
+		};
+		let cached = CachedCodexFile {
+			checkpoint,
+			parser: parser.clone(),
+		};
+		return (parser.finish(None, options), Some(cached));
+	}
 
-				let mut raw = last_usage;
-				if raw.is_none() {
-					if let Some(total_usage) = total_usage {
-						raw = Some(subtract_raw_usage(total_usage, previous_totals));
-					}
-				}
+	let resumed = cached.and_then(|cached| {
+		let offset = offset_cache::resume_offset(path, &cached.checkpoint, stamp)?;
+		Some((cached.parser.clone(), offset))
+	});
+	let (mut parser, offset) = resumed.unwrap_or_default();
+	let Some((offset, partial)) =
+		offset_cache::read_lines_from(path, offset, |line| {
+			parser.feed(line, None, options);
+		})
+	else {
+		return (HashMap::new(), None);
+	};
 
-				if let Some(total_usage) = total_usage {
-					previous_totals = Some(total_usage);
-				}
+	let cached = CachedCodexFile {
+		checkpoint: offset_cache::checkpoint_at(path, stamp, offset),
+		parser: parser.clone(),
+	};
+	// 还没写完的最后一行本次照常计入，但不进缓存：下次会从它的开头重新读。
+	if let Some(partial) = partial {
+		parser.feed(&partial, None, options);
+	}
+	(parser.finish(None, options), Some(cached))
+}
 
-				let Some(raw) = raw else {
-					continue;
-				};
+fn breakdown_with_pricing(
+	model_usage: HashMap<String, ModelUsage>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let should_calculate_cost = !dataset.is_empty();
+	model_usage
+		.into_iter()
+		.map(|(model, usage)| {
+			let cost_usd = if should_calculate_cost {
+				cost_for_usage(&usage, &model, dataset, options.strict_model_matching)
+			} else {
+				0.0
+			};
+			let totals = UsageTotals {
+				total_tokens: usage.total_tokens,
+				cost_usd,
+				reasoning_tokens: usage.reasoning_tokens,
+				cache_read_tokens: usage.tokens.cached_input_tokens,
+				cache_creation_tokens: usage.tokens.cache_creation_input_tokens,
+				is_estimated: usage.is_estimated,
+			};
+			(model, totals)
+		})
+		.collect()
+}
 
-				let delta = convert_to_delta(raw);
-				if delta.input_tokens == 0
-					&& delta.cached_input_tokens == 0
-					&& delta.output_tokens == 0
-					&& delta.reasoning_output_tokens == 0
-				{
-					continue;
-				}
+fn sum_breakdown(breakdown: HashMap<String, UsageTotals>) -> UsageTotals {
+	let mut totals = UsageTotals::default();
+	for model_totals in breakdown.into_values() {
+		totals += model_totals;
+	}
+	totals
+}
 
-				let extracted = extract_model(payload);
-				let extracted_is_none = extracted.is_none();
-				let mut is_fallback_model = false;
+/// 按模型拆分的 token/成本统计（key 为日志里的模型名；无法识别模型时归入 `gpt-5`）。
+pub fn load_codex_breakdown_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let Some(bounds) = range_bounds(range) else {
+		return HashMap::new();
+	};
 
-				if let Some(extracted_model) = extracted.clone() {
-					current_model = Some(extracted_model);
-					current_model_is_fallback = false;
-				}
+	let options = options.for_range(range).with_tier_thresholds(dataset);
+	let model_usage = scan_codex_files(files, Some(bounds), &options);
+	breakdown_with_pricing(model_usage, dataset, &options)
+}
 
-				let mut model = extracted.or_else(|| current_model.clone());
-				if model.is_none() {
-					model = Some(LEGACY_FALLBACK_MODEL.to_string());
-					is_fallback_model = true;
-					current_model = model.clone();
-					current_model_is_fallback = true;
-				} else if extracted_is_none && current_model_is_fallback {
-					is_fallback_model = true;
-				}
+/// 把任意来源的行（如 stdin）按会话文件解析，按模型拆分；`range` 为 None 时不按日期过滤。
+///
+/// 说明：多个会话文件拼接输入时，每遇到一行 `session_meta` 就开始一个新会话，
+/// 差分基线与当前模型不会跨会话沿用。
+pub fn load_codex_breakdown_from_lines_with_pricing(
+	lines: impl IntoIterator<Item = String>,
+	range: Option<&DateRange>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
+	let bounds = match range {
+		Some(range) => match range_bounds(range) {
+			Some(bounds) => Some(bounds),
+			None => return HashMap::new(),
+		},
+		None => None,
+	};
 
-				let model = model.unwrap_or_else(|| LEGACY_FALLBACK_MODEL.to_string());
-				let _ = is_fallback_model; // reserved for later surfacing/annotation
-
-				totals.total_tokens = totals.total_tokens.saturating_add(delta.total_tokens);
-				if should_calculate_cost {
-					let entry = model_tokens.entry(model.clone()).or_default();
-					entry.input_tokens = entry.input_tokens.saturating_add(delta.input_tokens);
-					entry.cached_input_tokens = entry
-						.cached_input_tokens
-						.saturating_add(delta.cached_input_tokens);
-					entry.output_tokens = entry.output_tokens.saturating_add(delta.output_tokens);
-				}
+	let options = range
+		.map_or_else(|| options.clone(), |range| options.for_range(range))
+		.with_tier_thresholds(dataset);
+	let mut model_usage: HashMap<String, ModelUsage> = HashMap::new();
+	let mut parser = CodexFileParser::default();
+	for line in lines {
+		if is_session_meta(&line) {
+			let finished = std::mem::take(&mut parser).finish(bounds, &options);
+			for (model, usage) in finished {
+				model_usage.entry(model).or_default().merge(usage);
 			}
 		}
+		parser.feed(&line, bounds, &options);
+	}
+	for (model, usage) in parser.finish(bounds, &options) {
+		model_usage.entry(model).or_default().merge(usage);
+	}
+	breakdown_with_pricing(model_usage, dataset, &options)
+}
 
-		if should_calculate_cost {
-			for (model, tokens) in model_tokens {
-				totals.cost_usd += cost_for_tokens(tokens, &model, dataset);
-			}
-		}
+/// 会话文件的首行（`"type": "session_meta"`）。
+fn is_session_meta(line: &str) -> bool {
+	line.contains("\"session_meta\"")
+		&& serde_json::from_str::<Value>(line.trim())
+			.is_ok_and(|entry| entry.get("type").and_then(Value::as_str) == Some("session_meta"))
+}
 
-		totals
+/// 与 [`load_codex_totals_from_files_with_pricing`] 同口径，额外统计每行的处理结果（诊断用）。
+///
+/// 说明：顺序扫描、不走并行与增量缓存，只在用户主动查看诊断时调用。
+pub fn load_codex_totals_with_diagnostics_from_files(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> (UsageTotals, ScanDiagnostics) {
+	let mut diagnostics = ScanDiagnostics::default();
+	let Some(bounds) = range_bounds(range) else {
+		return (UsageTotals::default(), diagnostics);
+	};
+	let bounds = Some(bounds);
+	let options = options.for_range(range).with_tier_thresholds(dataset);
+
+	let mut model_usage: HashMap<String, ModelUsage> = HashMap::new();
+	for path in files {
+		let Some(lines) = session_file_lines(path) else {
+			continue;
+		};
+		diagnostics.files += 1;
+		let mut parser = CodexFileParser::default();
+		for line in lines {
+			diagnostics.record(parser.feed(&line, bounds, &options));
+		}
+		for (model, usage) in parser.finish(bounds, &options) {
+			model_usage.entry(model).or_default().merge(usage);
+		}
 	}
+	(sum_breakdown(breakdown_with_pricing(model_usage, dataset, &options)), diagnostics)
+}
+
+pub fn load_codex_totals_from_files_with_pricing(
+	files: &[PathBuf],
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> UsageTotals {
+	sum_breakdown(load_codex_breakdown_from_files_with_pricing(files, range, dataset, options))
+}
+
+pub fn load_codex_totals_from_files_all_time_with_pricing(
+	files: &[PathBuf],
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+) -> UsageTotals {
+	let options = options.clone().with_tier_thresholds(dataset);
+	let model_usage = scan_codex_files(files, None, &options);
+	sum_breakdown(breakdown_with_pricing(model_usage, dataset, &options))
+}
+
+/// 同 [`load_codex_totals_from_files_all_time_with_pricing`]，但借助 `cache_path` 处的增量缓存
+/// 只解析上次之后新追加的内容；`cache_path` 为 None 时等同于全量解析。
+pub fn load_codex_totals_all_time_incremental(
+	files: &[PathBuf],
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
+	cache_path: Option<&Path>,
+) -> UsageTotals {
+	let options = options.clone().with_tier_thresholds(dataset);
+	sum_breakdown(breakdown_with_pricing(
+		scan_codex_files_incremental(files, &options, cache_path),
+		dataset,
+		&options,
+	))
+}
+
+/// 全量扫描：每个模型的 token 合计与最后使用日期（不计价）。
+pub fn load_codex_model_history_from_files(
+	files: &[PathBuf],
+	options: &ScanOptions,
+) -> HashMap<String, ModelHistory> {
+	scan_codex_files(files, None, options)
+		.into_iter()
+		.map(|(model, usage)| {
+			let history = ModelHistory {
+				total_tokens: usage.total_tokens,
+				last_used: usage.last_used,
+			};
+			(model, history)
+		})
+		.collect()
+}
 
 pub fn load_codex_totals_from_session_dirs_with_pricing(
 	session_dirs: &[PathBuf],
 	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &ScanOptions,
 ) -> UsageTotals {
 	let files = session_files_from_dirs(session_dirs);
-	load_codex_totals_from_files_with_pricing(&files, range, dataset)
+	load_codex_totals_from_files_with_pricing(&files, range, dataset, options)
 }
 
-pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
+pub fn load_codex_breakdown_from_session_dirs_with_pricing(
 	session_dirs: &[PathBuf],
+	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
-) -> UsageTotals {
+	options: &ScanOptions,
+) -> HashMap<String, UsageTotals> {
 	let files = session_files_from_dirs(session_dirs);
-	load_codex_totals_from_files_all_time_with_pricing(&files, dataset)
+	load_codex_breakdown_from_files_with_pricing(&files, range, dataset, options)
 }
 
 	#[cfg(test)]
 	mod tests {
 		use super::*;
+		use crate::test_util;
+		use crate::pricing::calculate_codex_cost_from_pricing;
 		use chrono::Local;
 		use chrono::TimeZone;
 
@@ -630,11 +1176,7 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 		std::fs::create_dir_all(&sessions).expect("mkdir");
 
 		let file_path = sessions.join("s1.jsonl");
-		let day = Local
-			.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
-			.single()
-			.expect("local dt")
-			.to_rfc3339();
+		let day = test_util::local_noon_20260206();
 
 		// First event uses total_token_usage without last_token_usage -> delta = totals - previous (0).
 		// Second event uses last_token_usage directly.
@@ -667,7 +1209,7 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 					"info": {
 						"last_token_usage": {
 							"input_tokens": 100,
-							"cached_input_tokens": 9999, // should be clamped to <= input
+							"cached_input_tokens": 9999, // cached > input -> input is net-of-cache
 							"output_tokens": 50,
 							"reasoning_output_tokens": 0,
 							"total_tokens": 150
@@ -684,11 +1226,7 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 			.join("\n");
 		std::fs::write(&file_path, content).expect("write");
 
-		let range = DateRange {
-			since_yyyymmdd: "20260206".to_string(),
-			until_yyyymmdd: "20260206".to_string(),
-			label: "Today",
-		};
+		let range = test_util::day_range("20260206");
 
 		let mut dataset = HashMap::new();
 		dataset.insert(
@@ -701,12 +1239,175 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 			},
 		);
 
-		let totals = load_codex_totals_from_files_with_pricing(&[file_path], &range, &dataset);
+		let options = ScanOptions::default();
+		let totals =
+			load_codex_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
 		assert_eq!(totals.total_tokens, 1500 + 150);
 
 		let cost1 = (800.0 * 1.25e-6) + (200.0 * 1.25e-7) + (500.0 * 1e-5);
-		let cost2 = (0.0 * 1.25e-6) + (100.0 * 1.25e-7) + (50.0 * 1e-5); // cached clamped to 100
+		let cost2 = (100.0 * 1.25e-6) + (9999.0 * 1.25e-7) + (50.0 * 1e-5);
 		assert!((totals.cost_usd - (cost1 + cost2)).abs() < 1e-12);
+		assert_eq!(totals.cache_read_tokens, 200 + 9999);
+		assert_eq!(totals.cache_creation_tokens, 0);
+	}
+
+	#[test]
+	fn lines_are_parsed_like_a_session_file() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let lines = [
+			serde_json::json!({
+				"type": "turn_context",
+				"payload": { "info": { "model": "gpt-5" } }
+			}),
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": "2026-02-06T12:00:00Z",
+				"payload": {
+					"type": "token_count",
+					"info": { "total_token_usage": { "input_tokens": 100, "output_tokens": 10 } }
+				}
+			}),
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": "2026-02-06T12:05:00Z",
+				"payload": {
+					"type": "token_count",
+					"info": { "total_token_usage": { "input_tokens": 300, "output_tokens": 30 } }
+				}
+			}),
+		]
+		.map(|v| v.to_string());
+		let file_path = tmp.path().join("s1.jsonl");
+		std::fs::write(&file_path, lines.join("\n")).expect("write");
+
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let from_lines =
+			load_codex_breakdown_from_lines_with_pricing(lines, None, &dataset, &options);
+		assert_eq!(from_lines["gpt-5"].total_tokens, 330);
+		let from_file =
+			load_codex_totals_from_files_all_time_with_pricing(&[file_path], &dataset, &options);
+		assert_eq!(from_file.total_tokens, 330);
+	}
+
+	#[test]
+	fn concatenated_sessions_do_not_share_the_delta_baseline() {
+		let session = |model: &str, totals: [u64; 2]| {
+			let meta = serde_json::json!({ "type": "session_meta", "payload": { "id": model } });
+			let context = serde_json::json!({
+				"type": "turn_context",
+				"payload": { "info": { "model": model } }
+			});
+			let events = totals.map(|input| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": "2026-02-06T12:00:00Z",
+					"payload": {
+						"type": "token_count",
+						"info": { "total_token_usage": { "input_tokens": input } }
+					}
+				})
+			});
+			[meta, context, events[0].clone(), events[1].clone()].map(|v| v.to_string())
+		};
+		// 第二个会话的累计值从头开始：若沿用第一个会话的基线，差分会被算成 0。
+		let lines: Vec<String> =
+			session("gpt-5", [100, 300]).into_iter().chain(session("o3", [50, 80])).collect();
+
+		let dataset = HashMap::new();
+		let options = ScanOptions::default();
+		let breakdown =
+			load_codex_breakdown_from_lines_with_pricing(lines, None, &dataset, &options);
+		assert_eq!(breakdown["gpt-5"].total_tokens, 300);
+		assert_eq!(breakdown["o3"].total_tokens, 80);
+	}
+
+	#[test]
+	fn usage_without_a_model_is_flagged_as_estimated() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let event = |model: Option<&str>| {
+			let mut info = serde_json::json!({
+				"last_token_usage": { "input_tokens": 100, "output_tokens": 10 }
+			});
+			if let Some(model) = model {
+				info["model"] = serde_json::json!(model);
+			}
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": "2026-02-06T12:00:00Z",
+				"payload": { "type": "token_count", "info": info }
+			})
+			.to_string()
+		};
+		let unknown = tmp.path().join("unknown.jsonl");
+		std::fs::write(&unknown, event(None)).expect("write");
+		let explicit = tmp.path().join("explicit.jsonl");
+		std::fs::write(&explicit, event(Some("gpt-5.2"))).expect("write");
+
+		let options = ScanOptions::default();
+		let by_model = |path: &Path| {
+			let model_usage = scan_codex_file(path, None, &options);
+			breakdown_with_pricing(model_usage, &HashMap::new(), &options)
+		};
+		let assumed = by_model(&unknown);
+		assert!(assumed[LEGACY_FALLBACK_MODEL].is_estimated);
+		let named = by_model(&explicit);
+		assert!(!named["gpt-5.2"].is_estimated);
+
+		let mut totals = named["gpt-5.2"];
+		totals += assumed[LEGACY_FALLBACK_MODEL];
+		assert!(totals.is_estimated);
+	}
+
+	#[test]
+	fn cache_creation_tokens_are_counted_and_priced() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("s1.jsonl");
+		let day = test_util::local_noon_20260206();
+		let lines = [
+			serde_json::json!({
+				"type": "turn_context",
+				"payload": { "info": { "model": "gpt-5" } }
+			}),
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": day,
+				"payload": {
+					"type": "token_count",
+					"info": {
+						"last_token_usage": {
+							"input_tokens": 1000,
+							"cached_input_tokens": 200,
+							"cache_creation_input_tokens": 300,
+							"output_tokens": 100
+						}
+					}
+				}
+			}),
+		];
+		let content = lines.map(|v| v.to_string()).join("\n");
+		std::fs::write(&file_path, content).expect("write");
+
+		let range = test_util::day_range("20260206");
+		let mut dataset = HashMap::new();
+		dataset.insert(
+			"gpt-5".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(1e-6),
+				cache_read_input_token_cost: Some(1e-7),
+				cache_creation_input_token_cost: Some(1.25e-6),
+				output_cost_per_token: Some(1e-5),
+				..Default::default()
+			},
+		);
+
+		let options = ScanOptions::default();
+		let totals =
+			load_codex_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
+		assert_eq!(totals.total_tokens, 1000 + 300 + 100);
+		assert_eq!(totals.cache_creation_tokens, 300);
+		let expected = 800.0 * 1e-6 + 200.0 * 1e-7 + 300.0 * 1.25e-6 + 100.0 * 1e-5;
+		assert!((totals.cost_usd - expected).abs() < 1e-12);
 	}
 
 	#[test]
@@ -735,6 +1436,41 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 			assert_eq!(dirs[0], expected);
 		}
 
+		#[test]
+		fn extra_codex_dirs_are_appended_and_deduped() {
+			let _lock = crate::test_util::env_cwd_lock()
+				.lock()
+				.expect("env/cwd lock poisoned");
+			let _restore_cwd = RestoreCwd::new();
+			let _restore_home = RestoreEnvVar::new("CODEX_HOME");
+			let _restore_extra = RestoreEnvVar::new(EXTRA_CODEX_DIRS_ENV);
+
+			let tmp = tempfile::tempdir().expect("tempdir");
+			std::env::set_current_dir(tmp.path()).expect("set_current_dir");
+			for dir in ["work/sessions", "personal/sessions", "archive"] {
+				std::fs::create_dir_all(dir).expect("mkdir");
+			}
+			let cwd = std::env::current_dir().expect("current_dir");
+			std::env::set_var("CODEX_HOME", "work");
+			let work_again = cwd.join("work").to_string_lossy().to_string();
+			std::env::set_var(
+				EXTRA_CODEX_DIRS_ENV,
+				format!("personal, {work_again} ,archive,missing,,"),
+			);
+
+			assert_eq!(
+				default_codex_session_dirs(),
+				vec![
+					cwd.join("work").join("sessions"),
+					cwd.join("personal").join("sessions"),
+					cwd.join("archive"),
+				]
+			);
+
+			std::env::remove_var(EXTRA_CODEX_DIRS_ENV);
+			assert_eq!(default_codex_session_dirs(), vec![cwd.join("work").join("sessions")]);
+		}
+
 		#[test]
 		fn all_time_includes_token_count_events_without_timestamp() {
 			let tmp = tempfile::tempdir().expect("tempdir");
@@ -766,7 +1502,588 @@ pub fn load_codex_totals_from_session_dirs_all_time_with_pricing(
 			std::fs::write(&file_path, content).expect("write");
 
 			let dataset = HashMap::<String, LiteLLMModelPricing>::new();
-			let totals = load_codex_totals_from_files_all_time_with_pricing(&[file_path], &dataset);
+			let options = ScanOptions::default();
+			let totals = load_codex_totals_from_files_all_time_with_pricing(
+				&[file_path],
+				&dataset,
+				&options,
+			);
 			assert_eq!(totals.total_tokens, 3);
 		}
+
+		#[test]
+		fn billable_input_matches_inclusive_and_exclusive_conventions() {
+			let pricing = LiteLLMModelPricing {
+				input_cost_per_token: Some(1e-6),
+				cache_read_input_token_cost: Some(1e-7),
+				..Default::default()
+			};
+			let billable_cost = |raw: RawUsage, convention| {
+				let delta = convert_to_delta(raw, convention);
+				let tokens = CodexTokens {
+					input_tokens: delta.input_tokens,
+					cached_input_tokens: delta.cached_input_tokens,
+					output_tokens: delta.output_tokens,
+					cache_creation_input_tokens: delta.cache_creation_input_tokens,
+				};
+				calculate_codex_cost_from_pricing(tokens, &pricing)
+			};
+			// 同一次请求：800 未命中缓存 + 200 命中缓存。
+			let expected = 800.0 * 1e-6 + 200.0 * 1e-7;
+
+			let inclusive = RawUsage {
+				input_tokens: 1_000,
+				cached_input_tokens: 200,
+				..Default::default()
+			};
+			let exclusive = RawUsage {
+				input_tokens: 800,
+				cached_input_tokens: 200,
+				..Default::default()
+			};
+			assert!((billable_cost(inclusive, CodexInputConvention::Inclusive) - expected).abs() < 1e-12);
+			assert!((billable_cost(exclusive, CodexInputConvention::Exclusive) - expected).abs() < 1e-12);
+			assert!((billable_cost(inclusive, CodexInputConvention::Auto) - expected).abs() < 1e-12);
+
+			// Auto：cached > input 只可能是不含缓存的口径。
+			let exclusive_heavy_cache = RawUsage {
+				input_tokens: 100,
+				cached_input_tokens: 900,
+				..Default::default()
+			};
+			let delta = convert_to_delta(exclusive_heavy_cache, CodexInputConvention::Auto);
+			assert_eq!(delta.input_tokens, 1_000);
+			assert_eq!(delta.cached_input_tokens, 900);
+		}
+
+		#[test]
+		fn input_convention_comes_from_the_given_options() {
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("s.jsonl");
+			let usage = serde_json::json!({ "input_tokens": 800, "cached_input_tokens": 200 });
+			let event = serde_json::json!({
+				"type": "event_msg",
+				"payload": { "type": "token_count", "info": { "last_token_usage": usage } }
+			});
+			std::fs::write(&file_path, event.to_string()).expect("write");
+
+			let files = [file_path];
+			let input_with = |convention| {
+				let options = ScanOptions::new(
+					convention,
+					CodexAccounting::Deltas,
+					BusinessHours::default(),
+					false,
+				);
+				scan_codex_files(&files, None, &options)
+					.values()
+					.map(|usage| usage.tokens.input_tokens)
+					.sum::<u64>()
+			};
+			assert_eq!(input_with(CodexInputConvention::Inclusive), 800);
+			assert_eq!(input_with(CodexInputConvention::Exclusive), 1_000);
+		}
+
+		#[test]
+		fn final_snapshot_matches_session_total_when_deltas_are_incomplete() {
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("s1.jsonl");
+			let day = test_util::local_noon_20260206();
+			let event = |last: u64, total: u64| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": {
+							"last_token_usage": { "input_tokens": last, "output_tokens": 0, "total_tokens": last },
+							"total_token_usage": { "input_tokens": total, "output_tokens": 0, "total_tokens": total }
+						}
+					}
+				})
+			};
+			// 中间丢了一条 200 的事件：增量只能看到 100 + 50，而最终快照是 350。
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } }),
+				event(100, 100),
+				event(50, 350),
+			];
+			std::fs::write(
+				&file_path,
+				lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+			)
+			.expect("write");
+
+			let bounds = parse_yyyymmdd("20260206").map(|d| (d, d));
+			let files = vec![file_path];
+			let total_of = |accounting| {
+				let options = ScanOptions {
+					accounting,
+					..Default::default()
+				};
+				scan_codex_files(&files, bounds, &options)
+					.values()
+					.map(|usage| usage.total_tokens)
+					.sum::<u64>()
+			};
+
+			assert_eq!(total_of(CodexAccounting::Deltas), 150);
+			assert_eq!(total_of(CodexAccounting::FinalSnapshot), 350);
+		}
+
+		#[test]
+		fn tiered_pricing_uses_the_model_threshold_per_request() {
+			let event = |last: u64, total: u64| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": "2026-02-06T12:00:00Z",
+					"payload": {
+						"type": "token_count",
+						"info": {
+							"last_token_usage": { "input_tokens": last, "total_tokens": last },
+							"total_token_usage": { "input_tokens": total, "total_tokens": total }
+						}
+					}
+				})
+			};
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } }),
+				event(150_000, 150_000),
+				event(150_000, 300_000),
+			]
+			.map(|v| v.to_string());
+			let dataset = HashMap::from([(
+				"gpt-5".to_string(),
+				LiteLLMModelPricing {
+					input_cost_per_token: Some(1e-6),
+					input_cost_per_token_above_200k_tokens: Some(2e-6),
+					tier_threshold_tokens: Some(128_000),
+					..Default::default()
+				},
+			)]);
+			let cost_of = |accounting| {
+				let options = ScanOptions {
+					accounting,
+					..Default::default()
+				};
+				let breakdown = load_codex_breakdown_from_lines_with_pricing(
+					lines.clone(),
+					None,
+					&dataset,
+					&options,
+				);
+				breakdown["gpt-5"].cost_usd
+			};
+
+			// 每次请求各有 22k 超过 128k 的分界点（合计 300k 也不会按 200k 重算）。
+			let per_request = 2.0 * (128_000.0 * 1e-6 + 22_000.0 * 2e-6);
+			assert!((cost_of(CodexAccounting::Deltas) - per_request).abs() < 1e-9);
+			// 快照是整个会话的累计，不做分档，全部按基础单价计。
+			assert!((cost_of(CodexAccounting::FinalSnapshot) - 300_000.0 * 1e-6).abs() < 1e-9);
+		}
+
+		#[test]
+		fn interleaved_last_and_total_events_count_each_turn_once() {
+			let usage =
+				|tokens: u64| serde_json::json!({ "input_tokens": tokens, "total_tokens": tokens });
+			let event = |last: Option<u64>, total: Option<u64>| {
+				let mut info = serde_json::Map::new();
+				if let Some(last) = last {
+					info.insert("last_token_usage".to_string(), usage(last));
+				}
+				if let Some(total) = total {
+					info.insert("total_token_usage".to_string(), usage(total));
+				}
+				serde_json::json!({
+					"type": "event_msg",
+					"payload": { "type": "token_count", "info": info }
+				})
+				.to_string()
+			};
+			// 回合依次为 100、50、30、20：第二回合只有 `last`，第三回合只有 `total`。
+			let lines = [
+				event(Some(100), Some(100)),
+				event(Some(50), None),
+				event(None, Some(180)),
+				event(Some(20), Some(200)),
+			];
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("s.jsonl");
+			std::fs::write(&file_path, lines.join("\n")).expect("write");
+
+			let usage = scan_codex_files(&[file_path], None, &ScanOptions::default());
+			let total: u64 = usage.values().map(|usage| usage.total_tokens).sum();
+			assert_eq!(total, 200);
+		}
+
+		#[test]
+		fn business_hours_filter_excludes_after_hours_entries() {
+			use crate::time_parse::BusinessHoursMode;
+
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("s.jsonl");
+			// 2026-02-06 是周五：10:00 在工作时间内，21:30 在下班后。
+			let event = |time: &str, total: u64| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": format!("2026-02-06T{time}"),
+					"payload": {
+						"type": "token_count",
+						"info": { "last_token_usage": { "input_tokens": total, "output_tokens": 0, "total_tokens": total } }
+					}
+				})
+			};
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } }),
+				event("10:00:00", 100),
+				event("21:30:00", 40),
+			];
+			std::fs::write(
+				&file_path,
+				lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+			)
+			.expect("write");
+
+			let bounds = parse_yyyymmdd("20260206").map(|d| (d, d));
+			let files = vec![file_path];
+			let total_with = |mode| {
+				let options = ScanOptions {
+					business_hours: BusinessHours {
+						mode,
+						..Default::default()
+					},
+					..Default::default()
+				};
+				scan_codex_files(&files, bounds, &options)
+					.values()
+					.map(|usage| usage.total_tokens)
+					.sum::<u64>()
+			};
+
+			assert_eq!(total_with(BusinessHoursMode::Off), 140);
+			assert_eq!(total_with(BusinessHoursMode::Inside), 100);
+			assert_eq!(total_with(BusinessHoursMode::Outside), 40);
+		}
+
+		#[test]
+		fn counts_flattened_token_count_entries_once() {
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("flat.jsonl");
+			let day = test_util::local_noon_20260206();
+			let lines = [
+				// 平铺形态：没有 payload，info 在根上。
+				serde_json::json!({
+					"type": "token_count",
+					"timestamp": day,
+					"model": "gpt-5",
+					"info": {
+						"last_token_usage": { "input_tokens": 100, "output_tokens": 20, "total_tokens": 120 }
+					}
+				}),
+				// 嵌套形态：根上同时带了 info，也只按 payload 读一次。
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"info": {
+						"last_token_usage": { "input_tokens": 999, "output_tokens": 0, "total_tokens": 999 }
+					},
+					"payload": {
+						"type": "token_count",
+						"info": {
+							"last_token_usage": { "input_tokens": 10, "output_tokens": 5, "total_tokens": 15 }
+						}
+					}
+				}),
+			];
+			std::fs::write(
+				&file_path,
+				lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+			)
+			.expect("write");
+
+			let range = test_util::day_range("20260206");
+			let dataset = HashMap::<String, LiteLLMModelPricing>::new();
+			let options = ScanOptions::default();
+			let totals =
+				load_codex_totals_from_files_with_pricing(&[file_path], &range, &dataset, &options);
+			assert_eq!(totals.total_tokens, 120 + 15);
+		}
+
+		#[test]
+		fn breakdown_keys_by_model_and_sums_to_totals() {
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let day = test_util::local_noon_20260206();
+			let token_count = |input: u64, output: u64| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": {
+							"last_token_usage": {
+								"input_tokens": input,
+								"output_tokens": output,
+								"total_tokens": input + output
+							}
+						}
+					}
+				})
+			};
+
+			// 没有任何模型信息的旧日志归入 `gpt-5`。
+			let legacy = tmp.path().join("legacy.jsonl");
+			std::fs::write(&legacy, token_count(100, 10).to_string()).expect("write");
+
+			let mini = tmp.path().join("mini.jsonl");
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5-mini" } }),
+				token_count(40, 4),
+				token_count(60, 6),
+			];
+			std::fs::write(
+				&mini,
+				lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+			)
+			.expect("write");
+
+			let range = test_util::day_range("20260206");
+			let mut dataset = HashMap::new();
+			dataset.insert(
+				"gpt-5".to_string(),
+				LiteLLMModelPricing {
+					input_cost_per_token: Some(1e-6),
+					output_cost_per_token: Some(1e-5),
+					..Default::default()
+				},
+			);
+			let files = [legacy, mini];
+
+			let options = ScanOptions::default();
+			let breakdown =
+				load_codex_breakdown_from_files_with_pricing(&files, &range, &dataset, &options);
+			let mut models: Vec<&str> = breakdown.keys().map(String::as_str).collect();
+			models.sort();
+			assert_eq!(models, vec!["gpt-5", "gpt-5-mini"]);
+			assert_eq!(breakdown["gpt-5"].total_tokens, 110);
+			assert_eq!(breakdown["gpt-5-mini"].total_tokens, 110);
+			assert!((breakdown["gpt-5"].cost_usd - (100.0 * 1e-6 + 10.0 * 1e-5)).abs() < 1e-12);
+
+			let totals =
+				load_codex_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+			assert_eq!(totals.total_tokens, 220);
+			let breakdown_cost: f64 = breakdown.values().map(|t| t.cost_usd).sum();
+			assert!((totals.cost_usd - breakdown_cost).abs() < 1e-12);
+		}
+
+		#[test]
+		fn model_history_tracks_last_used_date_per_model() {
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let at = |day: u32| {
+				Local
+					.with_ymd_and_hms(2026, 1, day, 12, 0, 0)
+					.single()
+					.expect("local dt")
+					.to_rfc3339()
+			};
+			let token_count = |timestamp: String, model: &str, tokens: u64| {
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": timestamp,
+					"payload": {
+						"type": "token_count",
+						"model": model,
+						"info": {
+							"last_token_usage": { "input_tokens": tokens, "total_tokens": tokens }
+						}
+					}
+				})
+			};
+
+			let old = tmp.path().join("old.jsonl");
+			let lines = [
+				token_count(at(3), "gpt-5", 100),
+				token_count(at(5), "gpt-5-mini", 10),
+			];
+			std::fs::write(&old, lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"))
+				.expect("write");
+			let recent = tmp.path().join("recent.jsonl");
+			std::fs::write(&recent, token_count(at(20), "gpt-5-mini", 30).to_string())
+				.expect("write");
+
+			let options = ScanOptions::default();
+			let history = load_codex_model_history_from_files(&[old, recent], &options);
+			assert_eq!(history.len(), 2);
+			assert_eq!(history["gpt-5"].total_tokens, 100);
+			assert_eq!(history["gpt-5"].last_used, NaiveDate::from_ymd_opt(2026, 1, 3));
+			assert_eq!(history["gpt-5-mini"].total_tokens, 40);
+			assert_eq!(history["gpt-5-mini"].last_used, NaiveDate::from_ymd_opt(2026, 1, 20));
+
+			let sorted = crate::usage::sorted_model_history(history);
+			let order: Vec<&str> = sorted.iter().map(|(model, _)| model.as_str()).collect();
+			assert_eq!(order, vec!["gpt-5-mini", "gpt-5"]);
+		}
+
+		#[test]
+		fn gzipped_sessions_are_found_and_counted_like_plain_ones() {
+			use std::io::Write;
+
+			let day = test_util::local_noon_20260206();
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } }),
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": { "total_token_usage": { "input_tokens": 100, "total_tokens": 100 } }
+					}
+				}),
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": { "total_token_usage": { "input_tokens": 130, "total_tokens": 130 } }
+					}
+				}),
+			];
+			let content = lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n");
+
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let plain_dir = tmp.path().join("plain");
+			let archived_dir = tmp.path().join("archived").join("2026").join("02");
+			std::fs::create_dir_all(&plain_dir).expect("mkdir");
+			std::fs::create_dir_all(&archived_dir).expect("mkdir");
+			std::fs::write(plain_dir.join("s.jsonl"), &content).expect("write");
+			let mut encoder = flate2::write::GzEncoder::new(
+				std::fs::File::create(archived_dir.join("s.jsonl.gz")).expect("create"),
+				flate2::Compression::default(),
+			);
+			encoder.write_all(content.as_bytes()).expect("gzip");
+			encoder.finish().expect("gzip finish");
+
+			let range = test_util::day_range("20260206");
+			let dataset = HashMap::new();
+			let archived_root = tmp.path().join("archived");
+			let files = session_files_from_dirs(std::slice::from_ref(&archived_root));
+			assert_eq!(files, vec![archived_dir.join("s.jsonl.gz")]);
+
+			let options = ScanOptions::default();
+			let plain = load_codex_totals_from_session_dirs_with_pricing(
+				&[plain_dir],
+				&range,
+				&dataset,
+				&options,
+			);
+			let archived = load_codex_totals_from_session_dirs_with_pricing(
+				&[archived_root],
+				&range,
+				&dataset,
+				&options,
+			);
+			assert_eq!(plain.total_tokens, 130);
+			assert_eq!(archived.total_tokens, plain.total_tokens);
+		}
+
+		#[test]
+		fn diagnostics_count_unparseable_and_out_of_range_lines() {
+			let at = |day| {
+				Local
+					.with_ymd_and_hms(2026, 2, day, 12, 0, 0)
+					.single()
+					.expect("local dt")
+					.to_rfc3339()
+			};
+			let event = |day, total: u64| {
+				let usage = serde_json::json!({ "input_tokens": total, "total_tokens": total });
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": at(day),
+					"payload": { "type": "token_count", "info": { "total_token_usage": usage } }
+				})
+				.to_string()
+			};
+			let lines = [
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } })
+					.to_string(),
+				event(1, 100),
+				event(6, 130),
+				r#"{"type":"event_msg","payload":{"type":"token_count""#.to_string(),
+				r#"{"type":"event_msg","payload":{"type":"agent_message"}}"#.to_string(),
+			];
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let file_path = tmp.path().join("s.jsonl");
+			std::fs::write(&file_path, lines.join("\n")).expect("write");
+
+			let range = test_util::day_range("20260206");
+			let files = [file_path];
+			let dataset = HashMap::new();
+			let options = ScanOptions::default();
+			let (totals, diagnostics) =
+				load_codex_totals_with_diagnostics_from_files(&files, &range, &dataset, &options);
+			let plain =
+				load_codex_totals_from_files_with_pricing(&files, &range, &dataset, &options);
+			assert_eq!(totals.total_tokens, plain.total_tokens);
+			assert_eq!(
+				diagnostics,
+				ScanDiagnostics {
+					files: 1,
+					lines_total: 5,
+					lines_skipped_parse: 1,
+					lines_skipped_range: 1,
+					entries_deduped: 0,
+				}
+			);
+
+			// 只看最终快照时，区间外的快照行同样记为超出区间。
+			let snapshot = ScanOptions {
+				accounting: CodexAccounting::FinalSnapshot,
+				..Default::default()
+			};
+			let (_, diagnostics) =
+				load_codex_totals_with_diagnostics_from_files(&files, &range, &dataset, &snapshot);
+			assert_eq!(diagnostics.lines_skipped_range, 1);
+		}
+
+		#[test]
+		fn incremental_scan_matches_full_scan_after_appends_and_rewrites() {
+			use std::io::Write;
+
+			let event = |total: u64| {
+				let usage = serde_json::json!({ "input_tokens": total, "total_tokens": total });
+				serde_json::json!({
+					"type": "event_msg",
+					"payload": { "type": "token_count", "info": { "total_token_usage": usage } }
+				})
+				.to_string()
+			};
+			let tmp = tempfile::tempdir().expect("tempdir");
+			let cache_path = tmp.path().join("cache").join("codex.json");
+			let file_path = tmp.path().join("s.jsonl");
+			let files = vec![file_path.clone()];
+			let options = ScanOptions::default();
+			let total = |usage: HashMap<String, ModelUsage>| {
+				usage.values().map(|usage| usage.total_tokens).sum::<u64>()
+			};
+			let scan = || {
+				total(scan_codex_files_incremental(&files, &options, Some(&cache_path)))
+			};
+
+			// 最后一行还没写完（没有换行）：本次计入，但下次要从它的开头重新读。
+			std::fs::write(&file_path, format!("{}\n{}", event(100), event(150))).expect("write");
+			assert_eq!(scan(), 150);
+			let cache: CodexOffsetCache = offset_cache::load_cache(Some(&cache_path));
+			assert_eq!(cache.files[&file_path].checkpoint.offset, event(100).len() as u64 + 1);
+
+			// 追加：差分基线来自缓存里的解析器状态，结果与全量解析一致。
+			let mut file =
+				std::fs::OpenOptions::new().append(true).open(&file_path).expect("open");
+			file.write_all(format!("\n{}\n", event(180)).as_bytes()).expect("append");
+			assert_eq!(scan(), 180);
+			assert_eq!(total(scan_codex_files(&files, None, &options)), 180);
+
+			// 改写成更短的内容：回退为从头解析。
+			std::fs::write(&file_path, format!("{}\n", event(40))).expect("rewrite");
+			assert_eq!(scan(), 40);
+		}
 	}
@@ -0,0 +1,79 @@
+//! `~/.tokbar/` 下配置文件的目录定位与准备。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// tokbar 的数据目录：通常是 `~/.tokbar`；`HOME` 未设置或为空（部分 launchd/Windows 环境）时
+/// 退回系统的应用数据目录（如 `%APPDATA%\tokbar`），都拿不到时返回 None。
+pub(crate) fn tokbar_data_dir() -> Option<PathBuf> {
+	data_dir_from(std::env::var("HOME").ok().as_deref(), dirs::data_dir())
+}
+
+fn data_dir_from(home: Option<&str>, fallback: Option<PathBuf>) -> Option<PathBuf> {
+	match home {
+		Some(home) if !home.trim().is_empty() => Some(PathBuf::from(home).join(".tokbar")),
+		_ => fallback.map(|dir| dir.join("tokbar")),
+	}
+}
+
+/// 确保 `path` 的父目录存在（不存在则递归创建）。
+///
+/// 说明：如果路径上的某一级（通常是 `~/.tokbar`）已经存在但是普通文件，
+/// `create_dir_all` 只会给出含糊的 “File exists / Not a directory”；这里先检查并给出明确原因。
+pub(crate) fn ensure_parent_dir(path: &Path) -> Result<(), String> {
+	let Some(parent) = path.parent() else {
+		return Err("invalid config path".to_string());
+	};
+
+	for dir in parent.ancestors() {
+		if dir.as_os_str().is_empty() {
+			continue;
+		}
+		match fs::metadata(dir) {
+			Ok(meta) if meta.is_dir() => break,
+			Ok(_) => return Err(format!("{} exists but is not a directory", dir.display())),
+			Err(_) => continue,
+		}
+	}
+
+	fs::create_dir_all(parent).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn data_dir_prefers_home_and_falls_back_when_it_is_empty() {
+		let fallback = || Some(PathBuf::from("/data"));
+		assert_eq!(
+			data_dir_from(Some("/home/me"), fallback()),
+			Some(PathBuf::from("/home/me/.tokbar"))
+		);
+		assert_eq!(data_dir_from(Some("  "), fallback()), Some(PathBuf::from("/data/tokbar")));
+		assert_eq!(data_dir_from(None, fallback()), Some(PathBuf::from("/data/tokbar")));
+		assert_eq!(data_dir_from(None, None), None);
+	}
+
+	#[test]
+	fn reports_tokbar_file_instead_of_directory() {
+		let home = tempfile::tempdir().expect("tempdir");
+		let tokbar = home.path().join(".tokbar");
+		fs::write(&tokbar, "oops").expect("write");
+
+		let err = ensure_parent_dir(&tokbar.join("settings.json")).expect_err("should fail");
+		assert_eq!(err, format!("{} exists but is not a directory", tokbar.display()));
+
+		let nested = tokbar.join("litellm").join("prices.json");
+		let err = ensure_parent_dir(&nested).expect_err("should fail");
+		assert!(err.ends_with(".tokbar exists but is not a directory"));
+	}
+
+	#[test]
+	fn creates_missing_parent_dirs() {
+		let home = tempfile::tempdir().expect("tempdir");
+		let path = home.path().join(".tokbar").join("litellm").join("prices.json");
+		ensure_parent_dir(&path).expect("create");
+		assert!(path.parent().expect("parent").is_dir());
+	}
+}
@@ -1,31 +1,262 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::raw_format::{format_u64_with_commas, CostFormat, SourceLabels};
 use crate::usage::UsageTotals;
 
-pub fn format_cost_usd(cost: f64) -> String {
-	format!("${:.2}", cost)
+/// 预算告警的提示样式：emoji（🟡/🔴）或纯文本前缀（`[!]`/`[!!]`，适合不渲染 emoji 的环境）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BudgetIndicatorStyle {
+	#[default]
+	Emoji,
+	Text,
+}
+
+/// 推理 token 在标题里的展示方式。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasoningDisplay {
+	/// 推理 token 计入总量，不单独展示。
+	#[default]
+	Folded,
+	/// 总量只展示非推理部分，并追加 `(+400k reasoning)`。
+	Annotated,
+	/// 总量不变（仍包含推理 token），只在末尾追加 `(rsn 400k)`。
+	Suffix,
+}
+
+/// 状态栏标题里 token 数的写法：`1.2m` 这样的缩写，或 `1,234,567` 这样的完整数字。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStyle {
+	#[default]
+	Compact,
+	Full,
+}
+
+/// 状态栏标题展示哪些数字：只看 token、只看金额，或两者都看（默认，`1.2m($1.23)`）。
+///
+/// 说明：选了 `cost` 但价格表不可用时退回显示 token，避免标题里出现空白的金额。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayMetric {
+	Tokens,
+	Cost,
+	#[default]
+	Both,
+}
+
+/// 状态栏标题的写法：金额格式、token 写法与展示的数字（调用方按设置构造一次后传入；菜单不受影响）。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayStyle {
+	pub cost: CostFormat,
+	pub tokens: TokenStyle,
+	pub metric: TrayMetric,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLevel {
+	Normal,
+	Warning,
+	Critical,
+}
+
+/// 预算告警阈值（百分比，100 表示刚好用满预算）。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetThresholds {
+	pub warning_percent: f64,
+	pub critical_percent: f64,
+	pub style: BudgetIndicatorStyle,
+}
+
+impl Default for BudgetThresholds {
+	fn default() -> Self {
+		Self {
+			warning_percent: 80.0,
+			critical_percent: 100.0,
+			style: BudgetIndicatorStyle::Emoji,
+		}
+	}
+}
+
+/// 按阈值判定预算使用等级；先判 critical，保证两个阈值被配置成相同值时取更严重的等级。
+pub fn budget_level(used_percent: f64, thresholds: &BudgetThresholds) -> BudgetLevel {
+	if used_percent >= thresholds.critical_percent {
+		return BudgetLevel::Critical;
+	}
+	if used_percent >= thresholds.warning_percent {
+		return BudgetLevel::Warning;
+	}
+	BudgetLevel::Normal
+}
+
+/// 返回预算等级对应的提示前缀；Normal 返回空串，避免在正常情况下给标题增加噪音。
+pub fn budget_indicator(used_percent: f64, thresholds: &BudgetThresholds) -> &'static str {
+	match (budget_level(used_percent, thresholds), thresholds.style) {
+		(BudgetLevel::Normal, _) => "",
+		(BudgetLevel::Warning, BudgetIndicatorStyle::Emoji) => "🟡",
+		(BudgetLevel::Critical, BudgetIndicatorStyle::Emoji) => "🔴",
+		(BudgetLevel::Warning, BudgetIndicatorStyle::Text) => "[!]",
+		(BudgetLevel::Critical, BudgetIndicatorStyle::Text) => "[!!]",
+	}
+}
+
+/// 按预算等级给 rc 额度片段加上提示前缀；用量未知或处于正常等级时原样返回。
+pub fn with_budget_indicator(
+	title_part: String,
+	used_percent: Option<f64>,
+	thresholds: &BudgetThresholds,
+) -> String {
+	match used_percent.map(|p| budget_indicator(p, thresholds)) {
+		Some(indicator) if !indicator.is_empty() => format!("{indicator} {title_part}"),
+		_ => title_part,
+	}
+}
+
+/// 每日花费提醒阈值：非正数/非有限值视为未设置。
+pub fn daily_cost_alert_threshold(raw: Option<f64>) -> Option<f64> {
+	raw.filter(|v| v.is_finite() && *v > 0.0)
+}
+
+/// 今天的花费首次达到阈值时才提醒；同一天只提醒一次（`fired_on` 为上次提醒的本地日期）。
+pub fn daily_cost_alert_due(
+	threshold: f64,
+	today_cost: f64,
+	today: NaiveDate,
+	fired_on: Option<NaiveDate>,
+) -> bool {
+	fired_on != Some(today) && today_cost >= threshold
+}
+
+/// 金额的展示币种：价格表里的成本都是 USD，按用户填写的固定汇率换算（离线环境拿不到实时汇率）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayCurrency {
+	/// ISO 4217 代码（如 `EUR`），目前只用于辨识。
+	pub code: String,
+	/// 金额前面的符号（如 `€`）；为空时用代码加空格代替。
+	pub symbol: String,
+	/// 1 USD 折合多少该币种。
+	pub usd_rate: f64,
+}
+
+impl Default for DisplayCurrency {
+	fn default() -> Self {
+		Self {
+			code: "USD".to_string(),
+			symbol: "$".to_string(),
+			usd_rate: 1.0,
+		}
+	}
+}
+
+impl DisplayCurrency {
+	/// 金额前缀：优先用符号，没有符号时用 `EUR ` 这样的代码形式。
+	pub fn prefix(&self) -> String {
+		let symbol = self.symbol.trim();
+		if !symbol.is_empty() {
+			return symbol.to_string();
+		}
+		match self.code.trim() {
+			"" => "$".to_string(),
+			code => format!("{code} "),
+		}
+	}
+
+	/// 把 USD 金额换算成该币种；汇率无效（非正数/NaN）时按 1:1 处理。
+	pub fn convert(&self, cost_usd: f64) -> f64 {
+		if self.usd_rate.is_finite() && self.usd_rate > 0.0 {
+			cost_usd * self.usd_rate
+		} else {
+			cost_usd
+		}
+	}
+}
+
+/// 金额默认保留的小数位数。
+pub const DEFAULT_COST_DECIMALS: usize = 2;
+/// 小数位数上限：再多就只是浮点噪音了。
+pub(crate) const MAX_COST_DECIMALS: usize = 8;
+
+/// 保留 `decimals` 位小数时，非 0 金额会被显示成 0 的上界（即最小单位的一半）。
+pub(crate) fn below_display_precision(cost: f64, decimals: usize) -> bool {
+	cost > 0.0 && cost < 0.5 / 10f64.powi(decimals as i32)
+}
+
+/// 状态栏用的金额：保留 `decimals` 位小数；非 0 但小于能显示的最小单位时显示 `<$0.01`
+/// （4 位小数时为 `<$0.0001`），避免看起来像“免费”。
+pub fn format_cost(cost_usd: f64, currency: &DisplayCurrency, decimals: usize) -> String {
+	let prefix = currency.prefix();
+	let cost = currency.convert(cost_usd);
+	if below_display_precision(cost, decimals) {
+		let smallest = 1.0 / 10f64.powi(decimals as i32);
+		return format!("<{prefix}{smallest:.decimals$}");
+	}
+	format!("{prefix}{cost:.decimals$}")
+}
+
+/// `tokens / unit` 四舍五入到整数。
+fn rounded_units(tokens: u64, unit: u64) -> u64 {
+	tokens.saturating_add(unit / 2) / unit
+}
+
+/// `tokens / unit` 四舍五入到 0.1，返回以 0.1 为单位的整数（`12_345 / 1000` → `123`）。
+fn rounded_tenths(tokens: u64, unit: u64) -> u64 {
+	rounded_units(tokens, unit / 10)
+}
+
+/// 缩写 token 数（`12.3k` / `123k` / `1.2m` / `1.0b`）。
+///
+/// 说明：先按要显示的精度四舍五入再判断单位，舍入后进位的值（如 999,950 → `1000k`）
+/// 会直接用下一个单位显示（`1.0m`），避免同一个数出现两种写法。
 pub fn format_tokens_compact(tokens: u64) -> String {
-	const K: f64 = 1000.0;
-	const M: f64 = 1_000_000.0;
-	const B: f64 = 1_000_000_000.0;
+	const K: u64 = 1_000;
+	const M: u64 = 1_000_000;
+	const B: u64 = 1_000_000_000;
 
-	let value = tokens as f64;
-	if value < K {
+	if tokens < K {
 		return tokens.to_string();
 	}
-	if value < 100_000.0 {
-		return format!("{:.1}k", value / K);
+	let tenths = |unit: u64, suffix: &str| {
+		let value = rounded_tenths(tokens, unit);
+		format!("{}.{}{suffix}", value / 10, value % 10)
+	};
+	if rounded_tenths(tokens, K) < 1_000 {
+		return tenths(K, "k");
+	}
+	if rounded_units(tokens, K) < 1_000 {
+		return format!("{}k", rounded_units(tokens, K));
+	}
+	if rounded_tenths(tokens, M) < 1_000 {
+		return tenths(M, "m");
 	}
-	if value < M {
-		return format!("{:.0}k", value / K);
+	if rounded_units(tokens, M) < 1_000 {
+		return format!("{}m", rounded_units(tokens, M));
 	}
-	if value < 100_000_000.0 {
-		return format!("{:.1}m", value / M);
+	tenths(B, "b")
+}
+
+pub fn format_tokens(tokens: u64, style: TokenStyle) -> String {
+	match style {
+		TokenStyle::Compact => format_tokens_compact(tokens),
+		TokenStyle::Full => format_u64_with_commas(tokens),
+	}
+}
+
+/// 标题里某个来源的数字部分：`1.2m($1.23)` / `1.2m` / `$1.23`；不展示金额时总是 token。
+fn tray_amount(totals: UsageTotals, show_cost: bool, style: &TrayStyle) -> String {
+	let tokens = format_tokens(totals.total_tokens, style.tokens);
+	if !show_cost {
+		return tokens;
 	}
-	if value < B {
-		return format!("{:.0}m", value / M);
+	match style.metric {
+		TrayMetric::Tokens => tokens,
+		TrayMetric::Cost => style.cost.format(totals.cost_usd),
+		TrayMetric::Both => format!("{tokens}({})", style.cost.format(totals.cost_usd)),
 	}
-	format!("{:.1}b", value / B)
 }
 
 pub fn format_single_title(
@@ -33,48 +264,153 @@ pub fn format_single_title(
 	source_abbr: &str,
 	totals: UsageTotals,
 	show_cost: bool,
+	style: &TrayStyle,
+) -> String {
+	format!("{period} {source_abbr} {}", tray_amount(totals, show_cost, style))
+}
+
+/// 与 `format_single_title` 相同，但可以把推理 token 单独标注出来（没有推理 token 时不追加）。
+pub fn format_single_title_with_reasoning(
+	period: &str,
+	source_abbr: &str,
+	totals: UsageTotals,
+	show_cost: bool,
+	reasoning: ReasoningDisplay,
+	style: &TrayStyle,
 ) -> String {
-	if show_cost {
+	// 只显示金额时标题里没有 token 数，推理 token 也就无从标注。
+	let cost_only = show_cost && style.metric == TrayMetric::Cost;
+	if reasoning == ReasoningDisplay::Folded || totals.reasoning_tokens == 0 || cost_only {
+		return format_single_title(period, source_abbr, totals, show_cost, style);
+	}
+	if reasoning == ReasoningDisplay::Suffix {
 		return format!(
-			"{period} {source_abbr} {tokens}({cost})",
-			tokens = format_tokens_compact(totals.total_tokens),
-			cost = format_cost_usd(totals.cost_usd)
+			"{} (rsn {})",
+			format_single_title(period, source_abbr, totals, show_cost, style),
+			format_tokens(totals.reasoning_tokens, style.tokens)
 		);
 	}
 
+	let non_reasoning = UsageTotals {
+		total_tokens: totals.total_tokens.saturating_sub(totals.reasoning_tokens),
+		..totals
+	};
 	format!(
-		"{period} {source_abbr} {tokens}",
-		tokens = format_tokens_compact(totals.total_tokens),
+		"{} (+{} reasoning)",
+		format_single_title(period, source_abbr, non_reasoning, show_cost, style),
+		format_tokens(totals.reasoning_tokens, style.tokens)
 	)
 }
 
 pub fn format_both_title_one_line(
 	period: &str,
+	labels: SourceLabels<'_>,
 	cx: UsageTotals,
 	cc: UsageTotals,
 	show_cost: bool,
+	style: &TrayStyle,
 ) -> String {
-	if show_cost {
-		return format!(
-			"{period} | cx {cx_tokens}({cx_cost}) | cc {cc_tokens}({cc_cost})",
-			cx_tokens = format_tokens_compact(cx.total_tokens),
-			cx_cost = format_cost_usd(cx.cost_usd),
-			cc_tokens = format_tokens_compact(cc.total_tokens),
-			cc_cost = format_cost_usd(cc.cost_usd),
-		);
+	let segment = |label: &str, totals: UsageTotals| {
+		format!("{label} {}", tray_amount(totals, show_cost, style))
+	};
+	let (first, second) = labels.ordered(segment(labels.cx, cx), segment(labels.cc, cc));
+	format!("{period} | {first} | {second}")
+}
+
+/// 所有数据来源都不可用时的状态栏标题（新装、还没用过 Codex/Claude Code 时最常见）。
+pub const EMPTY_STATE_TITLE: &str = "tokbar：无数据（点击设置）";
+
+/// 空状态下菜单首行的引导文案。
+pub const EMPTY_STATE_MENU_HINT: &str =
+	"未检测到 Codex / Claude Code 日志；使用后会自动出现统计，价格与 Right.codes 可在下方设置";
+
+/// 各数据来源当前是否可用，用于判断是否进入“完全没有数据”的空状态。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceAvailability {
+	pub codex_logs: bool,
+	pub claude_logs: bool,
+	pub pricing: bool,
+	pub rightcodes: bool,
+}
+
+impl SourceAvailability {
+	pub fn is_empty(&self) -> bool {
+		!(self.codex_logs || self.claude_logs || self.pricing || self.rightcodes)
 	}
+}
 
-	format!(
-		"{period} | cx {cx_tokens} | cc {cc_tokens}",
-		cx_tokens = format_tokens_compact(cx.total_tokens),
-		cc_tokens = format_tokens_compact(cc.total_tokens),
-	)
+/// 全部来源都不可用时用空状态标题替换 `title`（否则 `Today cx 0` 这样的标题只会让人困惑）。
+pub fn title_or_empty_state(title: String, sources: SourceAvailability) -> String {
+	if sources.is_empty() {
+		return EMPTY_STATE_TITLE.to_string();
+	}
+	title
+}
+
+/// 有目录扫描失败时在菜单统计行后面注明，避免把“扫描出错”误读成“没有用量”。
+pub fn append_scan_failure(line: String, failed_dirs: &[PathBuf]) -> String {
+	match failed_dirs {
+		[] => line,
+		[dir] => format!("{line}（扫描目录失败：{}）", dir.display()),
+		[dir, ..] => {
+			format!("{line}（扫描目录失败：{} 等 {} 个）", dir.display(), failed_dirs.len())
+		}
+	}
+}
+
+/// 把“多久以前”写成菜单里的短文案：刚刚 / N 分钟前 / N 小时前 / N 天前。
+///
+/// 说明：`then` 晚于 `now`（系统时间被调过）时按“刚刚”处理。
+pub fn format_time_ago(then: DateTime<Local>, now: DateTime<Local>) -> String {
+	let minutes = (now - then).num_minutes();
+	match minutes {
+		..1 => "刚刚".to_string(),
+		1..60 => format!("{minutes} 分钟前"),
+		60..1440 => format!("{} 小时前", minutes / 60),
+		_ => format!("{} 天前", minutes / 1440),
+	}
+}
+
+/// 把 rc 额度片段拼到托盘标题后面；`rc_in_title = false` 时只返回基础标题。
+///
+/// 说明：这里只影响状态栏标题，菜单里的 rc 状态文案由调用方照常更新。
+pub fn append_rc_title_part(base_title: String, rc_title_part: Option<&str>, rc_in_title: bool) -> String {
+	match rc_title_part {
+		Some(rc) if rc_in_title => format!("{base_title} {rc}"),
+		_ => base_title,
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	#[test]
+	fn time_ago_uses_the_largest_whole_unit() {
+		let now = Local::now();
+		let ago = |minutes: i64| format_time_ago(now - chrono::Duration::minutes(minutes), now);
+		assert_eq!(ago(0), "刚刚");
+		assert_eq!(ago(-5), "刚刚");
+		assert_eq!(ago(59), "59 分钟前");
+		assert_eq!(ago(3 * 60 + 20), "3 小时前");
+		assert_eq!(ago(2 * 1440 + 1), "2 天前");
+	}
+
+	#[test]
+	fn scan_failures_are_appended_to_the_menu_line() {
+		let line = "Today cx 1.0k".to_string();
+		assert_eq!(append_scan_failure(line.clone(), &[]), line);
+		let dirs = [PathBuf::from("/a/locked"), PathBuf::from("/b")];
+		assert_eq!(
+			append_scan_failure(line.clone(), &dirs[..1]),
+			"Today cx 1.0k（扫描目录失败：/a/locked）"
+		);
+		assert_eq!(
+			append_scan_failure(line, &dirs),
+			"Today cx 1.0k（扫描目录失败：/a/locked 等 2 个）"
+		);
+	}
+
 	#[test]
 	fn tokens_compact_formats_expected() {
 		assert_eq!(format_tokens_compact(0), "0");
@@ -85,22 +421,284 @@ mod tests {
 		assert_eq!(format_tokens_compact(1_234_000), "1.2m");
 	}
 
+	#[test]
+	fn tokens_compact_rolls_over_to_the_next_unit_when_rounding_up() {
+		assert_eq!(format_tokens_compact(99_949), "99.9k");
+		assert_eq!(format_tokens_compact(99_950), "100k");
+		assert_eq!(format_tokens_compact(999_499), "999k");
+		assert_eq!(format_tokens_compact(999_950), "1.0m");
+		assert_eq!(format_tokens_compact(99_950_000), "100m");
+		assert_eq!(format_tokens_compact(999_999_950), "1.0b");
+		assert_eq!(format_tokens_compact(12_345_678_901), "12.3b");
+		assert_eq!(format_tokens_compact(u64::MAX), "18446744073.7b");
+	}
+
+	#[test]
+	fn token_style_picks_compact_or_full_digits() {
+		assert_eq!(format_tokens(1_234_567, TokenStyle::Compact), "1.2m");
+		assert_eq!(format_tokens(1_234_567, TokenStyle::Full), "1,234,567");
+		assert_eq!(format_tokens(999, TokenStyle::Full), "999");
+		assert_eq!(TokenStyle::default(), TokenStyle::Compact);
+		let parsed: TokenStyle = serde_json::from_str("\"full\"").expect("parse style");
+		assert_eq!(parsed, TokenStyle::Full);
+	}
+
+	#[test]
+	fn tray_metric_picks_tokens_cost_or_both() {
+		let totals = UsageTotals {
+			total_tokens: 1_234_567,
+			cost_usd: 1.234,
+			..Default::default()
+		};
+		let style = |metric| TrayStyle {
+			metric,
+			..TrayStyle::default()
+		};
+		assert_eq!(tray_amount(totals, true, &style(TrayMetric::Both)), "1.2m($1.23)");
+		assert_eq!(tray_amount(totals, true, &style(TrayMetric::Tokens)), "1.2m");
+		assert_eq!(tray_amount(totals, true, &style(TrayMetric::Cost)), "$1.23");
+		// 价格表不可用时，只看金额也退回显示 token。
+		assert_eq!(tray_amount(totals, false, &style(TrayMetric::Cost)), "1.2m");
+		let full = TrayStyle {
+			tokens: TokenStyle::Full,
+			..TrayStyle::default()
+		};
+		assert_eq!(tray_amount(totals, true, &full), "1,234,567($1.23)");
+		assert_eq!(TrayMetric::default(), TrayMetric::Both);
+		let parsed: TrayMetric = serde_json::from_str("\"cost\"").expect("parse metric");
+		assert_eq!(parsed, TrayMetric::Cost);
+	}
+
 	#[test]
 	fn both_title_one_line_has_separators() {
+		let style = TrayStyle::default();
 		let title = format_both_title_one_line(
 			"Today",
+			SourceLabels::default(),
 			UsageTotals {
 				total_tokens: 12_300,
 				cost_usd: 0.45,
+				..Default::default()
 			},
 			UsageTotals {
 				total_tokens: 8_100,
 				cost_usd: 0.30,
+				..Default::default()
 			},
 			true,
+			&style,
 		);
 		assert!(title.contains("Today | cx"));
 		assert!(title.contains(" | cc "));
 		assert!(!title.contains('\n'));
 	}
+
+	#[test]
+	fn single_title_annotates_reasoning_tokens() {
+		let totals = UsageTotals {
+			total_tokens: 1_600_000,
+			cost_usd: 1.5,
+			reasoning_tokens: 400_000,
+			..Default::default()
+		};
+		let style = TrayStyle::default();
+		let title = |show_cost, display| {
+			format_single_title_with_reasoning("Today", "cx", totals, show_cost, display, &style)
+		};
+		assert_eq!(
+			title(false, ReasoningDisplay::Annotated),
+			"Today cx 1.2m (+400k reasoning)"
+		);
+		assert_eq!(
+			title(true, ReasoningDisplay::Annotated),
+			"Today cx 1.2m($1.50) (+400k reasoning)"
+		);
+		assert_eq!(title(false, ReasoningDisplay::Folded), "Today cx 1.6m");
+		assert_eq!(title(true, ReasoningDisplay::Suffix), "Today cx 1.6m($1.50) (rsn 400k)");
+
+		let no_reasoning = UsageTotals {
+			reasoning_tokens: 0,
+			..totals
+		};
+		assert_eq!(
+			format_single_title_with_reasoning(
+				"Today",
+				"cx",
+				no_reasoning,
+				false,
+				ReasoningDisplay::Suffix,
+				&style,
+			),
+			"Today cx 1.6m"
+		);
+	}
+
+	#[test]
+	fn titles_use_custom_source_labels() {
+		let labels = SourceLabels {
+			cx: "Codex",
+			cc: "Claude",
+			..Default::default()
+		};
+		let cx = UsageTotals {
+			total_tokens: 12_300,
+			..Default::default()
+		};
+		let cc = UsageTotals {
+			total_tokens: 8_100,
+			..Default::default()
+		};
+		let style = TrayStyle::default();
+		assert_eq!(format_single_title("Today", labels.cx, cx, false, &style), "Today Codex 12.3k");
+		assert_eq!(
+			format_both_title_one_line("Today", labels, cx, cc, false, &style),
+			"Today | Codex 12.3k | Claude 8.1k"
+		);
+		assert_eq!(
+			crate::raw_format::format_both_title_raw("Today", labels, cx, cc, false, &style.cost),
+			"Today |\tCodex 12,300\n\tClaude 8,100"
+		);
+	}
+
+	#[test]
+	fn cc_first_swaps_both_title_segments() {
+		let labels = SourceLabels {
+			cc_first: true,
+			..Default::default()
+		};
+		let cx = UsageTotals {
+			total_tokens: 12_300,
+			cost_usd: 0.45,
+			..Default::default()
+		};
+		let cc = UsageTotals {
+			total_tokens: 8_100,
+			cost_usd: 0.30,
+			..Default::default()
+		};
+		let style = TrayStyle::default();
+		assert_eq!(
+			format_both_title_one_line("Today", labels, cx, cc, true, &style),
+			"Today | cc 8.1k($0.30) | cx 12.3k($0.45)"
+		);
+		assert_eq!(
+			crate::raw_format::format_both_title_raw("Today", labels, cx, cc, true, &style.cost),
+			"Today |\tcc 8,100($0.30)\n\tcx 12,300($0.45)"
+		);
+		// 默认仍是 cx 在前。
+		assert_eq!(
+			format_both_title_one_line("Today", SourceLabels::default(), cx, cc, false, &style),
+			"Today | cx 12.3k | cc 8.1k"
+		);
+	}
+
+	#[test]
+	fn empty_state_title_replaces_title_only_when_every_source_is_missing() {
+		let title = || "Today cx 0".to_string();
+		assert_eq!(title_or_empty_state(title(), SourceAvailability::default()), EMPTY_STATE_TITLE);
+		assert_eq!(EMPTY_STATE_TITLE, "tokbar：无数据（点击设置）");
+
+		let rc_only = SourceAvailability {
+			rightcodes: true,
+			..Default::default()
+		};
+		assert_eq!(title_or_empty_state(title(), rc_only), "Today cx 0");
+	}
+
+	#[test]
+	fn rc_title_part_respects_rc_in_title() {
+		let base = "Today cx 1.0k".to_string();
+		assert_eq!(
+			append_rc_title_part(base.clone(), Some("rc $10/$20 R"), true),
+			"Today cx 1.0k rc $10/$20 R"
+		);
+		assert_eq!(append_rc_title_part(base.clone(), Some("rc $10/$20 R"), false), base);
+		assert_eq!(append_rc_title_part(base.clone(), None, true), base);
+	}
+
+	#[test]
+	fn sub_cent_costs_are_distinguishable_from_zero() {
+		let cost = CostFormat::default();
+		assert_eq!(cost.format(0.0), "$0.00");
+		assert_eq!(cost.format(0.004), "<$0.01");
+		assert_eq!(cost.format(0.006), "$0.01");
+		assert_eq!(cost.format(12.345), "$12.35");
+	}
+
+	#[test]
+	fn costs_convert_to_the_configured_currency() {
+		let eur = DisplayCurrency {
+			code: "EUR".to_string(),
+			symbol: "€".to_string(),
+			usd_rate: 0.9,
+		};
+		assert_eq!(format_cost(10.0, &eur, 2), "€9.00");
+		assert_eq!(format_cost(0.004, &eur, 2), "<€0.01");
+
+		let code_only = DisplayCurrency {
+			code: "GBP".to_string(),
+			symbol: String::new(),
+			usd_rate: 0.8,
+		};
+		assert_eq!(format_cost(2.5, &code_only, 2), "GBP 2.00");
+
+		let invalid_rate = DisplayCurrency {
+			usd_rate: 0.0,
+			..DisplayCurrency::default()
+		};
+		assert_eq!(format_cost(1.5, &invalid_rate, 2), "$1.50");
+	}
+
+	#[test]
+	fn cost_decimals_control_precision_and_the_below_precision_marker() {
+		let usd = DisplayCurrency::default();
+		assert_eq!(format_cost(0.0042, &usd, 2), "<$0.01");
+		assert_eq!(format_cost(0.0042, &usd, 4), "$0.0042");
+		assert_eq!(format_cost(0.00004, &usd, 4), "<$0.0001");
+		assert_eq!(format_cost(12.3456, &usd, 0), "$12");
+		assert_eq!(format_cost(0.2, &usd, 0), "<$1");
+		assert_eq!(format_cost(0.0, &usd, 4), "$0.0000");
+	}
+
+	#[test]
+	fn daily_cost_alert_fires_once_per_day() {
+		let day = NaiveDate::from_ymd_opt(2026, 2, 6).unwrap();
+		let next_day = NaiveDate::from_ymd_opt(2026, 2, 7).unwrap();
+
+		assert!(!daily_cost_alert_due(10.0, 9.99, day, None));
+		assert!(daily_cost_alert_due(10.0, 10.0, day, None));
+		assert!(!daily_cost_alert_due(10.0, 12.0, day, Some(day)));
+		assert!(daily_cost_alert_due(10.0, 12.0, next_day, Some(day)));
+
+		assert_eq!(daily_cost_alert_threshold(Some(5.0)), Some(5.0));
+		assert_eq!(daily_cost_alert_threshold(Some(0.0)), None);
+		assert_eq!(daily_cost_alert_threshold(Some(f64::NAN)), None);
+		assert_eq!(daily_cost_alert_threshold(None), None);
+	}
+
+	#[test]
+	fn budget_thresholds_pick_indicator() {
+		let defaults = BudgetThresholds::default();
+		assert_eq!(budget_level(90.0, &defaults), BudgetLevel::Warning);
+		assert_eq!(budget_indicator(90.0, &defaults), "🟡");
+		assert_eq!(budget_indicator(50.0, &defaults), "");
+
+		let custom = BudgetThresholds {
+			warning_percent: 60.0,
+			critical_percent: 85.0,
+			style: BudgetIndicatorStyle::Text,
+		};
+		assert_eq!(budget_level(90.0, &custom), BudgetLevel::Critical);
+		assert_eq!(budget_indicator(90.0, &custom), "[!!]");
+	}
+
+	#[test]
+	fn budget_indicator_prefixes_the_rc_title_part() {
+		let defaults = BudgetThresholds::default();
+		let part = "rc $18/$20 R".to_string();
+		assert_eq!(with_budget_indicator(part.clone(), Some(90.0), &defaults), "🟡 rc $18/$20 R");
+		assert_eq!(with_budget_indicator(part.clone(), Some(100.0), &defaults), "🔴 rc $18/$20 R");
+		assert_eq!(with_budget_indicator(part.clone(), Some(50.0), &defaults), part);
+		assert_eq!(with_budget_indicator(part.clone(), None, &defaults), part);
+	}
 }
@@ -7,17 +7,25 @@
 // - Tauri GUI/Tray 相关代码在 Windows 上跑测试时可能因为 WebView2 运行时环境差异导致测试可执行文件无法启动。
 // 因此我们把 GUI 部分放到 `app.rs`，并在 `cfg(not(test))` 下才编译/链接它。
 
-mod app_settings;
+pub mod app_settings;
+mod cache_lock;
+pub mod ccusage;
 mod claude;
 mod codex;
+mod config_dir;
 mod format;
 pub mod litellm;
+mod offset_cache;
+mod parallel;
 mod pricing;
+mod pricing_network_config;
 mod proxy_config;
 pub mod raw_format;
+mod refresh;
 mod rightcodes;
 mod rightcodes_api;
 mod rightcodes_token_store;
+pub mod status;
 
 #[cfg(test)]
 mod test_util;
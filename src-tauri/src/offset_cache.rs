@@ -0,0 +1,237 @@
+//! “全部”统计的增量解析缓存：按文件记录已解析到的字节位置，刷新时只解析新追加的部分。
+//!
+//! 说明：
+//! - 会话/日志文件只会追加写入；文件变短、修改时间倒退或已解析部分的末尾内容变了，
+//!   都视为文件被改写，回退为从头解析。
+//! - 缓存落在 `~/.tokbar/cache/<name>.json`，丢失或损坏时等同于没有缓存。
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::config_dir;
+
+/// 校验“已解析前缀未变”时比对的末尾字节数。
+const TAIL_CHECK_BYTES: u64 = 64;
+
+/// 缓存格式版本：结构或统计口径变化时递增，旧缓存整体作废。
+pub(crate) const CACHE_VERSION: u32 = 8;
+
+/// 稳定的 64 位 FNV-1a 哈希（需要落盘的指纹不能用 `DefaultHasher`，它不保证跨版本一致）。
+pub(crate) fn fnv1a64(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+	for byte in bytes {
+		hash ^= u64::from(*byte);
+		hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+	}
+	hash
+}
+
+/// 某个文件上次解析到的位置。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FileCheckpoint {
+	pub len: u64,
+	pub modified_ms: u64,
+	/// 已确认解析完的字节数（总是停在换行符之后；末尾不完整的行下次重新读）。
+	pub offset: u64,
+	/// `offset` 之前最多 64 字节的指纹。
+	pub tail_fingerprint: u64,
+}
+
+/// 文件当前的大小与修改时间。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStamp {
+	pub len: u64,
+	pub modified_ms: u64,
+}
+
+impl FileStamp {
+	pub(crate) fn of(path: &Path) -> Option<Self> {
+		let meta = fs::metadata(path).ok()?;
+		let modified_ms = meta
+			.modified()
+			.ok()
+			.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+			.map(|d| d.as_millis() as u64)
+			.unwrap_or(0);
+		Some(Self {
+			len: meta.len(),
+			modified_ms,
+		})
+	}
+}
+
+/// 能否从上次的位置接着解析；不能时返回 None（调用方从头解析）。
+pub(crate) fn resume_offset(
+	path: &Path,
+	checkpoint: &FileCheckpoint,
+	stamp: FileStamp,
+) -> Option<u64> {
+	if stamp.len < checkpoint.len
+		|| stamp.len < checkpoint.offset
+		|| stamp.modified_ms < checkpoint.modified_ms
+	{
+		return None;
+	}
+	if tail_fingerprint(path, checkpoint.offset)? != checkpoint.tail_fingerprint {
+		return None;
+	}
+	Some(checkpoint.offset)
+}
+
+/// `offset` 之前最多 64 字节的指纹。
+pub(crate) fn tail_fingerprint(path: &Path, offset: u64) -> Option<u64> {
+	let start = offset.saturating_sub(TAIL_CHECK_BYTES);
+	let mut file = File::open(path).ok()?;
+	file.seek(SeekFrom::Start(start)).ok()?;
+	let mut buf = Vec::with_capacity((offset - start) as usize);
+	file.take(offset - start).read_to_end(&mut buf).ok()?;
+	if buf.len() as u64 != offset - start {
+		return None;
+	}
+	Some(fnv1a64(&buf))
+}
+
+/// 从 `offset` 开始逐行读取：完整的行交给 `on_line`，返回 `(新的 offset, 末尾不完整的行)`。
+///
+/// 说明：无法按 UTF-8 解码的行直接跳过（与 `lines().flatten()` 的行为一致）。
+pub(crate) fn read_lines_from(
+	path: &Path,
+	offset: u64,
+	mut on_line: impl FnMut(&str),
+) -> Option<(u64, Option<String>)> {
+	let mut file = File::open(path).ok()?;
+	file.seek(SeekFrom::Start(offset)).ok()?;
+	let mut reader = BufReader::new(file);
+	let mut committed = offset;
+	let mut buf = Vec::new();
+	loop {
+		buf.clear();
+		let read = reader.read_until(b'\n', &mut buf).ok()?;
+		if read == 0 {
+			return Some((committed, None));
+		}
+		if buf.last() != Some(&b'\n') {
+			// 还在写入中的最后一行：本次照常统计，但不记入已解析的位置。
+			return Some((committed, String::from_utf8(buf).ok()));
+		}
+		committed += read as u64;
+		if let Ok(line) = std::str::from_utf8(&buf) {
+			on_line(line);
+		}
+	}
+}
+
+/// 解析到 `offset` 之后的检查点。
+pub(crate) fn checkpoint_at(path: &Path, stamp: FileStamp, offset: u64) -> FileCheckpoint {
+	FileCheckpoint {
+		len: stamp.len,
+		modified_ms: stamp.modified_ms,
+		offset,
+		tail_fingerprint: tail_fingerprint(path, offset).unwrap_or(0),
+	}
+}
+
+pub(crate) fn default_cache_path(name: &str) -> Option<PathBuf> {
+	Some(config_dir::tokbar_data_dir()?.join("cache").join(format!("{name}.json")))
+}
+
+pub(crate) fn load_cache<T: DeserializeOwned + Default>(path: Option<&Path>) -> T {
+	let Some(path) = path else {
+		return T::default();
+	};
+	let Ok(body) = fs::read_to_string(path) else {
+		return T::default();
+	};
+	serde_json::from_str(&body).unwrap_or_default()
+}
+
+/// 写入缓存；失败只影响下次刷新的速度，不影响结果，因此忽略错误。
+pub(crate) fn save_cache<T: Serialize>(path: Option<&Path>, cache: &T) {
+	let Some(path) = path else {
+		return;
+	};
+	let Ok(body) = serde_json::to_string(cache) else {
+		return;
+	};
+	if config_dir::ensure_parent_dir(path).is_ok() {
+		let _ = fs::write(path, body);
+	}
+}
+
+/// 删除缓存文件（“重新计算全部”时使用，保证真正从头解析）。
+pub(crate) fn clear_cache(path: Option<&Path>) {
+	if let Some(path) = path {
+		let _ = fs::remove_file(path);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+
+	#[test]
+	fn resumes_only_when_file_grew_with_unchanged_prefix() {
+		let dir = tempfile::tempdir().expect("tempdir");
+		let path = dir.path().join("s.jsonl");
+		fs::write(&path, "line one\nline two\npartial").expect("write");
+
+		let mut lines = Vec::new();
+		let (offset, partial) =
+			read_lines_from(&path, 0, |line| lines.push(line.to_string())).expect("read");
+		assert_eq!(lines, vec!["line one\n", "line two\n"]);
+		assert_eq!(partial.as_deref(), Some("partial"));
+		assert_eq!(offset, 18);
+		let stamp = FileStamp::of(&path).expect("stamp");
+		let checkpoint = checkpoint_at(&path, stamp, offset);
+
+		// 追加：从上次的位置继续，未完成的那一行会被重新读到。
+		let mut file = fs::OpenOptions::new().append(true).open(&path).expect("open");
+		file.write_all(b" done\nline three\n").expect("append");
+		let grown = FileStamp::of(&path).expect("stamp");
+		assert_eq!(resume_offset(&path, &checkpoint, grown), Some(18));
+		let mut tail = Vec::new();
+		read_lines_from(&path, 18, |line| tail.push(line.to_string())).expect("read");
+		assert_eq!(tail, vec!["partial done\n", "line three\n"]);
+
+		// 修改时间倒退 / 文件变短 / 已解析部分被改写：都从头解析。
+		let older = FileStamp {
+			modified_ms: checkpoint.modified_ms.saturating_sub(1),
+			..grown
+		};
+		assert_eq!(resume_offset(&path, &checkpoint, older), None);
+		fs::write(&path, "line one\n").expect("truncate");
+		let shrunk = FileStamp::of(&path).expect("stamp");
+		assert_eq!(resume_offset(&path, &checkpoint, shrunk), None);
+		fs::write(&path, "LINE ONE\nline two\npartial done\n").expect("rewrite");
+		let rewritten = FileStamp {
+			modified_ms: checkpoint.modified_ms,
+			..FileStamp::of(&path).expect("stamp")
+		};
+		assert_eq!(resume_offset(&path, &checkpoint, rewritten), None);
+	}
+
+	#[test]
+	fn cache_roundtrips_and_tolerates_missing_or_corrupt_files() {
+		let dir = tempfile::tempdir().expect("tempdir");
+		let path = dir.path().join("cache").join("codex.json");
+		let empty: Vec<u64> = load_cache(Some(&path));
+		assert!(empty.is_empty());
+
+		save_cache(Some(&path), &vec![1u64, 2, 3]);
+		let loaded: Vec<u64> = load_cache(Some(&path));
+		assert_eq!(loaded, vec![1, 2, 3]);
+
+		fs::write(&path, "{not json").expect("write");
+		let corrupt: Vec<u64> = load_cache(Some(&path));
+		assert!(corrupt.is_empty());
+
+		clear_cache(Some(&path));
+		assert!(!path.exists());
+	}
+}
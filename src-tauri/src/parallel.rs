@@ -0,0 +1,67 @@
+//! 日志文件的多核并行解析（标准库 scoped threads，不引入额外依赖）。
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// 文件数少于该值时直接顺序处理：起线程的开销比解析本身还大。
+const MIN_FILES_FOR_PARALLEL: usize = 8;
+
+/// 对每个文件执行 `f`，结果按输入顺序返回。
+///
+/// 说明：工作线程从共享下标里逐个领取文件（而不是预先切块），
+/// 这样少数超大的会话文件不会让某一个线程拖住整体。
+pub(crate) fn map_files<T, F>(files: &[PathBuf], f: F) -> Vec<T>
+where
+	T: Send,
+	F: Fn(&PathBuf) -> T + Sync,
+{
+	let workers = thread::available_parallelism()
+		.map(|n| n.get())
+		.unwrap_or(1)
+		.min(files.len());
+	if workers <= 1 || files.len() < MIN_FILES_FOR_PARALLEL {
+		return files.iter().map(&f).collect();
+	}
+
+	let next = AtomicUsize::new(0);
+	let mut indexed: Vec<(usize, T)> = thread::scope(|scope| {
+		let handles: Vec<_> = (0..workers)
+			.map(|_| {
+				scope.spawn(|| {
+					let mut out = Vec::new();
+					loop {
+						let index = next.fetch_add(1, Ordering::Relaxed);
+						let Some(path) = files.get(index) else {
+							break;
+						};
+						out.push((index, f(path)));
+					}
+					out
+				})
+			})
+			.collect();
+		handles
+			.into_iter()
+			.flat_map(|handle| handle.join().expect("log parsing worker panicked"))
+			.collect()
+	});
+
+	indexed.sort_by_key(|(index, _)| *index);
+	indexed.into_iter().map(|(_, value)| value).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn results_keep_input_order() {
+		let files: Vec<PathBuf> = (0..200).map(|i| PathBuf::from(format!("f{i}"))).collect();
+		let names = map_files(&files, |path| path.to_string_lossy().to_string());
+		let expected: Vec<String> = (0..200).map(|i| format!("f{i}")).collect();
+		assert_eq!(names, expected);
+
+		assert!(map_files(&[], |path: &PathBuf| path.clone()).is_empty());
+	}
+}
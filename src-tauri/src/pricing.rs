@@ -1,11 +1,16 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::cache_lock;
+use crate::offset_cache;
 
 pub const LITELLM_PRICING_URL: &str =
 	"https://raw.githubusercontent.com/BerriAI/litellm/main/model_prices_and_context_window.json";
 
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LiteLLMModelPricing {
 	pub input_cost_per_token: Option<f64>,
 	pub output_cost_per_token: Option<f64>,
@@ -17,6 +22,55 @@ pub struct LiteLLMModelPricing {
 	pub output_cost_per_token_above_200k_tokens: Option<f64>,
 	pub cache_creation_input_token_cost_above_200k_tokens: Option<f64>,
 	pub cache_read_input_token_cost_above_200k_tokens: Option<f64>,
+	/// `*_above_200k_tokens` 单价开始生效的 token 数；数据集没给时按 200k。
+	pub tier_threshold_tokens: Option<u64>,
+	/// Batch API 的折扣单价（LiteLLM 数据集中部分模型提供）。
+	pub input_cost_per_token_batches: Option<f64>,
+	pub output_cost_per_token_batches: Option<f64>,
+	/// 按“每千 token”计价的条目（部分非 Anthropic 模型）；只在没有逐 token 单价时使用。
+	pub input_cost_per_1k_tokens: Option<f64>,
+	pub output_cost_per_1k_tokens: Option<f64>,
+	/// 每次请求的固定费用（与 token 数无关，每条用量记录计一次）。
+	pub input_cost_per_request: Option<f64>,
+}
+
+impl LiteLLMModelPricing {
+	pub const DEFAULT_TIER_THRESHOLD_TOKENS: u64 = 200_000;
+
+	/// 分档计价的分界点（超过这个 token 数的部分按 `*_above_200k_tokens` 单价计）。
+	pub fn tier_threshold(&self) -> u64 {
+		self.tier_threshold_tokens.unwrap_or(Self::DEFAULT_TIER_THRESHOLD_TOKENS)
+	}
+
+	/// 是否有任何一类 token 的高档单价（没有时分界点无意义）。
+	pub fn has_tiered_pricing(&self) -> bool {
+		self.input_cost_per_token_above_200k_tokens.is_some()
+			|| self.output_cost_per_token_above_200k_tokens.is_some()
+			|| self.cache_creation_input_token_cost_above_200k_tokens.is_some()
+			|| self.cache_read_input_token_cost_above_200k_tokens.is_some()
+	}
+
+	/// 返回用于 Batch API 计费的价格：有 batch 单价的字段替换为 batch 单价，其余保持不变。
+	pub fn for_batch(mut self) -> Self {
+		if let Some(input) = self.input_cost_per_token_batches {
+			self.input_cost_per_token = Some(input);
+		}
+		if let Some(output) = self.output_cost_per_token_batches {
+			self.output_cost_per_token = Some(output);
+		}
+		self
+	}
+
+	/// 缺少逐 token 单价、但有“每千 token”单价时，换算成逐 token 单价。
+	fn with_per_1k_fallback(mut self) -> Self {
+		if self.input_cost_per_token.is_none() {
+			self.input_cost_per_token = self.input_cost_per_1k_tokens.map(|v| v / 1000.0);
+		}
+		if self.output_cost_per_token.is_none() {
+			self.output_cost_per_token = self.output_cost_per_1k_tokens.map(|v| v / 1000.0);
+		}
+		self
+	}
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
@@ -27,18 +81,63 @@ pub struct ClaudeTokens {
 	pub cache_read_input_tokens: u64,
 }
 
-#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub struct CodexTokens {
 	pub input_tokens: u64,
 	pub cached_input_tokens: u64,
 	pub output_tokens: u64,
+	/// 写入缓存的 token（与 Claude 一致，不包含在 `input_tokens` 里）。
+	pub cache_creation_input_tokens: u64,
 }
 
+impl CodexTokens {
+	pub fn saturating_add(self, other: Self) -> Self {
+		Self {
+			input_tokens: self.input_tokens.saturating_add(other.input_tokens),
+			cached_input_tokens: self.cached_input_tokens.saturating_add(other.cached_input_tokens),
+			output_tokens: self.output_tokens.saturating_add(other.output_tokens),
+			cache_creation_input_tokens: self
+				.cache_creation_input_tokens
+				.saturating_add(other.cache_creation_input_tokens),
+		}
+	}
+
+	/// 单次请求里各类 token 超过分档分界点的部分（与 Claude 一样按类别分别计算）。
+	///
+	/// 说明：约定与本结构一致，`input_tokens` 包含 `cached_input_tokens`。
+	pub fn above_tier(self, threshold: u64) -> Self {
+		let over = |tokens: u64| tokens.saturating_sub(threshold);
+		let cached = over(self.cached_input_tokens);
+		let non_cached = over(self.input_tokens.saturating_sub(self.cached_input_tokens));
+		Self {
+			input_tokens: non_cached.saturating_add(cached),
+			cached_input_tokens: cached,
+			output_tokens: over(self.output_tokens),
+			cache_creation_input_tokens: over(self.cache_creation_input_tokens),
+		}
+	}
+}
+
+/// 按模型名查价格：先试原名与各前缀形式；`strict` 为 false 时最后再做子串匹配兜底。
+///
+/// 说明：子串匹配可能命中别的模型（如 `gpt-4` 与 `gpt-4o-mini`），`strict` 时找不到就返回 None。
 pub fn find_model_pricing(
 	dataset: &HashMap<String, LiteLLMModelPricing>,
 	model_name: &str,
 	provider_prefixes: &[&str],
+	strict: bool,
 ) -> Option<LiteLLMModelPricing> {
+	find_model_pricing_with_key(dataset, model_name, provider_prefixes, strict)
+		.map(|(_, pricing)| pricing)
+}
+
+/// 同 [`find_model_pricing`]，并返回实际命中的价格表 key（便于排查“为什么是这个价”）。
+pub fn find_model_pricing_with_key(
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	model_name: &str,
+	provider_prefixes: &[&str],
+	strict: bool,
+) -> Option<(String, LiteLLMModelPricing)> {
 	let mut candidates = Vec::with_capacity(1 + provider_prefixes.len());
 	candidates.push(model_name.to_string());
 	for prefix in provider_prefixes {
@@ -47,44 +146,224 @@ pub fn find_model_pricing(
 
 	for candidate in candidates {
 		if let Some(pricing) = dataset.get(&candidate) {
-			return Some(pricing.clone());
+			return Some((candidate, pricing.clone()));
 		}
 	}
 
+	if strict {
+		return None;
+	}
 	let lower = model_name.to_ascii_lowercase();
-	for (key, value) in dataset {
-		let comparison = key.to_ascii_lowercase();
-		if comparison.contains(&lower) || lower.contains(&comparison) {
-			return Some(value.clone());
-		}
+	let key = match registered_index(dataset) {
+		Some(index) => index.fallback_key(&lower).map(str::to_string),
+		None => linear_fallback_key(dataset, &lower).cloned(),
+	};
+	let key = key?;
+	let pricing = dataset.get(&key)?.clone();
+	Some((key, pricing))
+}
+
+/// 子串回退候选的排序依据（越小越优先）：
+/// - 与模型名重合的部分越长越好（模型名包含 key 时为 key 长度，反之为模型名长度）；
+/// - 重合一样长时，长度越接近模型名越好；
+/// - 最后按 key 字典序，保证结果稳定。
+fn fallback_rank<'a>(key: &'a str, lower: &str) -> (Reverse<usize>, usize, &'a str) {
+	let common = key.len().min(lower.len());
+	(Reverse(common), key.len().abs_diff(lower.len()), key)
+}
+
+/// 子串回退（线性扫描）：在所有“互相包含”的 key 里按 [`fallback_rank`] 取最具体的一个。
+fn linear_fallback_key<'a>(
+	dataset: &'a HashMap<String, LiteLLMModelPricing>,
+	lower: &str,
+) -> Option<&'a String> {
+	dataset
+		.keys()
+		.filter(|key| {
+			let comparison = key.to_ascii_lowercase();
+			comparison.contains(lower) || lower.contains(&comparison)
+		})
+		.min_by(|a, b| fallback_rank(a, lower).cmp(&fallback_rank(b, lower)))
+}
+
+/// 价格表的内容指纹（按排序后的 key 与各自的单价计算，跨进程稳定）：
+/// 重新拉取到相同的数据集时可以复用已建好的索引；单价变了指纹也会变。
+pub(crate) fn dataset_fingerprint(dataset: &HashMap<String, LiteLLMModelPricing>) -> u64 {
+	let mut keys: Vec<&String> = dataset.keys().collect();
+	keys.sort();
+	let mut material = String::new();
+	for key in keys {
+		material.push_str(&format!("{key}={:?}\n", dataset[key]));
 	}
+	offset_cache::fnv1a64(material.as_bytes())
+}
 
-	None
+/// 子串回退的查找索引：与 [`linear_fallback_key`] 结果一致，但不再逐个扫描整张价格表。
+///
+/// 说明：
+/// - “key 包含模型名”：在所有小写 key 的后缀数组里二分查找以模型名开头的后缀；
+/// - “模型名包含 key”：枚举模型名的子串，直接查小写 key 表。
+struct PricingIndex {
+	fingerprint: u64,
+	/// 去重后的小写 key，以及每个小写 key 对应的字典序最小的原始 key。
+	lower_keys: Vec<String>,
+	original_keys: Vec<String>,
+	by_lower: HashMap<String, usize>,
+	/// `(lower_keys 下标, 字节偏移)`，按后缀排序。
+	suffixes: Vec<(usize, usize)>,
 }
 
-pub fn calculate_claude_cost_from_pricing(tokens: ClaudeTokens, pricing: &LiteLLMModelPricing) -> f64 {
-	const DEFAULT_TIERED_THRESHOLD: u64 = 200_000;
+impl PricingIndex {
+	fn build(dataset: &HashMap<String, LiteLLMModelPricing>, fingerprint: u64) -> Self {
+		let mut by_lower: HashMap<String, usize> = HashMap::new();
+		let mut lower_keys: Vec<String> = Vec::new();
+		let mut original_keys: Vec<String> = Vec::new();
+		for key in dataset.keys() {
+			let lower = key.to_ascii_lowercase();
+			match by_lower.get(&lower) {
+				Some(&i) => {
+					if *key < original_keys[i] {
+						original_keys[i] = key.clone();
+					}
+				}
+				None => {
+					by_lower.insert(lower.clone(), lower_keys.len());
+					lower_keys.push(lower);
+					original_keys.push(key.clone());
+				}
+			}
+		}
+
+		let mut suffixes: Vec<(usize, usize)> = lower_keys
+			.iter()
+			.enumerate()
+			.flat_map(|(i, lower)| {
+				lower
+					.char_indices()
+					.map(move |(offset, _)| (i, offset))
+					.chain(std::iter::once((i, lower.len())))
+			})
+			.collect();
+		suffixes.sort_by(|&(a, a_off), &(b, b_off)| {
+			lower_keys[a][a_off..].cmp(&lower_keys[b][b_off..])
+		});
+
+		Self {
+			fingerprint,
+			lower_keys,
+			original_keys,
+			by_lower,
+			suffixes,
+		}
+	}
+
+	fn fallback_key(&self, lower: &str) -> Option<&str> {
+		let mut best: Option<&str> = None;
+		let mut consider = |i: usize| {
+			let key = self.original_keys[i].as_str();
+			let rank = fallback_rank(key, lower);
+			if best.is_none_or(|current| rank < fallback_rank(current, lower)) {
+				best = Some(key);
+			}
+		};
 
-	fn tiered_cost(total_tokens: u64, base: Option<f64>, above: Option<f64>) -> f64 {
-		if total_tokens == 0 {
-			return 0.0;
+		// key 包含模型名。
+		let suffix = |&(i, offset): &(usize, usize)| &self.lower_keys[i][offset..];
+		let start = self.suffixes.partition_point(|entry| suffix(entry) < lower);
+		for entry in &self.suffixes[start..] {
+			if !suffix(entry).starts_with(lower) {
+				break;
+			}
+			consider(entry.0);
 		}
 
-		if total_tokens > DEFAULT_TIERED_THRESHOLD {
-			if let Some(above_price) = above {
-				let below_tokens = DEFAULT_TIERED_THRESHOLD as f64;
-				let above_tokens = (total_tokens - DEFAULT_TIERED_THRESHOLD) as f64;
-				let mut cost = above_tokens * above_price;
-				if let Some(base_price) = base {
-					cost += below_tokens * base_price;
+		// 模型名包含 key。
+		let bounds: Vec<usize> = lower
+			.char_indices()
+			.map(|(offset, _)| offset)
+			.chain(std::iter::once(lower.len()))
+			.collect();
+		for (n, &from) in bounds.iter().enumerate() {
+			for &to in &bounds[n..] {
+				if let Some(&i) = self.by_lower.get(&lower[from..to]) {
+					consider(i);
 				}
-				return cost;
 			}
 		}
 
-		base.unwrap_or(0.0) * (total_tokens as f64)
+		best
 	}
+}
 
+/// 当前价格表对应的索引。只保存弱引用：数据集被替换/释放后自动失效，不会误用到别的数据集上。
+struct RegisteredIndex {
+	dataset: Weak<HashMap<String, LiteLLMModelPricing>>,
+	index: Arc<PricingIndex>,
+}
+
+static INDEX: OnceLock<Mutex<Option<RegisteredIndex>>> = OnceLock::new();
+
+fn index_registry() -> &'static Mutex<Option<RegisteredIndex>> {
+	INDEX.get_or_init(|| Mutex::new(None))
+}
+
+/// 为（新加载的）价格表建立查找索引；内容指纹不变时复用旧索引。
+pub(crate) fn register_dataset(dataset: &Arc<HashMap<String, LiteLLMModelPricing>>) {
+	register_dataset_in(index_registry(), dataset);
+}
+
+fn register_dataset_in(
+	registry: &Mutex<Option<RegisteredIndex>>,
+	dataset: &Arc<HashMap<String, LiteLLMModelPricing>>,
+) {
+	if dataset.is_empty() {
+		return;
+	}
+	let fingerprint = dataset_fingerprint(dataset);
+	let mut guard = cache_lock::lock_or_reset(registry);
+	let index = match guard.as_ref() {
+		Some(registered) if registered.index.fingerprint == fingerprint => registered.index.clone(),
+		_ => Arc::new(PricingIndex::build(dataset, fingerprint)),
+	};
+	*guard = Some(RegisteredIndex {
+		dataset: Arc::downgrade(dataset),
+		index,
+	});
+}
+
+/// 只有传入的正是已注册（且仍存活）的那份价格表时才返回索引；其余情况回退到线性扫描。
+fn registered_index(dataset: &HashMap<String, LiteLLMModelPricing>) -> Option<Arc<PricingIndex>> {
+	registered_index_in(index_registry(), dataset)
+}
+
+fn registered_index_in(
+	registry: &Mutex<Option<RegisteredIndex>>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+) -> Option<Arc<PricingIndex>> {
+	let guard = cache_lock::lock_or_reset(registry);
+	let registered = guard.as_ref()?;
+	let live = registered.dataset.upgrade()?;
+	std::ptr::eq(Arc::as_ptr(&live), dataset).then(|| registered.index.clone())
+}
+
+/// 分档计价：`total_tokens` 中有 `above_tokens` 超过分界点，这部分按高档单价计，其余按基础单价。
+///
+/// 说明：没有高档单价时全部按基础单价；没有基础单价时只计超出部分（与 ccusage 一致）。
+fn tiered_cost(total_tokens: u64, above_tokens: u64, base: Option<f64>, above: Option<f64>) -> f64 {
+	if let Some(above_price) = above.filter(|_| above_tokens > 0) {
+		let below_tokens = total_tokens.saturating_sub(above_tokens) as f64;
+		return below_tokens * base.unwrap_or(0.0) + above_tokens as f64 * above_price;
+	}
+	base.unwrap_or(0.0) * (total_tokens as f64)
+}
+
+pub fn calculate_claude_cost_from_pricing(tokens: ClaudeTokens, pricing: &LiteLLMModelPricing) -> f64 {
+	let threshold = pricing.tier_threshold();
+	let tiered_cost = |total_tokens: u64, base: Option<f64>, above: Option<f64>| {
+		tiered_cost(total_tokens, total_tokens.saturating_sub(threshold), base, above)
+	};
+
+	let pricing = &pricing.clone().with_per_1k_fallback();
 	let input = tiered_cost(
 		tokens.input_tokens,
 		pricing.input_cost_per_token,
@@ -106,26 +385,71 @@ pub fn calculate_claude_cost_from_pricing(tokens: ClaudeTokens, pricing: &LiteLL
 		pricing.cache_read_input_token_cost_above_200k_tokens,
 	);
 
-	input + output + cache_creation + cache_read
+	// 调用方按每条用量记录调用一次，因此按请求计价的固定费用在这里加一次即可。
+	let per_request = pricing.input_cost_per_request.unwrap_or(0.0);
+
+	input + output + cache_creation + cache_read + per_request
 }
 
-pub fn calculate_codex_cost_from_pricing(tokens: CodexTokens, pricing: &LiteLLMModelPricing) -> f64 {
-	let non_cached_input_tokens = tokens
-		.input_tokens
-		.saturating_sub(tokens.cached_input_tokens) as f64;
-	let cached_input_tokens = tokens.cached_input_tokens as f64;
-	let output_tokens = tokens.output_tokens as f64;
+/// 价格表里带高档单价的模型用到的分档分界点（升序、去重）。
+pub fn tier_thresholds(dataset: &HashMap<String, LiteLLMModelPricing>) -> Vec<u64> {
+	let mut thresholds: Vec<u64> = dataset
+		.values()
+		.filter(|pricing| pricing.has_tiered_pricing())
+		.map(LiteLLMModelPricing::tier_threshold)
+		.collect();
+	thresholds.sort_unstable();
+	thresholds.dedup();
+	thresholds
+}
 
-	let input_cost = pricing.input_cost_per_token.unwrap_or(0.0);
-	let cache_read_cost = pricing
-		.cache_read_input_token_cost
-		.or(pricing.input_cost_per_token)
-		.unwrap_or(0.0);
-	let output_cost = pricing.output_cost_per_token.unwrap_or(0.0);
+/// 单次 Codex 请求的成本（分档按该模型的分界点计算）。
+pub fn calculate_codex_cost_from_pricing(tokens: CodexTokens, pricing: &LiteLLMModelPricing) -> f64 {
+	calculate_codex_cost_with_tiers(tokens, tokens.above_tier(pricing.tier_threshold()), pricing)
+}
 
-	(non_cached_input_tokens * input_cost)
-		+ (cached_input_tokens * cache_read_cost)
-		+ (output_tokens * output_cost)
+/// 多次请求累计后的 Codex 成本：`above_tier` 是逐条请求累加的 [`CodexTokens::above_tier`]，
+/// 这部分按 `*_above_200k_tokens` 单价计，其余按基础单价。
+///
+/// 说明：缓存读取/写入没有单价时退回普通输入单价，高档单价也一并退回输入的高档单价。
+pub fn calculate_codex_cost_with_tiers(
+	tokens: CodexTokens,
+	above_tier: CodexTokens,
+	pricing: &LiteLLMModelPricing,
+) -> f64 {
+	let non_cached = |t: CodexTokens| t.input_tokens.saturating_sub(t.cached_input_tokens);
+	let input = (
+		pricing.input_cost_per_token,
+		pricing.input_cost_per_token_above_200k_tokens,
+	);
+	let cache_read = match pricing.cache_read_input_token_cost {
+		Some(base) => (Some(base), pricing.cache_read_input_token_cost_above_200k_tokens),
+		None => input,
+	};
+	let cache_creation = match pricing.cache_creation_input_token_cost {
+		Some(base) => (Some(base), pricing.cache_creation_input_token_cost_above_200k_tokens),
+		None => input,
+	};
+
+	tiered_cost(non_cached(tokens), non_cached(above_tier), input.0, input.1)
+		+ tiered_cost(
+			tokens.cached_input_tokens,
+			above_tier.cached_input_tokens,
+			cache_read.0,
+			cache_read.1,
+		)
+		+ tiered_cost(
+			tokens.cache_creation_input_tokens,
+			above_tier.cache_creation_input_tokens,
+			cache_creation.0,
+			cache_creation.1,
+		)
+		+ tiered_cost(
+			tokens.output_tokens,
+			above_tier.output_tokens,
+			pricing.output_cost_per_token,
+			pricing.output_cost_per_token_above_200k_tokens,
+		)
 }
 
 #[cfg(test)]
@@ -148,6 +472,7 @@ mod tests {
 			&dataset,
 			"claude-opus-4-20250514",
 			&["anthropic/", "claude-"],
+			true,
 		);
 		assert!(pricing.is_some());
 	}
@@ -165,8 +490,127 @@ mod tests {
 			},
 		);
 
-		let pricing = find_model_pricing(&dataset, "gpt-5.2", &["openai/"]);
+		let pricing = find_model_pricing(&dataset, "gpt-5.2", &["openai/"], false);
 		assert!(pricing.is_some());
+		// 严格模式只认原名与前缀形式。
+		assert!(find_model_pricing(&dataset, "gpt-5.2", &["openai/"], true).is_none());
+		assert!(find_model_pricing(&dataset, "openai/gpt-5", &["openai/"], true).is_none());
+		assert!(find_model_pricing(&dataset, "gpt-5", &["openai/"], true).is_some());
+	}
+
+	#[test]
+	fn substring_fallback_prefers_the_most_specific_key() {
+		let dataset: HashMap<String, LiteLLMModelPricing> =
+			["gpt-4", "gpt-4o", "gpt-4o-mini", "azure/gpt-4o-mini", "gpt-4o-mini-tts"]
+				.iter()
+				.map(|k| (k.to_string(), LiteLLMModelPricing::default()))
+				.collect();
+		let key = |name: &str| {
+			find_model_pricing_with_key(&dataset, name, &[], false).map(|(k, _)| k)
+		};
+
+		// 模型名包含多个 key：取重合最长的。
+		assert_eq!(key("gpt-4o-mini-2024-07-18").as_deref(), Some("gpt-4o-mini"));
+		// 多个 key 包含模型名：取长度最接近的。
+		assert_eq!(key("4o-mini").as_deref(), Some("gpt-4o-mini"));
+		assert_eq!(key("4o").as_deref(), Some("gpt-4o"));
+	}
+
+	#[test]
+	fn indexed_fallback_matches_linear_scan() {
+		let keys = [
+			"gpt-5",
+			"openai/gpt-5",
+			"GPT-5-Mini",
+			"gpt-4o",
+			"azure/gpt-4o-mini",
+			"anthropic/claude-sonnet-4-20250514",
+			"claude-3-5-haiku-20241022",
+			"bedrock/us.anthropic.claude-opus-4-1-20250805-v1:0",
+			"o3",
+			"deepseek/deepseek-chat",
+			"gemini/gemini-2.5-pro",
+		];
+		let dataset: HashMap<String, LiteLLMModelPricing> = keys
+			.iter()
+			.map(|k| (k.to_string(), LiteLLMModelPricing::default()))
+			.collect();
+		let index = PricingIndex::build(&dataset, dataset_fingerprint(&dataset));
+
+		let names = [
+			"gpt-5",
+			"gpt-5.2",
+			"gpt-5-mini",
+			"GPT-5-MINI",
+			"gpt-4o-mini-2024",
+			"claude-sonnet-4",
+			"claude-opus-4-1-20250805",
+			"haiku",
+			"o3-pro",
+			"o",
+			"deepseek-chat",
+			"gemini-2.5",
+			"glm-4.6",
+			"qwen3-coder-plus",
+			"unknown",
+			"",
+		];
+		for name in names {
+			let lower = name.to_ascii_lowercase();
+			assert_eq!(
+				index.fallback_key(&lower),
+				linear_fallback_key(&dataset, &lower).map(String::as_str),
+				"name: {name:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn registered_index_tracks_the_live_dataset() {
+		let registry = Mutex::new(None);
+		let mut first = HashMap::new();
+		first.insert("gpt-5".to_string(), LiteLLMModelPricing::default());
+		let first = Arc::new(first);
+		register_dataset_in(&registry, &first);
+		let first_index = registered_index_in(&registry, &first).expect("indexed");
+
+		// 内容相同但不是同一份数据：不使用索引（回退到线性扫描）。
+		let copy = (*first).clone();
+		assert!(registered_index_in(&registry, &copy).is_none());
+
+		// 重新拉取到相同内容：指纹不变，复用已建好的索引。
+		let refetched = Arc::new(copy);
+		register_dataset_in(&registry, &refetched);
+		let reused = registered_index_in(&registry, &refetched).expect("indexed");
+		assert!(Arc::ptr_eq(&first_index, &reused));
+		assert!(registered_index_in(&registry, &first).is_none());
+
+		// 内容变化：按新指纹重建。
+		let mut second = HashMap::new();
+		second.insert("claude-sonnet-4".to_string(), LiteLLMModelPricing::default());
+		let second = Arc::new(second);
+		register_dataset_in(&registry, &second);
+		let rebuilt = registered_index_in(&registry, &second).expect("indexed");
+		assert!(!Arc::ptr_eq(&first_index, &rebuilt));
+		assert_eq!(rebuilt.fallback_key("claude-sonnet-4-20250514"), Some("claude-sonnet-4"));
+		assert_eq!(rebuilt.fallback_key("gpt-5.2"), None);
+
+		drop(second);
+		let orphan = HashMap::new();
+		assert!(registered_index_in(&registry, &orphan).is_none());
+	}
+
+	#[test]
+	fn dataset_fingerprint_covers_prices_not_just_keys() {
+		let dataset = |input: f64| {
+			let pricing = LiteLLMModelPricing {
+				input_cost_per_token: Some(input),
+				..Default::default()
+			};
+			HashMap::from([("gpt-5".to_string(), pricing)])
+		};
+		assert_eq!(dataset_fingerprint(&dataset(1e-6)), dataset_fingerprint(&dataset(1e-6)));
+		assert_ne!(dataset_fingerprint(&dataset(1e-6)), dataset_fingerprint(&dataset(2e-6)));
 	}
 
 	#[test]
@@ -187,6 +631,37 @@ mod tests {
 		assert!((cost - expected).abs() < 1e-9);
 	}
 
+	#[test]
+	fn claude_tiered_cost_uses_the_model_threshold_when_present() {
+		let pricing = LiteLLMModelPricing {
+			input_cost_per_token: Some(3e-6),
+			input_cost_per_token_above_200k_tokens: Some(6e-6),
+			output_cost_per_token: Some(1e-5),
+			output_cost_per_token_above_200k_tokens: Some(2e-5),
+			tier_threshold_tokens: Some(128_000),
+			..Default::default()
+		};
+
+		let tokens = ClaudeTokens {
+			input_tokens: 300_000,
+			output_tokens: 100_000,
+			..Default::default()
+		};
+
+		// 输入超过 128k 的部分按高档计；输出没超过分界点，全部按基础单价。
+		let cost = calculate_claude_cost_from_pricing(tokens, &pricing);
+		let expected = 128_000.0 * 3e-6 + 172_000.0 * 6e-6 + 100_000.0 * 1e-5;
+		assert!((cost - expected).abs() < 1e-9);
+
+		let parsed: LiteLLMModelPricing = serde_json::from_value(serde_json::json!({
+			"input_cost_per_token": 3e-6,
+			"tier_threshold_tokens": 128000
+		}))
+		.expect("parse");
+		assert_eq!(parsed.tier_threshold(), 128_000);
+		assert_eq!(LiteLLMModelPricing::default().tier_threshold(), 200_000);
+	}
+
 	#[test]
 	fn claude_tiered_cost_charges_only_above_threshold_if_base_missing() {
 		let pricing = LiteLLMModelPricing {
@@ -205,6 +680,89 @@ mod tests {
 		assert!((cost - expected).abs() < 1e-9);
 	}
 
+	#[test]
+	fn claude_cost_applies_per_request_and_per_1k_pricing() {
+		let pricing = LiteLLMModelPricing {
+			input_cost_per_1k_tokens: Some(0.003),
+			output_cost_per_token: Some(1.5e-5),
+			output_cost_per_1k_tokens: Some(1.0),
+			input_cost_per_request: Some(0.01),
+			..Default::default()
+		};
+		let tokens = ClaudeTokens {
+			input_tokens: 1_000,
+			output_tokens: 100,
+			..Default::default()
+		};
+
+		// 每千 token 单价只补缺失的逐 token 单价；逐 token 单价存在时优先使用。
+		let cost = calculate_claude_cost_from_pricing(tokens, &pricing);
+		let expected = 1_000.0 * 3e-6 + 100.0 * 1.5e-5 + 0.01;
+		assert!((cost - expected).abs() < 1e-9);
+
+		// 固定费用每条记录只加一次：两条记录合计两次。
+		let total: f64 = (0..2).map(|_| calculate_claude_cost_from_pricing(tokens, &pricing)).sum();
+		assert!((total - 2.0 * expected).abs() < 1e-9);
+
+		// 没有这些字段时保持逐 token 计价不变。
+		let plain = LiteLLMModelPricing {
+			input_cost_per_token: Some(3e-6),
+			..Default::default()
+		};
+		assert!((calculate_claude_cost_from_pricing(tokens, &plain) - 3e-3).abs() < 1e-12);
+	}
+
+	#[test]
+	fn codex_tiered_cost_matches_ccusage_logic() {
+		let pricing = LiteLLMModelPricing {
+			input_cost_per_token: Some(3e-6),
+			input_cost_per_token_above_200k_tokens: Some(6e-6),
+			..Default::default()
+		};
+
+		let tokens = CodexTokens {
+			input_tokens: 300_000,
+			..Default::default()
+		};
+
+		let cost = calculate_codex_cost_from_pricing(tokens, &pricing);
+		let expected = 200_000.0 * 3e-6 + 100_000.0 * 6e-6;
+		assert!((cost - expected).abs() < 1e-9);
+
+		// 累计值：只有单次超过分界点的部分按高档计（两次 150k 的请求都不超）。
+		let request = CodexTokens {
+			input_tokens: 150_000,
+			..Default::default()
+		};
+		let total = request.saturating_add(request);
+		let above_tier = request.above_tier(200_000).saturating_add(request.above_tier(200_000));
+		let cost = calculate_codex_cost_with_tiers(total, above_tier, &pricing);
+		assert!((cost - 300_000.0 * 3e-6).abs() < 1e-9);
+	}
+
+	#[test]
+	fn codex_tiered_cost_splits_cached_input_by_category() {
+		let pricing = LiteLLMModelPricing {
+			input_cost_per_token: Some(2e-6),
+			input_cost_per_token_above_200k_tokens: Some(4e-6),
+			cache_read_input_token_cost: Some(2e-7),
+			cache_read_input_token_cost_above_200k_tokens: Some(4e-7),
+			output_cost_per_token: Some(1e-5),
+			..Default::default()
+		};
+
+		// 未命中缓存 50k、命中缓存 250k：只有缓存读取超过分界点。
+		let tokens = CodexTokens {
+			input_tokens: 300_000,
+			cached_input_tokens: 250_000,
+			output_tokens: 1_000,
+			..Default::default()
+		};
+		let cost = calculate_codex_cost_from_pricing(tokens, &pricing);
+		let expected = 50_000.0 * 2e-6 + 200_000.0 * 2e-7 + 50_000.0 * 4e-7 + 1_000.0 * 1e-5;
+		assert!((cost - expected).abs() < 1e-9);
+	}
+
 	#[test]
 	fn codex_cost_splits_cached_and_non_cached_input() {
 		let pricing = LiteLLMModelPricing {
@@ -218,10 +776,36 @@ mod tests {
 			input_tokens: 1_000,
 			cached_input_tokens: 200,
 			output_tokens: 500,
+			..Default::default()
 		};
 
 		let cost = calculate_codex_cost_from_pricing(tokens, &pricing);
 		let expected = 800.0 * 1.25e-6 + 200.0 * 1.25e-7 + 500.0 * 1e-5;
 		assert!((cost - expected).abs() < 1e-12);
 	}
+
+	#[test]
+	fn codex_cost_charges_cache_creation_with_input_fallback() {
+		let tokens = CodexTokens {
+			input_tokens: 1_000,
+			cache_creation_input_tokens: 400,
+			..Default::default()
+		};
+
+		let priced = LiteLLMModelPricing {
+			input_cost_per_token: Some(1e-6),
+			cache_creation_input_token_cost: Some(1.25e-6),
+			..Default::default()
+		};
+		let cost = calculate_codex_cost_from_pricing(tokens, &priced);
+		assert!((cost - (1_000.0 * 1e-6 + 400.0 * 1.25e-6)).abs() < 1e-12);
+
+		// 没有缓存写入单价时按普通输入单价计。
+		let input_only = LiteLLMModelPricing {
+			input_cost_per_token: Some(1e-6),
+			..Default::default()
+		};
+		let cost = calculate_codex_cost_from_pricing(tokens, &input_only);
+		assert!((cost - 1_400.0 * 1e-6).abs() < 1e-12);
+	}
 }
@@ -0,0 +1,150 @@
+//! 价格拉取的网络参数（检查/数据集 TTL、连接/总超时与地址族偏好），
+//! 保存在 `~/.tokbar/pricing_network.json`。
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config_dir;
+
+/// 连接价格地址时使用哪种 IP 地址族。
+///
+/// 说明：ureq 按解析结果的顺序逐个尝试连接（没有 happy-eyeballs），在纯 IPv6 网络里
+/// 排在前面的 IPv4 地址会一直超时，导致价格始终不可用；这里允许只用/优先某一族。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+	/// 保持系统解析顺序。
+	#[default]
+	Auto,
+	PreferIpv4,
+	PreferIpv6,
+	Ipv4Only,
+	Ipv6Only,
+}
+
+impl AddressFamily {
+	/// 按偏好过滤并排序解析结果（排序是稳定的，同一族内保持系统顺序）。
+	pub fn apply(self, mut addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+		match self {
+			AddressFamily::Auto => {}
+			AddressFamily::PreferIpv4 => addrs.sort_by_key(|addr| !addr.is_ipv4()),
+			AddressFamily::PreferIpv6 => addrs.sort_by_key(|addr| !addr.is_ipv6()),
+			AddressFamily::Ipv4Only => addrs.retain(SocketAddr::is_ipv4),
+			AddressFamily::Ipv6Only => addrs.retain(SocketAddr::is_ipv6),
+		}
+		addrs
+	}
+}
+
+/// 时长字段单位均为秒；缺省值与历史上的编译期常量一致，没有配置文件时行为不变。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingNetworkConfig {
+	/// 两次可达性检查（HEAD）之间的最短间隔。
+	pub check_ttl_secs: u64,
+	/// 已拉取的数据集多久后重新下载。
+	pub dataset_ttl_secs: u64,
+	/// 建立连接的超时（卫星链路等高延迟网络需要调大）。
+	pub connect_timeout_secs: u64,
+	/// 单次请求的总超时（包含下载完整价格 JSON）。
+	pub total_timeout_secs: u64,
+	/// 连接时的地址族偏好（纯 IPv6/纯 IPv4 网络使用）。
+	pub address_family: AddressFamily,
+	/// 连续失败后重试间隔的上限（实际间隔另有 ±20% 的随机抖动）。
+	pub max_backoff_secs: u64,
+}
+
+impl Default for PricingNetworkConfig {
+	fn default() -> Self {
+		Self {
+			check_ttl_secs: 25,
+			dataset_ttl_secs: 60 * 60 * 12,
+			connect_timeout_secs: 3,
+			total_timeout_secs: 8,
+			address_family: AddressFamily::Auto,
+			max_backoff_secs: 60 * 30,
+		}
+	}
+}
+
+impl PricingNetworkConfig {
+	pub fn check_ttl(&self) -> Duration {
+		Duration::from_secs(self.check_ttl_secs)
+	}
+
+	pub fn dataset_ttl(&self) -> Duration {
+		Duration::from_secs(self.dataset_ttl_secs)
+	}
+
+	/// 上限为 0 会让失败后立即重试、失去退避的意义，这里至少保留 1 分钟。
+	pub fn max_backoff(&self) -> Duration {
+		Duration::from_secs(self.max_backoff_secs.max(60))
+	}
+
+	/// 超时为 0 会让每次请求立即失败，这里至少保留 1 秒。
+	pub fn connect_timeout(&self) -> Duration {
+		Duration::from_secs(self.connect_timeout_secs.max(1))
+	}
+
+	pub fn total_timeout(&self) -> Duration {
+		Duration::from_secs(self.total_timeout_secs.max(1))
+	}
+}
+
+fn default_config_path() -> Option<PathBuf> {
+	Some(config_dir::tokbar_data_dir()?.join("pricing_network.json"))
+}
+
+pub fn load_pricing_network_config() -> PricingNetworkConfig {
+	let Some(path) = default_config_path() else {
+		return PricingNetworkConfig::default();
+	};
+	let Ok(body) = fs::read_to_string(path) else {
+		return PricingNetworkConfig::default();
+	};
+	serde_json::from_str::<PricingNetworkConfig>(&body).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn missing_fields_fall_back_to_defaults() {
+		let config: PricingNetworkConfig =
+			serde_json::from_str(r#"{"connect_timeout_secs":20}"#).expect("parse");
+		assert_eq!(config.connect_timeout(), Duration::from_secs(20));
+		assert_eq!(config.check_ttl(), Duration::from_secs(25));
+		assert_eq!(config.dataset_ttl(), Duration::from_secs(60 * 60 * 12));
+		assert_eq!(config.total_timeout(), Duration::from_secs(8));
+
+		let zero = PricingNetworkConfig {
+			connect_timeout_secs: 0,
+			..Default::default()
+		};
+		assert_eq!(zero.connect_timeout(), Duration::from_secs(1));
+	}
+
+	#[test]
+	fn address_family_filters_and_orders_resolved_addresses() {
+		let config: PricingNetworkConfig =
+			serde_json::from_str(r#"{"address_family":"prefer_ipv6"}"#).expect("parse");
+		assert_eq!(config.address_family, AddressFamily::PreferIpv6);
+		assert_eq!(PricingNetworkConfig::default().address_family, AddressFamily::Auto);
+
+		let v4: SocketAddr = "192.0.2.1:443".parse().expect("v4");
+		let v4b: SocketAddr = "192.0.2.2:443".parse().expect("v4");
+		let v6: SocketAddr = "[2001:db8::1]:443".parse().expect("v6");
+		let resolved = vec![v4, v6, v4b];
+
+		assert_eq!(AddressFamily::Auto.apply(resolved.clone()), resolved);
+		assert_eq!(AddressFamily::PreferIpv6.apply(resolved.clone()), vec![v6, v4, v4b]);
+		assert_eq!(AddressFamily::PreferIpv4.apply(resolved.clone()), vec![v4, v4b, v6]);
+		assert_eq!(AddressFamily::Ipv4Only.apply(resolved.clone()), vec![v4, v4b]);
+		assert_eq!(AddressFamily::Ipv6Only.apply(resolved), vec![v6]);
+	}
+}
@@ -1,5 +1,39 @@
+use std::collections::HashMap;
+
+use crate::format::{format_cost, DisplayCurrency, DEFAULT_COST_DECIMALS};
 use crate::usage::UsageTotals;
 
+/// 标题/菜单里代表来源的缩写（默认 `cx` / `cc`，可在设置里改成 `Codex` / `Claude`），
+/// 以及 both 形式里两个来源的先后顺序。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLabels<'a> {
+	pub cx: &'a str,
+	pub cc: &'a str,
+	/// both 形式里 cc 排在 cx 前面（默认 cx 在前）。
+	pub cc_first: bool,
+}
+
+impl Default for SourceLabels<'_> {
+	fn default() -> Self {
+		Self {
+			cx: "cx",
+			cc: "cc",
+			cc_first: false,
+		}
+	}
+}
+
+impl SourceLabels<'_> {
+	/// 按设置的顺序返回 (前, 后) 两段。
+	pub fn ordered<T>(&self, cx: T, cc: T) -> (T, T) {
+		if self.cc_first {
+			(cc, cx)
+		} else {
+			(cx, cc)
+		}
+	}
+}
+
 pub fn format_u64_with_commas(value: u64) -> String {
 	let s = value.to_string();
 	let mut out = String::with_capacity(s.len() + s.len() / 3);
@@ -15,17 +49,42 @@ pub fn format_u64_with_commas(value: u64) -> String {
 	out.chars().rev().collect()
 }
 
+/// 金额的写法：展示币种与保留几位小数（调用方按设置构造一次后传给各格式化函数）。
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostFormat {
+	pub currency: DisplayCurrency,
+	pub decimals: usize,
+}
+
+impl Default for CostFormat {
+	fn default() -> Self {
+		Self {
+			currency: DisplayCurrency::default(),
+			decimals: DEFAULT_COST_DECIMALS,
+		}
+	}
+}
+
+impl CostFormat {
+	/// 标题/菜单/CLI 共用的金额写法：非 0 但小于能显示的最小单位时显示 `<$0.01`，
+	/// 与真正的 `$0.00` 区分开。
+	pub fn format(&self, cost_usd: f64) -> String {
+		format_cost(cost_usd, &self.currency, self.decimals)
+	}
+}
+
 pub fn format_single_title_raw(
 	period: &str,
 	source_abbr: &str,
 	totals: UsageTotals,
 	show_cost: bool,
+	cost: &CostFormat,
 ) -> String {
 	if show_cost {
 		return format!(
 			"{period} {source_abbr} {tokens}({cost})",
 			tokens = format_u64_with_commas(totals.total_tokens),
-			cost = format!("${:.2}", totals.cost_usd),
+			cost = cost.format(totals.cost_usd),
 		);
 	}
 
@@ -37,30 +96,146 @@ pub fn format_single_title_raw(
 
 pub fn format_both_title_raw(
 	period: &str,
+	labels: SourceLabels<'_>,
 	cx: UsageTotals,
 	cc: UsageTotals,
 	show_cost: bool,
+	cost: &CostFormat,
 ) -> String {
 	let left = format!("{period} |");
-	let cx_line = if show_cost {
-		format!(
-			"cx {tokens}({cost})",
-			tokens = format_u64_with_commas(cx.total_tokens),
-			cost = format!("${:.2}", cx.cost_usd)
+	let cx_line = format!("{} {}", labels.cx, format_totals_raw(cx, show_cost, cost));
+	let cc_line = format!("{} {}", labels.cc, format_totals_raw(cc, show_cost, cost));
+	let (first, second) = labels.ordered(cx_line, cc_line);
+	format!("{left}\t{first}\n\t{second}")
+}
+
+/// 菜单双列行里第一列的最小宽度（字符数）：足够放下 `cx 999,999,999($9,999.99)`，
+/// 这样数字位数在刷新之间变化时，第二列的起点保持不变。
+const BOTH_MENU_FIRST_COLUMN_WIDTH: usize = 28;
+
+/// 菜单用的单行双列形式：`Today | cx 1,234($0.01)          cc 456($0.02)`。
+///
+/// 说明：菜单项只能是单行，`format_both_title_raw` 的两行形式放不进去；这里把第一列补齐到固定宽度，
+/// 超出时退化为两个空格分隔。
+pub fn format_both_menu_line(
+	period: &str,
+	labels: SourceLabels<'_>,
+	cx: UsageTotals,
+	cc: UsageTotals,
+	show_cost: bool,
+	cost: &CostFormat,
+) -> String {
+	let cx_cell = format!("{} {}", labels.cx, format_totals_raw(cx, show_cost, cost));
+	let cc_cell = format!("{} {}", labels.cc, format_totals_raw(cc, show_cost, cost));
+	let (first, second) = labels.ordered(cx_cell, cc_cell);
+	let padding = BOTH_MENU_FIRST_COLUMN_WIDTH.saturating_sub(first.chars().count()).max(2);
+	format!("{period} | {first}{}{second}", " ".repeat(padding))
+}
+
+/// 同 `format_single_title_raw`，并追加输入里缓存 token 的拆分，
+/// 如 `Today cc 12,345($0.45) [cache read 10,000 / write 1,000]`。
+pub fn format_single_title_raw_with_cache(
+	period: &str,
+	source_abbr: &str,
+	totals: UsageTotals,
+	show_cost: bool,
+	cost: &CostFormat,
+) -> String {
+	format!(
+		"{} [cache read {} / write {}]",
+		format_single_title_raw(period, source_abbr, totals, show_cost, cost),
+		format_u64_with_commas(totals.cache_read_tokens),
+		format_u64_with_commas(totals.cache_creation_tokens),
+	)
+}
+
+fn format_totals_raw(totals: UsageTotals, show_cost: bool, cost: &CostFormat) -> String {
+	let tokens = format_u64_with_commas(totals.total_tokens);
+	if show_cost {
+		return format!("{tokens}({})", cost.format(totals.cost_usd));
+	}
+	tokens
+}
+
+/// 菜单里“按模型”的行文案：按成本（无成本时按 token）从高到低排序，最多 `cap` 行，
+/// 其余模型合并成一行“…及其他 N 个模型”并展示合计值。`cap = 0` 表示不折叠。
+pub fn format_model_menu_rows(
+	breakdown: &HashMap<String, UsageTotals>,
+	cap: usize,
+	show_cost: bool,
+	cost: &CostFormat,
+) -> Vec<String> {
+	let mut models: Vec<(&String, &UsageTotals)> = breakdown.iter().collect();
+	models.sort_by(|(a_model, a), (b_model, b)| {
+		let by_cost = if show_cost {
+			b.cost_usd.total_cmp(&a.cost_usd)
+		} else {
+			std::cmp::Ordering::Equal
+		};
+		by_cost
+			.then(b.total_tokens.cmp(&a.total_tokens))
+			.then(a_model.cmp(b_model))
+	});
+
+	let shown = if cap == 0 { models.len() } else { cap.min(models.len()) };
+	let mut rows: Vec<String> = models[..shown]
+		.iter()
+		.map(|(model, totals)| {
+			let assumed = if totals.is_estimated { " (assumed)" } else { "" };
+			format!("{model}{assumed} {}", format_totals_raw(**totals, show_cost, cost))
+		})
+		.collect();
+
+	let rest = &models[shown..];
+	if !rest.is_empty() {
+		let mut rest_totals = UsageTotals::default();
+		for (_, totals) in rest {
+			rest_totals += **totals;
+		}
+		rows.push(format!(
+			"…及其他 {} 个模型 {}",
+			rest.len(),
+			format_totals_raw(rest_totals, show_cost, cost)
+		));
+	}
+	rows
+}
+
+/// 相对上期的变化百分比；上期为 0 时没有意义，返回 None。
+pub fn percent_change(previous: f64, current: f64) -> Option<f64> {
+	(previous.is_finite() && previous > 0.0).then(|| (current - previous) / previous * 100.0)
+}
+
+/// 菜单里的本月/上月对比：`本月 vs 上月: +12.5% ($1.20 → $1.35)`。
+///
+/// 说明：有价格时比较成本，否则比较 token 数；上月为 0 时百分比显示 `n/a`。
+pub fn format_month_comparison(
+	previous: UsageTotals,
+	current: UsageTotals,
+	show_cost: bool,
+	cost: &CostFormat,
+) -> String {
+	let (change, detail) = if show_cost {
+		(
+			percent_change(previous.cost_usd, current.cost_usd),
+			format!(
+				"{} → {}",
+				cost.format(previous.cost_usd),
+				cost.format(current.cost_usd)
+			),
 		)
 	} else {
-		format!("cx {tokens}", tokens = format_u64_with_commas(cx.total_tokens))
-	};
-	let cc_line = if show_cost {
-		format!(
-			"cc {tokens}({cost})",
-			tokens = format_u64_with_commas(cc.total_tokens),
-			cost = format!("${:.2}", cc.cost_usd)
+		(
+			percent_change(previous.total_tokens as f64, current.total_tokens as f64),
+			format!(
+				"{} → {}",
+				format_u64_with_commas(previous.total_tokens),
+				format_u64_with_commas(current.total_tokens)
+			),
 		)
-	} else {
-		format!("cc {tokens}", tokens = format_u64_with_commas(cc.total_tokens))
 	};
-	format!("{left}\t{cx_line}\n\t{cc_line}")
+	let change = change.map_or_else(|| "n/a".to_string(), |percent| format!("{percent:+.1}%"));
+	format!("本月 vs 上月: {change} ({detail})")
 }
 
 #[cfg(test)]
@@ -69,31 +244,55 @@ mod tests {
 
 	#[test]
 	fn raw_single_title_prints_full_tokens() {
+		let cost = CostFormat::default();
 		let title = format_single_title_raw(
 			"Today",
 			"cx",
 			UsageTotals {
 				total_tokens: 12345,
 				cost_usd: 0.45,
+				..Default::default()
 			},
 			true,
+			&cost,
 		);
 		assert_eq!(title, "Today cx 12,345($0.45)");
 	}
 
+	#[test]
+	fn raw_title_with_cache_shows_read_and_write_split() {
+		let totals = UsageTotals {
+			total_tokens: 12_345,
+			cost_usd: 0.45,
+			cache_read_tokens: 10_000,
+			cache_creation_tokens: 1_000,
+			..Default::default()
+		};
+		let cost = CostFormat::default();
+		assert_eq!(
+			format_single_title_raw_with_cache("Today", "cc", totals, true, &cost),
+			"Today cc 12,345($0.45) [cache read 10,000 / write 1,000]"
+		);
+	}
+
 	#[test]
 	fn raw_both_title_prints_two_lines() {
+		let cost = CostFormat::default();
 		let title = format_both_title_raw(
 			"Today",
+			SourceLabels::default(),
 			UsageTotals {
 				total_tokens: 123,
 				cost_usd: 0.01,
+				..Default::default()
 			},
 			UsageTotals {
 				total_tokens: 456,
 				cost_usd: 0.02,
+				..Default::default()
 			},
 			true,
+			&cost,
 		);
 		assert!(title.contains("Today |"));
 		assert!(title.contains('\n'));
@@ -101,17 +300,160 @@ mod tests {
 		assert!(title.contains("cc 456($0.02)"));
 	}
 
+	#[test]
+	fn both_menu_line_keeps_cc_column_aligned() {
+		let totals = |total_tokens: u64, cost_usd: f64| UsageTotals {
+			total_tokens,
+			cost_usd,
+			..Default::default()
+		};
+		let cost = CostFormat::default();
+		let narrow = format_both_menu_line(
+			"Today",
+			SourceLabels::default(),
+			totals(1_234, 0.01),
+			totals(456, 0.02),
+			true,
+			&cost,
+		);
+		let wide = format_both_menu_line(
+			"Today",
+			SourceLabels::default(),
+			totals(123_456_789, 1_234.5),
+			totals(7, 0.0),
+			true,
+			&cost,
+		);
+		assert!(narrow.starts_with("Today | cx 1,234($0.01) "));
+		assert!(narrow.ends_with("cc 456($0.02)"));
+		assert!(!narrow.contains('\n'));
+		assert_eq!(narrow.find("cc "), wide.find("cc "));
+
+		// 超过列宽时仍至少用两个空格分隔。
+		let overflow = format_both_menu_line(
+			"Today",
+			SourceLabels {
+				cx: "Codex-long",
+				..Default::default()
+			},
+			totals(123_456_789_000, 99_999.99),
+			totals(1, 0.0),
+			true,
+			&cost,
+		);
+		assert!(overflow.contains("($99999.99)  cc 1($0.00)"));
+	}
+
+	#[test]
+	fn raw_cost_marks_sub_cent_amounts_like_the_tray() {
+		let cost = CostFormat::default();
+		assert_eq!(cost.format(0.0), "$0.00");
+		assert_eq!(cost.format(0.004), "<$0.01");
+		assert_eq!(cost.format(0.006), "$0.01");
+		assert_eq!(cost.format(1.5), "$1.50");
+
+		let eur = CostFormat {
+			currency: DisplayCurrency {
+				code: "EUR".to_string(),
+				symbol: "€".to_string(),
+				usd_rate: 0.5,
+			},
+			..CostFormat::default()
+		};
+		assert_eq!(eur.format(3.0), "€1.50");
+		assert_eq!(eur.format(0.006), "<€0.01");
+
+		let four = CostFormat {
+			decimals: 4,
+			..CostFormat::default()
+		};
+		assert_eq!(four.format(0.0042), "$0.0042");
+		assert_eq!(four.format(0.000042), "<$0.0001");
+	}
+
 	#[test]
 	fn comma_formatter_handles_large_numbers() {
+		let cost = CostFormat::default();
 		let title = format_single_title_raw(
 			"Today",
 			"cx",
 			UsageTotals {
 				total_tokens: 113_577_339,
 				cost_usd: 0.0,
+				..Default::default()
 			},
 			true,
+			&cost,
 		);
 		assert_eq!(title, "Today cx 113,577,339($0.00)");
 	}
+
+	#[test]
+	fn model_rows_collapse_beyond_cap() {
+		let breakdown: HashMap<String, UsageTotals> = (1..=10u64)
+			.map(|i| {
+				(
+					format!("model-{i:02}"),
+					UsageTotals {
+						total_tokens: i * 100,
+						cost_usd: i as f64,
+						..Default::default()
+					},
+				)
+			})
+			.collect();
+
+		let cost = CostFormat::default();
+		let rows = format_model_menu_rows(&breakdown, 3, true, &cost);
+		assert_eq!(
+			rows,
+			vec![
+				"model-10 1,000($10.00)".to_string(),
+				"model-09 900($9.00)".to_string(),
+				"model-08 800($8.00)".to_string(),
+				"…及其他 7 个模型 2,800($28.00)".to_string(),
+			]
+		);
+		assert_eq!(format_model_menu_rows(&breakdown, 0, true, &cost).len(), 10);
+	}
+
+	#[test]
+	fn model_rows_mark_estimated_models() {
+		let breakdown = HashMap::from([(
+			"gpt-5".to_string(),
+			UsageTotals {
+				total_tokens: 1_000,
+				cost_usd: 1.0,
+				is_estimated: true,
+				..Default::default()
+			},
+		)]);
+		let cost = CostFormat::default();
+		assert_eq!(
+			format_model_menu_rows(&breakdown, 0, true, &cost),
+			vec!["gpt-5 (assumed) 1,000($1.00)".to_string()]
+		);
+	}
+
+	#[test]
+	fn month_comparison_shows_change_and_guards_zero() {
+		let totals = |total_tokens: u64, cost_usd: f64| UsageTotals {
+			total_tokens,
+			cost_usd,
+			..UsageTotals::default()
+		};
+		let cost = CostFormat::default();
+		assert_eq!(
+			format_month_comparison(totals(1_000, 1.2), totals(1_500, 1.35), true, &cost),
+			"本月 vs 上月: +12.5% ($1.20 → $1.35)"
+		);
+		assert_eq!(
+			format_month_comparison(totals(2_000, 0.0), totals(1_500, 0.0), false, &cost),
+			"本月 vs 上月: -25.0% (2,000 → 1,500)"
+		);
+		assert_eq!(
+			format_month_comparison(totals(0, 0.0), totals(1_500, 0.5), true, &cost),
+			"本月 vs 上月: n/a ($0.00 → $0.50)"
+		);
+	}
 }
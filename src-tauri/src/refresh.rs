@@ -0,0 +1,234 @@
+//! 托盘刷新循环的节奏控制与日志目录监听（循环本体在 `app.rs`）。
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// 单次刷新超时后，循环至少要休眠这么久再开始下一轮，避免冷启动/超大历史时一直在扫描。
+pub(crate) const MIN_REFRESH_SLEEP: Duration = Duration::from_secs(5);
+
+/// 根据本轮刷新耗时决定下一次刷新前要休眠多久。
+///
+/// 说明：
+/// - 正常情况下按固定节奏刷新：休眠 `interval - elapsed`；
+/// - 刷新耗时超过（或接近）间隔时，至少休眠 `MIN_REFRESH_SLEEP`，让出 CPU 与磁盘。
+pub(crate) fn sleep_after_refresh(elapsed: Duration, interval: Duration) -> Duration {
+	interval.saturating_sub(elapsed).max(MIN_REFRESH_SLEEP)
+}
+
+/// 本轮刷新是否已经超过刷新间隔（调用方据此输出告警）。
+pub(crate) fn refresh_overran(elapsed: Duration, interval: Duration) -> bool {
+	elapsed > interval
+}
+
+/// 单飞执行：同一时刻只有一个调用在干活，运行期间到来的请求合并成一次尾随执行。
+///
+/// 说明：请求先写进 `pending`（后到的覆盖先到的，即“最后一次请求为准”），再尝试抢占运行权；
+/// 抢不到的调用直接返回，由正在运行的调用在本轮结束后接着处理。
+pub(crate) struct SingleFlight<T> {
+	pending: Mutex<Option<T>>,
+	running: AtomicBool,
+}
+
+/// 交还运行权；放在 guard 里是为了让干活时 panic 也能释放，否则之后的请求只会排队、再没人执行。
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+	fn drop(&mut self) {
+		self.0.store(false, Ordering::SeqCst);
+	}
+}
+
+impl<T> SingleFlight<T> {
+	pub(crate) const fn new() -> Self {
+		Self {
+			pending: Mutex::new(None),
+			running: AtomicBool::new(false),
+		}
+	}
+
+	fn pending(&self) -> MutexGuard<'_, Option<T>> {
+		self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+	}
+
+	pub(crate) fn run(&self, request: T, mut work: impl FnMut(T)) {
+		*self.pending() = Some(request);
+		loop {
+			if self
+				.running
+				.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+				.is_err()
+			{
+				return;
+			}
+			let running = RunningGuard(&self.running);
+			// 不用 `while let`：那样干活期间会一直持有 pending 锁，新请求都得等着。
+			loop {
+				let Some(next) = self.pending().take() else {
+					break;
+				};
+				work(next);
+			}
+			drop(running);
+			// 释放运行权前后可能刚有请求写入但没抢到运行权：再看一眼，有就接着跑。
+			if self.pending().is_none() {
+				return;
+			}
+		}
+	}
+}
+
+/// 日志变更后最多等这么久再刷新：同一批写入（一次对话通常连写多行）合并成一次刷新。
+pub(crate) const WATCH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// 事件是否改动了参与统计的日志文件（`is_usage_log` 由调用方按扫描规则给出）。
+fn is_usage_log_event(event: &Event, is_usage_log: &impl Fn(&Path) -> bool) -> bool {
+	!matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|p| is_usage_log(p))
+}
+
+/// 消费监听事件：收到第一条日志变更后再收集 `debounce` 时长内的后续事件，然后调用一次 `on_change`。
+///
+/// 说明：用“首条事件后固定等待”而不是“安静后才触发”，否则会话持续写日志时会一直推迟刷新。
+/// 发送端关闭（监听器被释放）后返回；还没触发的变更会先补一次。
+pub(crate) fn run_debounced(
+	events: Receiver<notify::Result<Event>>,
+	debounce: Duration,
+	is_usage_log: impl Fn(&Path) -> bool,
+	mut on_change: impl FnMut(),
+) {
+	loop {
+		match events.recv() {
+			Ok(Ok(event)) if is_usage_log_event(&event, &is_usage_log) => {}
+			Ok(_) => continue,
+			Err(_) => return,
+		}
+		let deadline = Instant::now() + debounce;
+		loop {
+			match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+				Ok(_) => continue,
+				Err(RecvTimeoutError::Timeout) => break,
+				Err(RecvTimeoutError::Disconnected) => {
+					on_change();
+					return;
+				}
+			}
+		}
+		on_change();
+	}
+}
+
+/// 递归监听各日志目录，`is_usage_log` 认可的日志文件变更（去抖后）时在后台线程调用 `on_change`。
+///
+/// 返回的监听器需要一直持有（释放即停止监听）；一个目录都监听不了时返回 `None`，调用方退回定时刷新。
+pub(crate) fn watch_usage_dirs(
+	dirs: &[PathBuf],
+	is_usage_log: impl Fn(&Path) -> bool + Send + 'static,
+	on_change: impl FnMut() + Send + 'static,
+) -> Option<RecommendedWatcher> {
+	let (tx, rx) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(tx).ok()?;
+	let watched = dirs
+		.iter()
+		.filter(|dir| watcher.watch(dir, RecursiveMode::Recursive).is_ok())
+		.count();
+	if watched == 0 {
+		return None;
+	}
+	std::thread::spawn(move || run_debounced(rx, WATCH_DEBOUNCE, is_usage_log, on_change));
+	Some(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slow_refresh_still_sleeps_at_least_the_floor() {
+		let interval = Duration::from_secs(30);
+
+		assert_eq!(sleep_after_refresh(Duration::from_secs(2), interval), Duration::from_secs(28));
+		assert_eq!(sleep_after_refresh(Duration::from_secs(28), interval), MIN_REFRESH_SLEEP);
+		assert_eq!(sleep_after_refresh(Duration::from_secs(90), interval), MIN_REFRESH_SLEEP);
+
+		assert!(!refresh_overran(Duration::from_secs(30), interval));
+		assert!(refresh_overran(Duration::from_secs(31), interval));
+	}
+
+	#[test]
+	fn single_flight_coalesces_overlapping_requests_and_keeps_the_last() {
+		use std::sync::Arc;
+
+		let flight = Arc::new(SingleFlight::new());
+		let (started_tx, started_rx) = mpsc::channel();
+		let (release_tx, release_rx) = mpsc::channel::<()>();
+		let ran = Arc::new(Mutex::new(Vec::new()));
+
+		let runner = {
+			let flight = Arc::clone(&flight);
+			let ran = Arc::clone(&ran);
+			std::thread::spawn(move || {
+				flight.run(1, |value| {
+					if value == 1 {
+						started_tx.send(()).expect("started");
+						release_rx.recv().expect("release");
+					}
+					ran.lock().expect("ran").push(value);
+				})
+			})
+		};
+		started_rx.recv().expect("first run started");
+		// 第一轮还在跑：这些请求只会排队，最后一次（4）生效。
+		for value in [2, 3, 4] {
+			flight.run(value, |_| panic!("only the running caller should do work"));
+		}
+		release_tx.send(()).expect("release");
+		runner.join().expect("runner");
+
+		assert_eq!(*ran.lock().expect("ran"), vec![1, 4]);
+	}
+
+	#[test]
+	fn single_flight_recovers_after_a_panicking_run() {
+		let flight = SingleFlight::new();
+		let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			flight.run(1, |_| panic!("refresh failed"));
+		}));
+		assert!(panicked.is_err());
+
+		let mut ran = Vec::new();
+		flight.run(2, |value| ran.push(value));
+		assert_eq!(ran, vec![2]);
+	}
+
+	fn modify_event(path: &str) -> notify::Result<Event> {
+		let kind = EventKind::Modify(notify::event::ModifyKind::Any);
+		Ok(Event::new(kind).add_path(PathBuf::from(path)))
+	}
+
+	#[test]
+	fn log_changes_are_coalesced_into_one_refresh() {
+		let is_usage_log = |path: &Path| crate::codex::is_session_file(path);
+		let (tx, rx) = mpsc::channel();
+		tx.send(modify_event("/p/a/session.jsonl")).expect("send");
+		tx.send(modify_event("/p/a/session.jsonl")).expect("send");
+		tx.send(modify_event("/p/b/session.jsonl")).expect("send");
+		drop(tx);
+		let mut calls = 0;
+		run_debounced(rx, Duration::from_millis(20), is_usage_log, || calls += 1);
+		assert_eq!(calls, 1);
+
+		let (tx, rx) = mpsc::channel();
+		tx.send(modify_event("/p/a/notes.txt")).expect("send");
+		let access = Event::new(EventKind::Access(notify::event::AccessKind::Any))
+			.add_path(PathBuf::from("/p/a/session.jsonl"));
+		tx.send(Ok(access)).expect("send");
+		drop(tx);
+		let mut calls = 0;
+		run_debounced(rx, Duration::from_millis(20), is_usage_log, || calls += 1);
+		assert_eq!(calls, 0);
+	}
+}
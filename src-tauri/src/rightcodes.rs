@@ -1,5 +1,10 @@
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
 use serde_json::Value;
 
+use crate::format;
+
 /// Right.codes 展示用的最小摘要（仅满足 tokbar 需求）。
 #[derive(Debug, Clone, PartialEq)]
 pub struct RcSummary {
@@ -7,6 +12,27 @@ pub struct RcSummary {
 	pub title_part: String,
 	/// 菜单里展示的状态文案（不含任何敏感信息）。
 	pub menu_status: String,
+	/// 已用额度占总额度的百分比；总额度不是正数时为 None（无法判断预算等级）。
+	pub used_percent: Option<f64>,
+}
+
+/// 一次刷新里 Right.codes 的展示结果。
+#[derive(Debug, Clone, PartialEq)]
+pub struct RightcodesUi {
+	/// 状态栏片段；拉取失败/未登录时为 None。
+	pub title_part: Option<String>,
+	pub menu_text: String,
+	/// 是否已保存 token（空状态判断用，避免每次刷新再读一遍钥匙串）。
+	pub logged_in: bool,
+}
+
+impl RightcodesUi {
+	/// 拼出托盘标题与 rc 菜单文案：`rc_in_title = false` 只把 rc 片段从标题里去掉，菜单文案照常给出。
+	pub fn title_and_menu(self, base_title: String, rc_in_title: bool) -> (String, String) {
+		let title =
+			format::append_rc_title_part(base_title, self.title_part.as_deref(), rc_in_title);
+		(title, self.menu_text)
+	}
 }
 
 /// 从 `/auth/login` 响应中提取 token（兼容 `user_token` / `userToken` 变体）。
@@ -31,12 +57,19 @@ pub fn extract_user_token(payload: &Value) -> Option<String> {
 	None
 }
 
+/// 额度金额默认保留的小数位数（与 Python 面板一致）。
+pub const DEFAULT_QUOTA_DECIMALS: usize = 5;
+
 /// 从 `/subscriptions/list` 响应中抽取“一个套餐包”的额度与 reset 状态，生成 tokbar 所需的展示摘要。
 ///
 /// 约束：
 /// - 当前按“用户只购买一个套餐包”的前提处理：从数组中挑选第一个可计算的包。
 /// - 若无法计算（字段缺失/类型不对），返回 None；上层应当“状态栏不显示 rc”，只在菜单里提示失败原因。
-pub fn summarize_single_subscription(payload: &Value) -> Option<RcSummary> {
+/// - 状态栏片段的金额按 `title_decimals` 位小数展示；菜单文案始终保留完整精度，便于与面板核对。
+pub fn summarize_single_subscription(
+	payload: &Value,
+	title_decimals: usize,
+) -> Option<RcSummary> {
 	let subs = payload
 		.as_object()?
 		.get("subscriptions")?
@@ -51,17 +84,73 @@ pub fn summarize_single_subscription(payload: &Value) -> Option<RcSummary> {
 		let remaining = obj.get("remaining_quota").and_then(_to_f64)?;
 		let used = (total - remaining).max(0.0);
 		let reset_today = obj.get("reset_today").and_then(|v| v.as_bool()).unwrap_or(false);
+		return Some(build_summary(used, total, reset_today, title_decimals));
+	}
 
-		let used_text = fmt_money_quota(used);
-		let total_text = fmt_money_quota(total);
-		let reset_text = if reset_today { "R" } else { "NR" };
+	None
+}
 
-		let title_part = format!("rc {used}/{total} {reset}", used = used_text, total = total_text, reset = reset_text);
-		let menu_status = format!("rc：{used}/{total} {reset}", used = used_text, total = total_text, reset = reset_text);
-		return Some(RcSummary { title_part, menu_status });
+fn build_summary(used: f64, total: f64, reset_today: bool, title_decimals: usize) -> RcSummary {
+	let reset_text = if reset_today { "R" } else { "NR" };
+	let title_part = format!(
+		"rc {used}/{total} {reset}",
+		used = fmt_money_quota_with_decimals(used, title_decimals),
+		total = fmt_money_quota_with_decimals(total, title_decimals),
+		reset = reset_text
+	);
+	let menu_status = format!(
+		"rc：{used}/{total} {reset}",
+		used = fmt_money_quota(used),
+		total = fmt_money_quota(total),
+		reset = reset_text
+	);
+	let used_percent = (total > 0.0).then(|| used / total * 100.0);
+	RcSummary {
+		title_part,
+		menu_status,
+		used_percent,
 	}
+}
 
-	None
+/// 所有可计算的套餐包：`(已用, 总额, 今天是否已 reset)`；字段缺失/类型不对的条目直接跳过。
+fn usable_subscriptions(payload: &Value) -> Vec<(f64, f64, bool)> {
+	let Some(subs) = payload.get("subscriptions").and_then(Value::as_array) else {
+		return Vec::new();
+	};
+	subs.iter()
+		.filter_map(|item| {
+			let obj = item.as_object()?;
+			let total = obj.get("total_quota").and_then(_to_f64)?;
+			let remaining = obj.get("remaining_quota").and_then(_to_f64)?;
+			let reset_today = obj.get("reset_today").and_then(|v| v.as_bool()).unwrap_or(false);
+			Some(((total - remaining).max(0.0), total, reset_today))
+		})
+		.collect()
+}
+
+/// 每个可计算的套餐包各自的摘要（金额保留完整精度）。
+pub fn summarize_all_subscriptions(payload: &Value) -> Vec<RcSummary> {
+	usable_subscriptions(payload)
+		.into_iter()
+		.map(|(used, total, reset_today)| {
+			build_summary(used, total, reset_today, DEFAULT_QUOTA_DECIMALS)
+		})
+		.collect()
+}
+
+/// 多个套餐包合并后的摘要：已用/总额分别求和；任一套餐今天已 reset 即显示 `R`。
+pub fn summarize_combined_subscriptions(
+	payload: &Value,
+	title_decimals: usize,
+) -> Option<RcSummary> {
+	let subs = usable_subscriptions(payload);
+	if subs.is_empty() {
+		return None;
+	}
+	let used = subs.iter().map(|(used, _, _)| used).sum();
+	let total = subs.iter().map(|(_, total, _)| total).sum();
+	let reset_today = subs.iter().any(|(_, _, reset)| *reset);
+	Some(build_summary(used, total, reset_today, title_decimals))
 }
 
 fn _to_f64(v: &Value) -> Option<f64> {
@@ -82,11 +171,16 @@ fn _to_f64(v: &Value) -> Option<f64> {
 /// - 统一带 `$`，并使用千分位分隔
 pub fn fmt_money_quota(value: f64) -> String {
 	// 说明：额度展示更偏“面板读数”，与成本/余额不同；这里严格仿照 Python 侧实现以便用户核对。
+	fmt_money_quota_with_decimals(value, DEFAULT_QUOTA_DECIMALS)
+}
+
+/// 同 [`fmt_money_quota`]，但非整数保留 `decimals` 位小数。
+pub fn fmt_money_quota_with_decimals(value: f64, decimals: usize) -> String {
 	let rounded = value.round();
 	if (value - rounded).abs() < 1e-9 {
 		return format!("${}", format_int_with_commas(rounded as i64));
 	}
-	format!("${}", format_f64_with_commas(value, 5))
+	format!("${}", format_f64_with_commas(value, decimals))
 }
 
 fn format_int_with_commas(value: i64) -> String {
@@ -122,11 +216,89 @@ fn format_f64_with_commas(value: f64, decimals: usize) -> String {
 	}
 }
 
+/// rc 请求的退避闸门：触发限流后在 `Retry-After` 到期前不再请求，期间沿用上一次的菜单文案。
+///
+/// 说明：菜单“rc：重试”通过 [`RcBackoff::request_retry`] 放行一次，之后恢复正常的闸门判断。
+#[derive(Debug, Default)]
+pub struct RcBackoff {
+	next_attempt_at: Option<Instant>,
+	bypass_once: bool,
+	last_failed: bool,
+	last_menu_text: Option<String>,
+}
+
+impl RcBackoff {
+	/// 本轮是否应该访问 rc 接口（放行一次的标记在这里被消费掉）。
+	pub fn should_attempt(&mut self, now: Instant) -> bool {
+		if std::mem::take(&mut self.bypass_once) {
+			return true;
+		}
+		self.next_attempt_at.is_none_or(|at| now >= at)
+	}
+
+	pub fn record_success(&mut self) {
+		self.next_attempt_at = None;
+		self.last_failed = false;
+		self.last_menu_text = None;
+	}
+
+	/// 记录一次失败；`backoff` 为 None 时下一轮照常重试。
+	pub fn record_failure(&mut self, now: Instant, backoff: Option<Duration>, menu_text: String) {
+		self.next_attempt_at = backoff.map(|d| now + d);
+		self.last_failed = true;
+		self.last_menu_text = Some(menu_text);
+	}
+
+	/// 跳过退避、让下一次刷新立即请求一次。
+	pub fn request_retry(&mut self) {
+		self.bypass_once = true;
+	}
+
+	pub fn last_failed(&self) -> bool {
+		self.last_failed
+	}
+
+	pub fn last_menu_text(&self) -> Option<&str> {
+		self.last_menu_text.as_deref()
+	}
+}
+
+static RC_BACKOFF: OnceLock<Mutex<RcBackoff>> = OnceLock::new();
+
+pub fn rc_backoff() -> &'static Mutex<RcBackoff> {
+	RC_BACKOFF.get_or_init(|| Mutex::new(RcBackoff::default()))
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 	use serde_json::json;
 
+	#[test]
+	fn retry_bypasses_backoff_for_exactly_one_attempt() {
+		let now = Instant::now();
+		let mut gate = RcBackoff::default();
+		assert!(gate.should_attempt(now));
+
+		gate.record_failure(
+			now,
+			Some(Duration::from_secs(60)),
+			"rc：触发限流（429），请 60s 后重试".to_string(),
+		);
+		assert!(gate.last_failed());
+		assert!(!gate.should_attempt(now + Duration::from_secs(1)));
+
+		gate.request_retry();
+		assert!(gate.should_attempt(now + Duration::from_secs(2)));
+		// 放行只生效一次：仍在退避期内的下一轮不会再请求。
+		assert!(!gate.should_attempt(now + Duration::from_secs(3)));
+		assert!(gate.should_attempt(now + Duration::from_secs(61)));
+
+		gate.record_success();
+		assert!(!gate.last_failed());
+		assert!(gate.last_menu_text().is_none());
+	}
+
 	#[test]
 	fn extract_user_token_accepts_user_token_and_user_token_camel() {
 		let a = json!({"user_token":"abc"});
@@ -150,9 +322,77 @@ mod tests {
 				{"total_quota": 20, "remaining_quota": 10, "reset_today": true}
 			]
 		});
-		let s = summarize_single_subscription(&payload).expect("should summarize");
+		let s = summarize_single_subscription(&payload, DEFAULT_QUOTA_DECIMALS).expect("should summarize");
 		assert_eq!(s.title_part, "rc $10/$20 R".to_string());
 		assert_eq!(s.menu_status, "rc：$10/$20 R".to_string());
+		assert_eq!(s.used_percent, Some(50.0));
+	}
+
+	#[test]
+	fn rc_in_title_off_drops_the_title_part_but_keeps_the_menu_text() {
+		let payload = json!({
+			"subscriptions": [
+				{"total_quota": 20, "remaining_quota": 10, "reset_today": true}
+			]
+		});
+		let summary = summarize_single_subscription(&payload, DEFAULT_QUOTA_DECIMALS)
+			.expect("should summarize");
+		let ui = RightcodesUi {
+			title_part: Some(summary.title_part),
+			menu_text: summary.menu_status,
+			logged_in: true,
+		};
+
+		let (title, menu_text) = ui.clone().title_and_menu("Today cx 1.0k".to_string(), false);
+		assert_eq!(title, "Today cx 1.0k");
+		assert_eq!(menu_text, "rc：$10/$20 R");
+
+		let (title, menu_text) = ui.title_and_menu("Today cx 1.0k".to_string(), true);
+		assert_eq!(title, "Today cx 1.0k rc $10/$20 R");
+		assert_eq!(menu_text, "rc：$10/$20 R");
+	}
+
+	#[test]
+	fn title_part_honors_precision_while_menu_keeps_full_precision() {
+		let payload = json!({
+			"subscriptions": [
+				{"total_quota": 20, "remaining_quota": 18.765432, "reset_today": false}
+			]
+		});
+		let s = summarize_single_subscription(&payload, 2).expect("should summarize");
+		assert_eq!(s.title_part, "rc $1.23/$20 NR".to_string());
+		assert_eq!(s.menu_status, "rc：$1.23457/$20 NR".to_string());
+	}
+
+	#[test]
+	fn multiple_subscriptions_are_listed_and_combined() {
+		let payload = json!({
+			"subscriptions": [
+				{"total_quota": 20, "remaining_quota": 10, "reset_today": false},
+				{"tier_id": "broken"},
+				{"total_quota": "30", "remaining_quota": 10, "reset_today": true}
+			]
+		});
+
+		let all = summarize_all_subscriptions(&payload);
+		let titles: Vec<&str> = all.iter().map(|s| s.title_part.as_str()).collect();
+		assert_eq!(titles, vec!["rc $10/$20 NR", "rc $20/$30 R"]);
+
+		let combined =
+			summarize_combined_subscriptions(&payload, DEFAULT_QUOTA_DECIMALS).expect("combined");
+		assert_eq!(combined.title_part, "rc $30/$50 R");
+		assert_eq!(combined.menu_status, "rc：$30/$50 R");
+
+		let none_reset = json!({
+			"subscriptions": [
+				{"total_quota": 20, "remaining_quota": 10},
+				{"total_quota": 5, "remaining_quota": 5, "reset_today": false}
+			]
+		});
+		let combined =
+			summarize_combined_subscriptions(&none_reset, DEFAULT_QUOTA_DECIMALS).expect("combined");
+		assert_eq!(combined.title_part, "rc $10/$25 NR");
+		assert_eq!(summarize_combined_subscriptions(&json!({}), 2), None);
 	}
 
 	#[test]
@@ -163,7 +403,7 @@ mod tests {
 				{"tier_id":"x"}
 			]
 		});
-		assert_eq!(summarize_single_subscription(&payload), None);
+		assert_eq!(summarize_single_subscription(&payload, DEFAULT_QUOTA_DECIMALS), None);
 	}
 }
 
@@ -1,7 +1,12 @@
-use std::time::Duration;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use serde_json::{json, Value};
 
+use crate::cache_lock;
 use crate::rightcodes::extract_user_token;
 
 /// Right.codes API 访问错误（只包含可展示信息，不包含任何敏感数据）。
@@ -36,6 +41,16 @@ impl RightcodesApiError {
 			RightcodesApiError::BadPayload => "rc：接口返回异常（无法解析）".to_string(),
 		}
 	}
+
+	/// 失败后应暂停请求多久：只有限流需要退避（没给 Retry-After 时默认 60s），其余错误下一轮照常重试。
+	pub fn backoff(&self) -> Option<Duration> {
+		match self {
+			RightcodesApiError::RateLimited { retry_after_seconds } => {
+				Some(Duration::from_secs(retry_after_seconds.unwrap_or(60)))
+			}
+			_ => None,
+		}
+	}
 }
 
 /// Right.codes 最小 HTTP 客户端（仅满足 tokbar：login + subscriptions/list）。
@@ -96,6 +111,65 @@ impl RightcodesApiClient {
 	}
 }
 
+/// 套餐列表的缓存时长：托盘每 30 秒刷新一次，没必要每次都请求接口。
+const SUBSCRIPTIONS_TTL: Duration = Duration::from_secs(60);
+
+/// 最近一次成功的 `/subscriptions/list` 结果。只记录 token 的哈希，用来在切换账号后让缓存失效。
+#[derive(Debug, Default)]
+struct SubscriptionsCache {
+	entry: Option<(u64, Instant, Value)>,
+}
+
+static SUBSCRIPTIONS_CACHE: OnceLock<Mutex<SubscriptionsCache>> = OnceLock::new();
+
+fn subscriptions_cache() -> &'static Mutex<SubscriptionsCache> {
+	SUBSCRIPTIONS_CACHE.get_or_init(|| Mutex::new(SubscriptionsCache::default()))
+}
+
+fn token_fingerprint(token: &str) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	token.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// TTL 内直接返回缓存；否则调用 `fetch` 并只缓存成功结果（失败由上层的退避闸门处理）。
+fn cached_or_fetch(
+	cache: &Mutex<SubscriptionsCache>,
+	token: &str,
+	now: Instant,
+	fetch: impl FnOnce() -> Result<Value, RightcodesApiError>,
+) -> Result<Value, RightcodesApiError> {
+	let fingerprint = token_fingerprint(token);
+	{
+		let guard = cache_lock::lock_or_reset(cache);
+		if let Some((cached_for, at, value)) = guard.entry.as_ref() {
+			if *cached_for == fingerprint && now.duration_since(*at) < SUBSCRIPTIONS_TTL {
+				return Ok(value.clone());
+			}
+		}
+	}
+
+	let value = fetch()?;
+	let mut guard = cache_lock::lock_or_reset(cache);
+	guard.entry = Some((fingerprint, now, value.clone()));
+	Ok(value)
+}
+
+/// 带 60 秒缓存的套餐列表（托盘刷新用）。
+pub fn list_subscriptions_cached(
+	client: &RightcodesApiClient,
+	token: &str,
+) -> Result<Value, RightcodesApiError> {
+	cached_or_fetch(subscriptions_cache(), token, Instant::now(), || {
+		client.list_subscriptions(token)
+	})
+}
+
+/// 丢弃缓存的套餐列表（重新登录后让新数据立即生效）。
+pub fn invalidate_subscriptions_cache() {
+	cache_lock::lock_or_reset(subscriptions_cache()).entry = None;
+}
+
 fn parse_json_or_map_error(resp: Result<ureq::Response, ureq::Error>) -> Result<Value, RightcodesApiError> {
 	match resp {
 		Ok(r) => r
@@ -119,12 +193,23 @@ fn parse_json_or_map_error(resp: Result<ureq::Response, ureq::Error>) -> Result<
 }
 
 fn parse_retry_after_seconds(resp: &ureq::Response) -> Option<u64> {
-	// 说明：我们只解析“秒数”这种常见形态；HTTP-date 解析在 tokbar 场景收益不高，先不做。
-	let raw = resp.header("Retry-After")?.trim();
+	parse_retry_after_value(resp.header("Retry-After")?, Utc::now())
+}
+
+/// 解析 `Retry-After` 的两种形态：秒数（快速路径），或 HTTP-date（如 `Wed, 21 Oct 2026 07:28:00 GMT`）。
+///
+/// 说明：HTTP-date 按与 `now` 的差值换算成秒；已经过去的时间点返回 0（即可以立即重试）。
+fn parse_retry_after_value(raw: &str, now: DateTime<Utc>) -> Option<u64> {
+	let raw = raw.trim();
 	if raw.is_empty() {
 		return None;
 	}
-	raw.parse::<u64>().ok()
+	if let Ok(seconds) = raw.parse::<u64>() {
+		return Some(seconds);
+	}
+	let at = DateTime::parse_from_rfc2822(raw).ok()?;
+	let delta = at.with_timezone(&Utc).signed_duration_since(now).num_seconds();
+	Some(delta.max(0) as u64)
 }
 
 #[cfg(test)]
@@ -139,6 +224,50 @@ mod tests {
 		assert_eq!(e.to_menu_text(), "rc：触发限流（429），请 12s 后重试".to_string());
 	}
 
+	#[test]
+	fn retry_after_parses_seconds_and_http_date() {
+		let now = DateTime::parse_from_rfc3339("2026-10-21T07:27:00Z")
+			.expect("now")
+			.with_timezone(&Utc);
+		assert_eq!(parse_retry_after_value(" 120 ", now), Some(120));
+		assert_eq!(parse_retry_after_value("Wed, 21 Oct 2026 07:28:00 GMT", now), Some(60));
+		// 已经过去的时间点：可以立即重试。
+		assert_eq!(parse_retry_after_value("Tue, 20 Oct 2026 07:28:00 GMT", now), Some(0));
+		assert_eq!(parse_retry_after_value("soon", now), None);
+		assert_eq!(parse_retry_after_value("", now), None);
+	}
+
+	#[test]
+	fn subscriptions_are_cached_within_ttl_and_per_token() {
+		let cache = Mutex::new(SubscriptionsCache::default());
+		let now = Instant::now();
+		let calls = std::cell::Cell::new(0);
+		let fetch = || {
+			calls.set(calls.get() + 1);
+			Ok(json!({ "subscriptions": [], "n": calls.get() }))
+		};
+
+		let first = cached_or_fetch(&cache, "t1", now, fetch).expect("fetch");
+		let again =
+			cached_or_fetch(&cache, "t1", now + Duration::from_secs(30), fetch).expect("cached");
+		assert_eq!(first, again);
+		assert_eq!(calls.get(), 1);
+
+		// 换了账号（token）或过了 TTL：重新请求。
+		cached_or_fetch(&cache, "t2", now + Duration::from_secs(31), fetch).expect("fetch");
+		assert_eq!(calls.get(), 2);
+		cached_or_fetch(&cache, "t2", now + Duration::from_secs(120), fetch).expect("fetch");
+		assert_eq!(calls.get(), 3);
+
+		// 失败不写入缓存，之前的成功结果也不会被覆盖。
+		let err = cached_or_fetch(&cache, "t3", now + Duration::from_secs(121), || {
+			Err(RightcodesApiError::Network)
+		});
+		assert_eq!(err, Err(RightcodesApiError::Network));
+		let cached_at = cache.lock().expect("lock").entry.as_ref().map(|e| e.1);
+		assert_eq!(cached_at, Some(now + Duration::from_secs(120)));
+	}
+
 	#[test]
 	fn auth_error_formats_menu_text() {
 		assert_eq!(
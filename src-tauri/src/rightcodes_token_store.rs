@@ -13,14 +13,16 @@ use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use crate::config_dir;
+
 /// Right.codes token store（keyring 优先，本地文件兜底）。
 ///
 /// 说明：
 /// - token 属于敏感信息：任何错误字符串/菜单状态都不得包含 token 明文。
 /// - 密码不落盘：本模块只存 token，不接触密码。
 pub struct RightcodesTokenStore {
-	/// 文件兜底路径（默认 `~/.tokbar/rightcodes-token.json`）。
-	file_path: PathBuf,
+	/// 文件兜底路径（默认 `~/.tokbar/rightcodes-token.json`）；找不到数据目录时为 None。
+	file_path: Option<PathBuf>,
 	/// 是否禁用 keyring（用于测试/无 keyring 环境的兜底路径验证）。
 	disable_keyring: bool,
 }
@@ -49,7 +51,7 @@ impl RightcodesTokenStore {
 	#[cfg(test)]
 	fn new_for_test(file_path: PathBuf) -> Self {
 		Self {
-			file_path,
+			file_path: Some(file_path),
 			disable_keyring: true,
 		}
 	}
@@ -61,7 +63,7 @@ impl RightcodesTokenStore {
 				return Some(t);
 			}
 		}
-		load_from_file(&self.file_path)
+		self.file_path.as_deref().and_then(load_from_file)
 	}
 
 	/// 保存 token（优先 keyring；失败则降级写入文件）。
@@ -71,11 +73,36 @@ impl RightcodesTokenStore {
 				return Ok(StoredIn::Keyring);
 			}
 		}
-		save_to_file(&self.file_path, token)?;
+		let Some(file_path) = self.file_path.as_deref() else {
+			return Err("找不到保存 token 的目录（HOME 未设置）".to_string());
+		};
+		save_to_file(file_path, token)?;
 		Ok(StoredIn::File)
 	}
 }
 
+/// 校验手动粘贴的 token：去掉首尾空白；为空或中间带空白/换行（多半是复制错了内容）时拒绝。
+///
+/// 说明：错误信息只描述原因，不回显剪贴板内容。
+pub fn normalize_pasted_token(raw: &str) -> Result<&str, String> {
+	let token = raw.trim();
+	if token.is_empty() {
+		return Err("剪贴板里没有 token".to_string());
+	}
+	if token.chars().any(char::is_whitespace) {
+		return Err("剪贴板内容不像 token（包含空白或换行）".to_string());
+	}
+	Ok(token)
+}
+
+impl RightcodesTokenStore {
+	/// 保存从网页端等渠道拿到、手动粘贴的 token（不经过账号密码登录）。
+	pub fn save_pasted_token(&self, raw: &str) -> Result<StoredIn, String> {
+		let token = normalize_pasted_token(raw)?;
+		self.save_token(token).map_err(|e| format!("保存 token 失败：{e}"))
+	}
+}
+
 impl Default for RightcodesTokenStore {
 	fn default() -> Self {
 		Self::new()
@@ -98,6 +125,35 @@ mod tests {
 		assert_eq!(loaded, "abc".to_string());
 	}
 
+	#[test]
+	fn pasted_token_roundtrips_through_file_store() {
+		let dir = tempfile::tempdir().expect("tempdir");
+		let store = RightcodesTokenStore::new_for_test(dir.path().join("rightcodes-token.json"));
+		// 未保存前：刷新时读不到 token，菜单显示“未登录”。
+		assert_eq!(store.load_token(), None);
+
+		assert_eq!(store.save_pasted_token("  rc-abc123\n"), Ok(StoredIn::File));
+		assert_eq!(store.load_token().as_deref(), Some("rc-abc123"));
+
+		// 无效内容不覆盖已保存的 token，错误里也不带剪贴板内容。
+		let err = store.save_pasted_token("   ").expect_err("empty");
+		assert_eq!(err, "剪贴板里没有 token");
+		let err = store.save_pasted_token("not a token").expect_err("whitespace");
+		assert!(!err.contains("not a token"));
+		assert_eq!(store.load_token().as_deref(), Some("rc-abc123"));
+	}
+
+	#[test]
+	fn save_reports_tokbar_path_that_is_a_file() {
+		let dir = tempfile::tempdir().expect("tempdir");
+		let tokbar = dir.path().join(".tokbar");
+		fs::write(&tokbar, "").expect("write");
+		let store = RightcodesTokenStore::new_for_test(tokbar.join("rightcodes-token.json"));
+
+		let err = store.save_token("abc").expect_err("save should fail");
+		assert_eq!(err, format!("{} exists but is not a directory", tokbar.display()));
+	}
+
 	#[test]
 	fn load_from_file_returns_none_for_missing_or_empty_token() {
 		let dir = tempfile::tempdir().expect("tempdir");
@@ -114,10 +170,10 @@ mod tests {
 	}
 }
 
-fn default_token_path() -> PathBuf {
+fn default_token_path() -> Option<PathBuf> {
 	// 说明：tokbar 现有设置都放在 ~/.tokbar/ 下，Right.codes token 也统一放这里。
-	let home = std::env::var("HOME").unwrap_or_default();
-	PathBuf::from(home).join(".tokbar").join("rightcodes-token.json")
+	// 找不到数据目录时不落盘，避免写到以当前目录为根的相对路径。
+	Some(config_dir::tokbar_data_dir()?.join("rightcodes-token.json"))
 }
 
 fn load_from_file(path: &Path) -> Option<String> {
@@ -131,8 +187,7 @@ fn load_from_file(path: &Path) -> Option<String> {
 }
 
 fn save_to_file(path: &Path, token: &str) -> Result<(), String> {
-	let parent = path.parent().ok_or("invalid token path")?;
-	fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+	config_dir::ensure_parent_dir(path)?;
 	let payload = TokenFilePayload {
 		token: token.to_string(),
 		saved_at: chrono::Local::now()
@@ -0,0 +1,369 @@
+//! 当前状态快照（价格可用性、日志文件数量、本期总计），用于 `tokbar-stats --status` 与定期落盘。
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::config_dir;
+use crate::litellm::PricingContext;
+use crate::pricing;
+use crate::time_range::{self, DateRange};
+use crate::usage::{self, ScanSettings, UsageTotals};
+
+/// 默认的状态日志大小上限；超过后轮转为 `<file>.1`（只保留一份旧文件）。
+pub const DEFAULT_STATUS_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusSnapshot {
+	pub timestamp: String,
+	pub period: String,
+	pub pricing_available: bool,
+	pub pricing_stale: bool,
+	pub pricing_error: Option<String>,
+	pub pricing_models: usize,
+	pub cx_files: usize,
+	/// 本机没有 Claude Code 日志目录时为 null。
+	pub cc_files: Option<usize>,
+	pub cx: UsageTotals,
+	pub cc: Option<UsageTotals>,
+	/// “全部”统计因 `max_files_scanned` 只扫描了部分文件（任一来源）。
+	pub all_time_partial: bool,
+}
+
+impl StatusSnapshot {
+	/// 用已经算好的总计拼出快照（托盘刷新循环复用本轮结果，避免重复扫描）。
+	pub fn from_totals(
+		range: &DateRange,
+		pricing: &PricingContext,
+		cx: UsageTotals,
+		cc: Option<UsageTotals>,
+		scan: &ScanSettings,
+	) -> Self {
+		let (cx_partial, cc_partial) = usage::all_time_partial();
+		Self {
+			timestamp: Local::now().to_rfc3339(),
+			period: range.label.clone(),
+			pricing_available: pricing.available,
+			pricing_stale: pricing.stale,
+			pricing_error: pricing.last_error.clone(),
+			pricing_models: pricing.dataset.len(),
+			cx_files: usage::cx_file_count(),
+			cc_files: usage::cc_file_count(scan).ok(),
+			cx,
+			cc,
+			all_time_partial: cx_partial || cc_partial,
+		}
+	}
+
+	/// 重新统计本期总计并生成快照。
+	pub fn collect(range: &DateRange, pricing: &PricingContext, scan: &ScanSettings) -> Self {
+		let cx = usage::load_cx_totals_with_pricing(range, &pricing.dataset, scan);
+		let cc = usage::load_cc_totals_with_pricing(range, &pricing.dataset, scan).ok();
+		Self::from_totals(range, pricing, cx, cc, scan)
+	}
+}
+
+/// 单个指标值（按来源 + 统计区间区分）。
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MetricGauge {
+	pub name: &'static str,
+	pub source: &'static str,
+	pub period: String,
+	pub value: f64,
+}
+
+/// 一次性的指标快照（`tokbar-stats --metrics`），适合 cron 推送到 push-gateway/TSDB。
+///
+/// 说明：带上价格表指纹与可用性，使每次采集都能自描述“金额是按哪份价格算的”。
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+	pub timestamp: String,
+	pub pricing_available: bool,
+	pub pricing_stale: bool,
+	/// 价格表内容（key 与单价）的指纹（16 位十六进制）；没有可用价格时为 null。
+	pub dataset_fingerprint: Option<String>,
+	pub pricing_models: usize,
+	pub gauges: Vec<MetricGauge>,
+}
+
+impl MetricsSnapshot {
+	/// 用各区间的总计拼出快照；`None` 表示该来源不可用（如本机没有 Claude Code 日志），不输出对应指标。
+	pub fn from_totals(
+		pricing: &PricingContext,
+		rows: Vec<(String, &'static str, Option<UsageTotals>)>,
+	) -> Self {
+		let mut gauges = Vec::new();
+		for (period, source, totals) in rows {
+			let Some(totals) = totals else {
+				continue;
+			};
+			gauges.push(MetricGauge {
+				name: "tokbar_tokens",
+				source,
+				period: period.clone(),
+				value: totals.total_tokens as f64,
+			});
+			// 价格不可用时金额恒为 0，输出出去只会污染时序数据。
+			if pricing.available {
+				gauges.push(MetricGauge {
+					name: "tokbar_cost_usd",
+					source,
+					period,
+					value: totals.cost_usd,
+				});
+			}
+		}
+		Self {
+			timestamp: Local::now().to_rfc3339(),
+			pricing_available: pricing.available,
+			pricing_stale: pricing.stale,
+			dataset_fingerprint: (!pricing.dataset.is_empty())
+				.then(|| format!("{:016x}", pricing::dataset_fingerprint(&pricing.dataset))),
+			pricing_models: pricing.dataset.len(),
+			gauges,
+		}
+	}
+
+	/// 统计今天/本周/本月/全部的 cx 与 cc 总计。
+	pub fn collect(pricing: &PricingContext, scan: &ScanSettings) -> Self {
+		let dataset = &pricing.dataset;
+		let mut rows = Vec::new();
+		for range in [
+			time_range::range_today(),
+			time_range::range_week_monday(),
+			time_range::range_month(),
+		] {
+			let cx = usage::load_cx_totals_with_pricing(&range, dataset, scan);
+			let cc = usage::load_cc_totals_with_pricing(&range, dataset, scan).ok();
+			rows.push((range.label.clone(), "cx", Some(cx)));
+			rows.push((range.label, "cc", cc));
+		}
+		let cx_all = usage::load_cx_totals_all_time_cached_with_pricing(dataset, scan);
+		let cc_all = usage::load_cc_totals_all_time_cached_with_pricing(dataset, scan).ok();
+		rows.push(("All".to_string(), "cx", Some(cx_all)));
+		rows.push(("All".to_string(), "cc", cc_all));
+		Self::from_totals(pricing, rows)
+	}
+}
+
+/// Prometheus 文本格式里的指标族：(快照里的 gauge 名, 导出的指标名, HELP 说明)。
+const PROMETHEUS_FAMILIES: [(&str, &str, &str); 2] = [
+	("tokbar_tokens", "tokbar_tokens_total", "Tokens used in the period."),
+	("tokbar_cost_usd", "tokbar_cost_usd", "Estimated cost in USD for the period."),
+];
+
+/// 区间标签转成 Prometheus 的 label 值：`This Week` → `this_week`。
+fn prometheus_period(label: &str) -> String {
+	label.trim().to_lowercase().replace(' ', "_")
+}
+
+impl MetricsSnapshot {
+	/// 渲染为 Prometheus 文本格式（node_exporter textfile collector 可直接读取）。
+	///
+	/// 说明：同一指标族的样本必须连续输出，所以按族分组，而不是按快照里的顺序。
+	pub fn to_prometheus_text(&self) -> String {
+		let mut out = String::new();
+		for (gauge_name, metric, help) in PROMETHEUS_FAMILIES {
+			let samples: Vec<&MetricGauge> =
+				self.gauges.iter().filter(|gauge| gauge.name == gauge_name).collect();
+			if samples.is_empty() {
+				continue;
+			}
+			out.push_str(&format!("# HELP {metric} {help}\n# TYPE {metric} gauge\n"));
+			for gauge in samples {
+				out.push_str(&format!(
+					"{metric}{{source=\"{}\",period=\"{}\"}} {}\n",
+					gauge.source,
+					prometheus_period(&gauge.period),
+					gauge.value
+				));
+			}
+		}
+		out.push_str("# HELP tokbar_pricing_available Whether model pricing was available.\n");
+		out.push_str("# TYPE tokbar_pricing_available gauge\n");
+		out.push_str(&format!("tokbar_pricing_available {}\n", u8::from(self.pricing_available)));
+		out
+	}
+}
+
+/// 写入 textfile：先写 `<file>.tmp` 再重命名，避免采集端读到写了一半的文件。
+pub fn write_textfile(path: &Path, body: &str) -> Result<(), String> {
+	config_dir::ensure_parent_dir(path)?;
+	let mut tmp = path.as_os_str().to_os_string();
+	tmp.push(".tmp");
+	let tmp = PathBuf::from(tmp);
+	fs::write(&tmp, body).map_err(|e| e.to_string())?;
+	fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+	let mut name = path.as_os_str().to_os_string();
+	name.push(".1");
+	PathBuf::from(name)
+}
+
+/// 以 NDJSON（一行一个 JSON 对象）追加写入快照。
+///
+/// 说明：追加前如果文件已达到 `max_bytes`，先轮转为 `<file>.1` 再写新文件，避免无限增长。
+pub fn append_snapshot(
+	path: &Path,
+	snapshot: &StatusSnapshot,
+	max_bytes: u64,
+) -> Result<(), String> {
+	let mut line = serde_json::to_string(snapshot).map_err(|e| e.to_string())?;
+	line.push('\n');
+
+	config_dir::ensure_parent_dir(path)?;
+	if let Ok(meta) = fs::metadata(path) {
+		if max_bytes > 0 && meta.len() + line.len() as u64 > max_bytes {
+			fs::rename(path, rotated_path(path)).map_err(|e| e.to_string())?;
+		}
+	}
+
+	let mut file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.map_err(|e| e.to_string())?;
+	file.write_all(line.as_bytes()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn snapshot() -> StatusSnapshot {
+		StatusSnapshot {
+			timestamp: Local::now().to_rfc3339(),
+			period: "Today".to_string(),
+			pricing_available: false,
+			pricing_stale: false,
+			pricing_error: Some("offline".to_string()),
+			pricing_models: 0,
+			cx_files: 3,
+			cc_files: None,
+			cx: UsageTotals {
+				total_tokens: 42,
+				..Default::default()
+			},
+			cc: None,
+			all_time_partial: false,
+		}
+	}
+
+	#[test]
+	fn metrics_json_has_self_describing_shape() {
+		let mut dataset = std::collections::HashMap::new();
+		dataset.insert("gpt-5".to_string(), pricing::LiteLLMModelPricing::default());
+		let pricing = PricingContext {
+			available: true,
+			dataset: std::sync::Arc::new(dataset),
+			..Default::default()
+		};
+		let totals = UsageTotals {
+			total_tokens: 1200,
+			cost_usd: 0.5,
+			..Default::default()
+		};
+		let snapshot = MetricsSnapshot::from_totals(
+			&pricing,
+			vec![
+				("Today".to_string(), "cx", Some(totals)),
+				("Today".to_string(), "cc", None),
+			],
+		);
+
+		let value = serde_json::to_value(&snapshot).expect("serialize");
+		assert_eq!(value["pricing_available"], true);
+		assert_eq!(value["pricing_models"], 1);
+		assert_eq!(value["dataset_fingerprint"].as_str().map(str::len), Some(16));
+		let gauges = value["gauges"].as_array().expect("gauges array");
+		assert_eq!(gauges.len(), 2);
+		assert_eq!(gauges[0]["name"], "tokbar_tokens");
+		assert_eq!(gauges[0]["source"], "cx");
+		assert_eq!(gauges[0]["period"], "Today");
+		assert_eq!(gauges[0]["value"], 1200.0);
+		assert_eq!(gauges[1]["name"], "tokbar_cost_usd");
+		assert_eq!(gauges[1]["value"], 0.5);
+
+		// 价格不可用：不输出金额指标，指纹为 null。
+		let offline = MetricsSnapshot::from_totals(
+			&PricingContext::default(),
+			vec![("Today".to_string(), "cx", Some(totals))],
+		);
+		let value = serde_json::to_value(&offline).expect("serialize");
+		assert!(value["dataset_fingerprint"].is_null());
+		assert_eq!(value["gauges"].as_array().map(Vec::len), Some(1));
+	}
+
+	#[test]
+	fn prometheus_text_groups_samples_by_family() {
+		let pricing = PricingContext {
+			available: true,
+			..Default::default()
+		};
+		let totals = |total_tokens: u64, cost_usd: f64| UsageTotals {
+			total_tokens,
+			cost_usd,
+			..Default::default()
+		};
+		let snapshot = MetricsSnapshot::from_totals(
+			&pricing,
+			vec![
+				("Today".to_string(), "cx", Some(totals(1200, 0.5))),
+				("Today".to_string(), "cc", None),
+				("Last Month".to_string(), "cc", Some(totals(30, 0.25))),
+			],
+		);
+
+		assert_eq!(
+			snapshot.to_prometheus_text(),
+			"# HELP tokbar_tokens_total Tokens used in the period.\n\
+			 # TYPE tokbar_tokens_total gauge\n\
+			 tokbar_tokens_total{source=\"cx\",period=\"today\"} 1200\n\
+			 tokbar_tokens_total{source=\"cc\",period=\"last_month\"} 30\n\
+			 # HELP tokbar_cost_usd Estimated cost in USD for the period.\n\
+			 # TYPE tokbar_cost_usd gauge\n\
+			 tokbar_cost_usd{source=\"cx\",period=\"today\"} 0.5\n\
+			 tokbar_cost_usd{source=\"cc\",period=\"last_month\"} 0.25\n\
+			 # HELP tokbar_pricing_available Whether model pricing was available.\n\
+			 # TYPE tokbar_pricing_available gauge\n\
+			 tokbar_pricing_available 1\n"
+		);
+
+		let dir = tempfile::tempdir().expect("tempdir");
+		let path = dir.path().join("textfile").join("tokbar.prom");
+		write_textfile(&path, "a 1\n").expect("write");
+		assert_eq!(fs::read_to_string(&path).expect("read"), "a 1\n");
+		assert!(!dir.path().join("textfile").join("tokbar.prom.tmp").exists());
+	}
+
+	#[test]
+	fn appends_valid_ndjson_lines_and_rotates() {
+		let dir = tempfile::tempdir().expect("tempdir");
+		let path = dir.path().join("logs").join("status.ndjson");
+
+		append_snapshot(&path, &snapshot(), 0).expect("append 1");
+		append_snapshot(&path, &snapshot(), 0).expect("append 2");
+
+		let body = fs::read_to_string(&path).expect("read");
+		let lines: Vec<&str> = body.lines().collect();
+		assert_eq!(lines.len(), 2);
+		for line in lines {
+			let value: serde_json::Value = serde_json::from_str(line).expect("valid json line");
+			assert_eq!(value["cx"]["total_tokens"], 42);
+			assert!(value["cc"].is_null());
+			assert!(value["timestamp"].is_string());
+		}
+
+		// 上限小于两行时，第二次写入前会把旧文件轮转走。
+		let capped = dir.path().join("capped.ndjson");
+		append_snapshot(&capped, &snapshot(), 10).expect("append capped 1");
+		append_snapshot(&capped, &snapshot(), 10).expect("append capped 2");
+		assert_eq!(fs::read_to_string(&capped).expect("read").lines().count(), 1);
+		assert!(rotated_path(&capped).exists());
+	}
+}
@@ -7,3 +7,32 @@ pub fn env_cwd_lock() -> &'static Mutex<()> {
 	LOCK.get_or_init(|| Mutex::new(()))
 }
 
+
+/// 测试用的单日区间（`YYYYMMDD`，按整天统计）。
+#[cfg(test)]
+pub fn day_range(yyyymmdd: &str) -> crate::time_range::DateRange {
+	span_range(yyyymmdd, yyyymmdd, "Today")
+}
+
+/// 测试用的多日区间（闭区间，按整天统计）。
+#[cfg(test)]
+pub fn span_range(since: &str, until: &str, label: &str) -> crate::time_range::DateRange {
+	crate::time_range::DateRange {
+		since_yyyymmdd: since.to_string(),
+		until_yyyymmdd: until.to_string(),
+		label: label.to_string(),
+		started_at_millis: None,
+	}
+}
+
+/// 落在 [`day_range`]`("20260206")` 里的本地时间（2026-02-06 12:00，RFC3339）。
+#[cfg(test)]
+pub fn local_noon_20260206() -> String {
+	use chrono::{Local, TimeZone};
+
+	Local
+		.with_ymd_and_hms(2026, 2, 6, 12, 0, 0)
+		.single()
+		.expect("local dt")
+		.to_rfc3339()
+}
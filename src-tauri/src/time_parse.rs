@@ -1,30 +1,199 @@
-use chrono::{DateTime, Local, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::OnceLock;
+
+use chrono::{
+	DateTime, Datelike, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime,
+	TimeZone, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ParsedTimestamp {
 	pub millis: i64,
 	pub local_date: NaiveDate,
+	/// 与 `local_date` 同一时区下的时刻（用于工作时间过滤）。
+	pub local_time: NaiveTime,
+}
+
+/// 工作时间过滤的口径。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BusinessHoursMode {
+	/// 不过滤（默认）。
+	#[default]
+	Off,
+	/// 只统计工作时间内的记录。
+	Inside,
+	/// 只统计工作时间外（周末/下班后）的记录，便于单独查看。
+	Outside,
+}
+
+/// 工作时间窗口：`weekdays` 中每天的 `[start, end)`（本地时间）；`start > end` 表示跨午夜。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BusinessHours {
+	pub mode: BusinessHoursMode,
+	pub weekdays: Vec<Weekday>,
+	pub start: NaiveTime,
+	pub end: NaiveTime,
+}
+
+impl Default for BusinessHours {
+	fn default() -> Self {
+		Self {
+			mode: BusinessHoursMode::Off,
+			weekdays: vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+			start: NaiveTime::from_hms_opt(9, 0, 0).unwrap_or(NaiveTime::MIN),
+			end: NaiveTime::from_hms_opt(18, 0, 0).unwrap_or(NaiveTime::MIN),
+		}
+	}
+}
+
+impl BusinessHours {
+	pub fn is_active(&self) -> bool {
+		self.mode != BusinessHoursMode::Off
+	}
+
+	/// 时间点是否落在工作时间窗口内（跨午夜的窗口按开始那天的星期判断）。
+	fn contains(&self, parsed: &ParsedTimestamp) -> bool {
+		let t = parsed.local_time;
+		let (in_window, day) = if self.start <= self.end {
+			(t >= self.start && t < self.end, parsed.local_date)
+		} else if t >= self.start {
+			(true, parsed.local_date)
+		} else {
+			(t < self.end, parsed.local_date.pred_opt().unwrap_or(parsed.local_date))
+		};
+		in_window && self.weekdays.contains(&day.weekday())
+	}
+
+	/// 按当前口径该记录是否计入。
+	pub fn allows(&self, parsed: &ParsedTimestamp) -> bool {
+		match self.mode {
+			BusinessHoursMode::Off => true,
+			BusinessHoursMode::Inside => self.contains(parsed),
+			BusinessHoursMode::Outside => !self.contains(parsed),
+		}
+	}
+
+	/// 同 [`BusinessHours::allows`]，直接接收日志里的时间戳；过滤开启时，无法解析的时间戳不计入。
+	pub fn allows_timestamp(&self, timestamp: Option<&str>) -> bool {
+		if !self.is_active() {
+			return true;
+		}
+		timestamp
+			.and_then(parse_js_timestamp)
+			.is_some_and(|parsed| self.allows(&parsed))
+	}
+}
+
+/// 代替系统时区的环境变量（IANA 时区名，如 `Asia/Shanghai`）：在 UTC 服务器上跑、
+/// 或出差时系统时区与实际工作地不一致时，让“今天”按指定时区划分。
+const TZ_ENV: &str = "TOKBAR_TZ";
+
+static TZ_OVERRIDE: OnceLock<Result<Option<chrono_tz::Tz>, String>> = OnceLock::new();
+
+/// 解析 `TOKBAR_TZ` 的取值：未设置/空白为 Ok(None)，不是 IANA 时区名时返回错误文案。
+fn parse_tz_override(raw: Option<&str>) -> Result<Option<chrono_tz::Tz>, String> {
+	let Some(name) = raw.map(str::trim).filter(|name| !name.is_empty()) else {
+		return Ok(None);
+	};
+	name.parse()
+		.map(Some)
+		.map_err(|_| format!("无法识别的时区 {TZ_ENV}={name}（需要 IANA 时区名，如 Asia/Shanghai）"))
+}
+
+fn tz_override_result() -> &'static Result<Option<chrono_tz::Tz>, String> {
+	TZ_OVERRIDE.get_or_init(|| parse_tz_override(std::env::var(TZ_ENV).ok().as_deref()))
+}
+
+/// `TOKBAR_TZ` 指定的时区（进程内只解析一次）；未设置或无法识别时为 None，即使用系统时区。
+fn tz_override() -> Option<chrono_tz::Tz> {
+	tz_override_result().as_ref().ok().copied().flatten()
+}
+
+/// `TOKBAR_TZ` 无法识别时的错误文案（此时按系统时区统计）；未设置或有效时为 Ok。
+pub fn check_tz_override() -> Result<(), String> {
+	tz_override_result().as_ref().map(|_| ()).map_err(Clone::clone)
+}
+
+/// 划分“本地日期”用的 UTC 偏移（秒）；`i32::MIN` 表示跟随系统时区（或 `TOKBAR_TZ`）。
+static DAY_OFFSET_SECS: AtomicI32 = AtomicI32::new(i32::MIN);
+
+/// 设置按哪个时区划分日期（None = 系统时区，设置了 `TOKBAR_TZ` 时为该时区）。影响带时区的时间戳归属哪一天，以及“今天”是哪天。
+pub fn set_day_offset(offset: Option<FixedOffset>) {
+	let secs = offset.map_or(i32::MIN, |o| o.local_minus_utc());
+	DAY_OFFSET_SECS.store(secs, Ordering::Relaxed);
+}
+
+pub fn day_offset() -> Option<FixedOffset> {
+	match DAY_OFFSET_SECS.load(Ordering::Relaxed) {
+		i32::MIN => None,
+		secs => FixedOffset::east_opt(secs),
+	}
+}
+
+/// 某个时刻在给定时区（None = 系统时区或 `TOKBAR_TZ`）下的日期。
+pub fn date_in_offset<Tz: TimeZone>(dt: &DateTime<Tz>, offset: Option<FixedOffset>) -> NaiveDate {
+	datetime_in_offset(dt, offset).date()
+}
+
+/// 某个时刻在给定时区（None = 系统时区或 `TOKBAR_TZ`）下的本地日期时间。
+fn datetime_in_offset<Tz: TimeZone>(dt: &DateTime<Tz>, offset: Option<FixedOffset>) -> NaiveDateTime {
+	match offset {
+		Some(offset) => dt.with_timezone(&offset).naive_local(),
+		None => datetime_in_zone(dt, tz_override()),
+	}
+}
+
+/// 某个时刻在 `zone`（None = 系统时区）下的本地日期时间。
+fn datetime_in_zone<Tz: TimeZone>(dt: &DateTime<Tz>, zone: Option<chrono_tz::Tz>) -> NaiveDateTime {
+	match zone {
+		Some(zone) => dt.with_timezone(&zone).naive_local(),
+		None => dt.with_timezone(&Local).naive_local(),
+	}
+}
+
+/// 当前划分规则下的“今天”。
+pub fn today() -> NaiveDate {
+	date_in_offset(&Utc::now(), day_offset())
+}
+
+/// 时间戳里显式写出的 UTC 偏移（如 `+09:00`，`Z` 即 `+00:00`）；不带时区的时间返回 None。
+pub fn explicit_utc_offset(value: &str) -> Option<FixedOffset> {
+	DateTime::parse_from_rfc3339(value.trim()).ok().map(|dt| *dt.offset())
 }
 
 fn from_rfc3339(value: &str) -> Option<ParsedTimestamp> {
 	let dt = DateTime::parse_from_rfc3339(value).ok()?;
 	let millis = dt.timestamp_millis();
+	let local = datetime_in_offset(&dt, day_offset());
 	Some(ParsedTimestamp {
 		millis,
-		local_date: dt.with_timezone(&Local).date_naive(),
+		local_date: local.date(),
+		local_time: local.time(),
 	})
 }
 
+/// 不带时区的时间按系统时区（设置了 `TOKBAR_TZ` 时为该时区）理解。
 fn from_local_naive(dt: NaiveDateTime) -> Option<ParsedTimestamp> {
-	let local = match Local.from_local_datetime(&dt) {
+	match tz_override() {
+		Some(zone) => from_naive_in_zone(&zone, dt),
+		None => from_naive_in_zone(&Local, dt),
+	}
+}
+
+fn from_naive_in_zone<Tz: TimeZone>(zone: &Tz, dt: NaiveDateTime) -> Option<ParsedTimestamp> {
+	let local = match zone.from_local_datetime(&dt) {
 		LocalResult::Single(value) => value,
 		LocalResult::Ambiguous(earliest, _) => earliest,
 		LocalResult::None => return None,
 	};
 
 	Some(ParsedTimestamp {
-		millis: local.with_timezone(&Utc).timestamp_millis(),
+		millis: local.timestamp_millis(),
 		local_date: local.date_naive(),
+		local_time: local.time(),
 	})
 }
 
@@ -32,9 +201,11 @@ fn from_utc_date_only(date: NaiveDate) -> Option<ParsedTimestamp> {
 	let dt = date.and_hms_opt(0, 0, 0)?;
 	let utc = Utc.from_utc_datetime(&dt);
 	let millis = utc.timestamp_millis();
+	let local = datetime_in_offset(&utc, day_offset());
 	Some(ParsedTimestamp {
 		millis,
-		local_date: utc.with_timezone(&Local).date_naive(),
+		local_date: local.date(),
+		local_time: local.time(),
 	})
 }
 
@@ -116,6 +287,56 @@ mod tests {
 		assert!(parse_js_timestamp("1").is_none());
 	}
 
+	#[test]
+	fn buckets_by_anchor_offset_instead_of_system_zone() {
+		let value = "2026-02-07T01:30:00+09:00";
+		let offset = explicit_utc_offset(value).expect("explicit offset");
+		assert_eq!(offset.local_minus_utc(), 9 * 3600);
+
+		let dt = DateTime::parse_from_rfc3339(value).expect("rfc3339");
+		assert_eq!(date_in_offset(&dt, Some(offset)), NaiveDate::from_ymd_opt(2026, 2, 7).unwrap());
+		// 同一时刻在 UTC-05:00 仍是前一天。
+		let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+		assert_eq!(date_in_offset(&dt, Some(new_york)), NaiveDate::from_ymd_opt(2026, 2, 6).unwrap());
+
+		let utc = FixedOffset::east_opt(0).unwrap();
+		assert_eq!(explicit_utc_offset("2026-02-06T12:00:00Z"), Some(utc));
+		assert_eq!(explicit_utc_offset("2026-02-06T12:00:00.123z"), Some(utc));
+		assert_eq!(explicit_utc_offset("2026-02-06T12:00:00"), None);
+	}
+
+	#[test]
+	fn tz_override_buckets_in_the_named_zone() {
+		let tokyo = chrono_tz::Asia::Tokyo;
+		let dt = DateTime::parse_from_rfc3339("2026-02-06T20:30:00Z").expect("rfc3339");
+		let local = datetime_in_zone(&dt, Some(tokyo));
+		assert_eq!(local.date(), NaiveDate::from_ymd_opt(2026, 2, 7).unwrap());
+		assert_eq!(local.time(), NaiveTime::from_hms_opt(5, 30, 0).unwrap());
+
+		// 不带时区的时间按指定时区理解：东京 09:00 即 UTC 00:00。
+		let naive = NaiveDateTime::parse_from_str("2026-02-06T09:00:00", "%Y-%m-%dT%H:%M:%S")
+			.expect("naive");
+		let parsed = from_naive_in_zone(&tokyo, naive).expect("parsed");
+		let expected = Utc.with_ymd_and_hms(2026, 2, 6, 0, 0, 0).unwrap().timestamp_millis();
+		assert_eq!(parsed.millis, expected);
+		assert_eq!(parsed.local_date, NaiveDate::from_ymd_opt(2026, 2, 6).unwrap());
+
+		// IANA 时区会跟着夏令时变化：纽约 7 月是 UTC-4。
+		let summer = DateTime::parse_from_rfc3339("2026-07-01T03:30:00Z").expect("rfc3339");
+		let local = datetime_in_zone(&summer, Some(chrono_tz::America::New_York));
+		assert_eq!(local.date(), NaiveDate::from_ymd_opt(2026, 6, 30).unwrap());
+		assert_eq!(local.time(), NaiveTime::from_hms_opt(23, 30, 0).unwrap());
+	}
+
+	#[test]
+	fn invalid_tz_override_is_reported() {
+		assert_eq!(parse_tz_override(None), Ok(None));
+		assert_eq!(parse_tz_override(Some("  ")), Ok(None));
+		assert_eq!(parse_tz_override(Some(" Asia/Tokyo ")), Ok(Some(chrono_tz::Asia::Tokyo)));
+		let err = parse_tz_override(Some("Mars/Olympus")).expect_err("unknown zone");
+		assert!(err.contains("TOKBAR_TZ=Mars/Olympus"), "{err}");
+	}
+
 	#[test]
 	fn parses_slash_date_as_local_midnight() {
 		let parsed = parse_js_timestamp("2026/02/06").expect("parsed");
@@ -1,10 +1,32 @@
-use chrono::{Datelike, Duration, Local, NaiveDate, Weekday};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Weekday};
+
+use crate::time_parse;
 
 #[derive(Debug, Clone)]
 pub struct DateRange {
 	pub since_yyyymmdd: String,
 	pub until_yyyymmdd: String,
-	pub label: &'static str,
+	pub label: String,
+	/// 精确到时刻的起点（毫秒时间戳，`Session` 用）：起始那天里更早的记录不计入。
+	/// 其余区间为 None，按整天统计。
+	pub started_at_millis: Option<i64>,
+}
+
+impl DateRange {
+	/// 记录时间是否不早于 `started_at_millis`；没有起点时总是 true，时间戳无法解析时为 false。
+	pub fn allows_timestamp(&self, timestamp: Option<&str>) -> bool {
+		let Some(started_at) = self.started_at_millis else {
+			return true;
+		};
+		timestamp
+			.and_then(time_parse::parse_js_timestamp)
+			.is_some_and(|parsed| parsed.millis >= started_at)
+	}
+}
+
+/// 检查 `TOKBAR_TZ`：取值无法识别时返回错误文案（统计会回退到系统时区）。
+pub fn check_tz_override() -> Result<(), String> {
+	time_parse::check_tz_override()
 }
 
 fn yyyymmdd(date: NaiveDate) -> String {
@@ -12,17 +34,45 @@ fn yyyymmdd(date: NaiveDate) -> String {
 }
 
 pub fn range_today() -> DateRange {
-	let today = Local::now().date_naive();
+	let today = time_parse::today();
 	let today_str = yyyymmdd(today);
 	DateRange {
 		since_yyyymmdd: today_str.clone(),
 		until_yyyymmdd: today_str,
-		label: "Today",
+		label: "Today".to_string(),
+		started_at_millis: None,
+	}
+}
+
+pub fn range_yesterday() -> DateRange {
+	let yesterday = time_parse::today() - Duration::days(1);
+	let yesterday_str = yyyymmdd(yesterday);
+	DateRange {
+		since_yyyymmdd: yesterday_str.clone(),
+		until_yyyymmdd: yesterday_str,
+		label: "Yesterday".to_string(),
+		started_at_millis: None,
+	}
+}
+
+/// 截至今天（含）的滚动 `n` 天窗口，标签形如 `Last 7d`；`n == 0` 视同今天。
+pub fn range_last_n_days(n: u32) -> DateRange {
+	if n == 0 {
+		return range_today();
+	}
+	let today = time_parse::today();
+	let since = today - Duration::days(i64::from(n) - 1);
+
+	DateRange {
+		since_yyyymmdd: yyyymmdd(since),
+		until_yyyymmdd: yyyymmdd(today),
+		label: format!("Last {n}d"),
+		started_at_millis: None,
 	}
 }
 
 pub fn range_week_monday() -> DateRange {
-	let today = Local::now().date_naive();
+	let today = time_parse::today();
 	let weekday = today.weekday();
 	let days_from_monday = match weekday {
 		Weekday::Mon => 0,
@@ -38,44 +88,182 @@ pub fn range_week_monday() -> DateRange {
 	DateRange {
 		since_yyyymmdd: yyyymmdd(since),
 		until_yyyymmdd: yyyymmdd(today),
-		label: "Week",
+		label: "Week".to_string(),
+		started_at_millis: None,
 	}
 }
 
 pub fn range_month() -> DateRange {
-	let today = Local::now().date_naive();
+	let today = time_parse::today();
 	let since = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap_or(today);
 
 	DateRange {
 		since_yyyymmdd: yyyymmdd(since),
 		until_yyyymmdd: yyyymmdd(today),
-		label: "Month",
+		label: "Month".to_string(),
+		started_at_millis: None,
+	}
+}
+
+/// 上一个自然月（1 号到月末），用于和本月对比。
+pub fn range_previous_month() -> DateRange {
+	let today = time_parse::today();
+	let this_month = NaiveDate::from_ymd_opt(today.year(), today.month(), 1).unwrap_or(today);
+	let until = this_month - Duration::days(1);
+	let since = NaiveDate::from_ymd_opt(until.year(), until.month(), 1).unwrap_or(until);
+
+	DateRange {
+		since_yyyymmdd: yyyymmdd(since),
+		until_yyyymmdd: yyyymmdd(until),
+		label: "Last Month".to_string(),
+		started_at_millis: None,
 	}
 }
 
 pub fn range_year() -> DateRange {
-	let today = Local::now().date_naive();
+	let today = time_parse::today();
 	let since = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap_or(today);
 
 	DateRange {
 		since_yyyymmdd: yyyymmdd(since),
 		until_yyyymmdd: yyyymmdd(today),
-		label: "Year",
+		label: "Year".to_string(),
+		started_at_millis: None,
 	}
 }
 
+/// 从 `started_at` 这一刻到今天（本次启动以来的用量），标签为 `Session`。
+pub fn range_session<Tz: TimeZone>(started_at: &DateTime<Tz>) -> DateRange {
+	let today = time_parse::today();
+	let since = time_parse::date_in_offset(started_at, time_parse::day_offset()).min(today);
+
+	DateRange {
+		since_yyyymmdd: yyyymmdd(since),
+		until_yyyymmdd: yyyymmdd(today),
+		label: "Session".to_string(),
+		started_at_millis: Some(started_at.timestamp_millis()),
+	}
+}
+
+/// 自定义的闭区间（本地日期）；`since > until` 时返回 None。标签形如 `2026-01-01..2026-01-31`。
+pub fn range_between(since: NaiveDate, until: NaiveDate) -> Option<DateRange> {
+	if since > until {
+		return None;
+	}
+	Some(DateRange {
+		since_yyyymmdd: yyyymmdd(since),
+		until_yyyymmdd: yyyymmdd(until),
+		label: format!("{}..{}", since.format("%Y-%m-%d"), until.format("%Y-%m-%d")),
+		started_at_millis: None,
+	})
+}
+
+/// 区间的文字说明（`--show-range` 用）：标签与起止日期各占一行，日期为 `YYYY-MM-DD`。
+pub fn describe_range(range: &DateRange) -> String {
+	let display = |raw: &str| {
+		NaiveDate::parse_from_str(raw, "%Y%m%d")
+			.map(|date| date.format("%Y-%m-%d").to_string())
+			.unwrap_or_else(|_| raw.to_string())
+	};
+	format!(
+		"label: {}\nsince: {}\nuntil: {}",
+		range.label,
+		display(&range.since_yyyymmdd),
+		display(&range.until_yyyymmdd)
+	)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 
 	#[test]
 	fn week_range_is_monday_start() {
-		let today = Local::now().date_naive();
+		let today = time_parse::today();
 		let range = range_week_monday();
 		let since = NaiveDate::parse_from_str(&range.since_yyyymmdd, "%Y%m%d").unwrap();
 		let delta = today.signed_duration_since(since);
 		assert!(delta.num_days() >= 0 && delta.num_days() <= 6);
 		assert_eq!(since.weekday(), Weekday::Mon);
 	}
-}
 
+	#[test]
+	fn yesterday_range_is_single_previous_day() {
+		let range = range_yesterday();
+		let expected = yyyymmdd(time_parse::today() - Duration::days(1));
+		assert_eq!(range.since_yyyymmdd, expected);
+		assert_eq!(range.until_yyyymmdd, expected);
+		assert_eq!(range.label, "Yesterday");
+	}
+
+	#[test]
+	fn last_n_days_is_rolling_and_inclusive() {
+		let today = time_parse::today();
+		let range = range_last_n_days(7);
+		assert_eq!(range.since_yyyymmdd, yyyymmdd(today - Duration::days(6)));
+		assert_eq!(range.until_yyyymmdd, yyyymmdd(today));
+		assert_eq!(range.label, "Last 7d");
+
+		let one = range_last_n_days(1);
+		assert_eq!(one.since_yyyymmdd, one.until_yyyymmdd);
+
+		let zero = range_last_n_days(0);
+		let today_range = range_today();
+		assert_eq!(zero.since_yyyymmdd, today_range.since_yyyymmdd);
+		assert_eq!(zero.until_yyyymmdd, today_range.until_yyyymmdd);
+		assert_eq!(zero.label, today_range.label);
+	}
+
+	#[test]
+	fn previous_month_ends_the_day_before_this_month_starts() {
+		let range = range_previous_month();
+		let since = NaiveDate::parse_from_str(&range.since_yyyymmdd, "%Y%m%d").unwrap();
+		let until = NaiveDate::parse_from_str(&range.until_yyyymmdd, "%Y%m%d").unwrap();
+		let this_month = NaiveDate::parse_from_str(&range_month().since_yyyymmdd, "%Y%m%d").unwrap();
+		assert_eq!(since.day(), 1);
+		assert_eq!((since.year(), since.month()), (until.year(), until.month()));
+		assert_eq!(until + Duration::days(1), this_month);
+		assert_eq!(range.label, "Last Month");
+	}
+
+	#[test]
+	fn custom_range_is_inclusive_and_ordered() {
+		let since = NaiveDate::from_ymd_opt(2026, 1, 1).unwrap();
+		let until = NaiveDate::from_ymd_opt(2026, 1, 31).unwrap();
+		let range = range_between(since, until).expect("valid range");
+		assert_eq!(range.since_yyyymmdd, "20260101");
+		assert_eq!(range.until_yyyymmdd, "20260131");
+		assert_eq!(range.label, "2026-01-01..2026-01-31");
+
+		assert!(range_between(until, since).is_none());
+		assert!(range_between(since, since).is_some());
+	}
+
+	#[test]
+	fn session_range_starts_at_the_given_instant() {
+		let started_at = chrono::Utc::now() - Duration::minutes(5);
+		let range = range_session(&started_at);
+		assert_eq!(range.until_yyyymmdd, yyyymmdd(time_parse::today()));
+		assert!(range.since_yyyymmdd <= range.until_yyyymmdd);
+		assert_eq!(range.label, "Session");
+
+		let before = (started_at - Duration::seconds(1)).to_rfc3339();
+		let after = (started_at + Duration::seconds(1)).to_rfc3339();
+		assert!(!range.allows_timestamp(Some(&before)));
+		assert!(range.allows_timestamp(Some(&after)));
+		assert!(!range.allows_timestamp(None));
+		assert!(range_today().allows_timestamp(None));
+	}
+
+	#[test]
+	fn described_range_matches_the_period_range() {
+		let range = range_week_monday();
+		let described = describe_range(&range);
+		let lines: Vec<&str> = described.lines().collect();
+		assert_eq!(lines[0], "label: Week");
+		let since = lines[1].strip_prefix("since: ").expect("since line").replace('-', "");
+		let until = lines[2].strip_prefix("until: ").expect("until line").replace('-', "");
+		assert_eq!(since, range.since_yyyymmdd);
+		assert_eq!(until, range.until_yyyymmdd);
+	}
+}
@@ -1,17 +1,129 @@
+use crate::app_settings;
+use crate::cache_lock;
 use crate::claude;
 use crate::codex;
-use crate::pricing::LiteLLMModelPricing;
-use crate::time_range::DateRange;
-use std::collections::HashMap;
+use crate::offset_cache;
+use crate::pricing::{self, LiteLLMModelPricing};
+use crate::time_parse;
+use crate::time_range::{self, DateRange};
+use chrono::{FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 const ALL_TIME_TTL: Duration = Duration::from_secs(60 * 5);
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct UsageTotals {
 	pub total_tokens: u64,
 	pub cost_usd: f64,
+	/// 其中的推理 token（已包含在 `total_tokens` 内；目前只有 Codex 会填充）。
+	pub reasoning_tokens: u64,
+	/// 其中命中缓存读取的输入 token（Claude 的 `cache_read_input_tokens`，
+	/// Codex 的 `cached_input_tokens`）。
+	pub cache_read_tokens: u64,
+	/// 写入缓存的输入 token（Claude 与 Codex 的 `cache_creation_input_tokens`）。
+	pub cache_creation_tokens: u64,
+	/// 有用量是记在回退模型名下的（日志没写模型，Codex 按 `gpt-5` 计），成本只是估算。
+	#[serde(default)]
+	pub is_estimated: bool,
+}
+
+impl std::ops::AddAssign for UsageTotals {
+	fn add_assign(&mut self, other: Self) {
+		self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+		self.cost_usd += other.cost_usd;
+		self.reasoning_tokens = self.reasoning_tokens.saturating_add(other.reasoning_tokens);
+		self.cache_read_tokens = self.cache_read_tokens.saturating_add(other.cache_read_tokens);
+		self.cache_creation_tokens =
+			self.cache_creation_tokens.saturating_add(other.cache_creation_tokens);
+		self.is_estimated |= other.is_estimated;
+	}
+}
+
+/// 某个模型的全量 token 与最后一次使用的本地日期（`tokbar-stats --models`）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ModelHistory {
+	pub total_tokens: u64,
+	/// 该模型的记录都没有可解析的时间戳时为 None。
+	pub last_used: Option<NaiveDate>,
+}
+
+impl ModelHistory {
+	pub(crate) fn record(&mut self, tokens: u64, date: Option<NaiveDate>) {
+		self.total_tokens = self.total_tokens.saturating_add(tokens);
+		self.last_used = self.last_used.max(date);
+	}
+}
+
+/// 单行日志的处理结果（只在诊断时统计；正常统计路径忽略它）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineOutcome {
+	/// 计入了统计（或作为后续统计的上下文，如 Codex 的 `turn_context`）。
+	Counted,
+	/// 与用量无关的行（空行、其它事件类型等）。
+	Ignored,
+	/// 看起来是用量记录，但 JSON 解析失败或不是合法 UTF-8。
+	Unparseable,
+	/// 用量记录落在统计区间之外（日期、工作时间或会话起点）。
+	OutOfRange,
+	/// 与已计入的记录重复，被去重掉。
+	Duplicate,
+}
+
+/// 一次扫描的行数统计，用来判断“数字对不上”是不是有行被悄悄跳过了。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ScanDiagnostics {
+	pub files: usize,
+	pub lines_total: u64,
+	pub lines_skipped_parse: u64,
+	pub lines_skipped_range: u64,
+	pub entries_deduped: u64,
+}
+
+impl ScanDiagnostics {
+	pub(crate) fn record(&mut self, outcome: LineOutcome) {
+		self.lines_total += 1;
+		match outcome {
+			LineOutcome::Counted | LineOutcome::Ignored => {}
+			LineOutcome::Unparseable => self.lines_skipped_parse += 1,
+			LineOutcome::OutOfRange => self.lines_skipped_range += 1,
+			LineOutcome::Duplicate => self.entries_deduped += 1,
+		}
+	}
+}
+
+/// 某个区间两个来源的扫描诊断；本机没有 Claude 数据目录时 `cc` 为 null。
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+	pub period: String,
+	pub cx: ScanDiagnostics,
+	pub cc: Option<ScanDiagnostics>,
+}
+
+/// 扫描日志时用到的设置：调用方读一次设置（[`AppSettings::scan_settings`]）后传给各 `load_*`，
+/// 加载函数本身不读 settings.json。
+///
+/// [`AppSettings::scan_settings`]: crate::app_settings::AppSettings::scan_settings
+#[derive(Debug, Clone, Default)]
+pub struct ScanSettings {
+	pub cx: codex::ScanOptions,
+	pub cc: claude::ScanOptions,
+	/// “全部”统计的起始日期（见 `all_time_since` 设置）。
+	pub all_time_since: Option<NaiveDate>,
+	/// “全部”统计最多扫描的文件数（见 `max_files_scanned` 设置）。
+	pub max_files_scanned: Option<usize>,
+}
+
+impl ScanSettings {
+	/// 读一次 settings.json（CLI 在 `main` 里调用；托盘用内存里的设置）。
+	pub fn from_settings() -> Self {
+		app_settings::load_settings().scan_settings()
+	}
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -23,6 +135,11 @@ pub enum UsageError {
 #[derive(Debug, Default)]
 struct CachedTotals {
 	computed_at: Option<Instant>,
+	baseline: Option<NaiveDate>,
+	/// 计算时生效的 `max_files_scanned`（设置变化后缓存失效）。
+	max_files: Option<usize>,
+	/// 文件数超过上限、只统计了最近的一部分文件。
+	partial: bool,
 	totals: UsageTotals,
 }
 
@@ -40,6 +157,11 @@ fn cx_all_time_cache_with_cost() -> &'static Mutex<CachedTotals> {
 #[derive(Debug, Default)]
 struct CachedTotalsMaybe {
 	computed_at: Option<Instant>,
+	baseline: Option<NaiveDate>,
+	max_files: Option<usize>,
+	/// 计算时生效的 `exclude_projects`（排除的项目变化后缓存失效）。
+	exclude_projects: Vec<String>,
+	partial: bool,
 	totals: Option<UsageTotals>,
 }
 
@@ -57,19 +179,74 @@ fn cc_all_time_cache_with_cost() -> &'static Mutex<CachedTotalsMaybe> {
 pub fn load_cc_totals_with_pricing(
 	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
 ) -> Result<UsageTotals, UsageError> {
-	let base_dirs = claude::default_claude_base_dirs()?;
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
 
 	Ok(claude::load_claude_totals_from_base_dirs_with_pricing(
 		&base_dirs,
 		range,
 		dataset,
+		&scan.cc,
 	))
 }
 
+/// 本机是否有 Codex 会话目录（没有时 cx 的数字恒为 0）。
+pub fn cx_logs_available() -> bool {
+	!codex::default_codex_session_dirs().is_empty()
+}
+
+/// 最近一次扫描 Codex/Claude 日志时失败的目录（路径非法、无权限等）。
+///
+/// 说明：扫描失败时对应目录的统计为 0，调用方据此区分“没有用量”和“扫描出错”。
+pub fn scan_failed_dirs() -> Vec<PathBuf> {
+	let mut dirs = cx_scan_failed_dirs();
+	dirs.extend(cc_scan_failed_dirs());
+	dirs
+}
+
+/// 同 [`scan_failed_dirs`]，只看 Codex 会话目录。
+pub fn cx_scan_failed_dirs() -> Vec<PathBuf> {
+	codex::session_scan_failures()
+}
+
+/// 同 [`scan_failed_dirs`]，只看 Claude 日志目录。
+pub fn cc_scan_failed_dirs() -> Vec<PathBuf> {
+	claude::usage_scan_failures()
+}
+
+/// 需要监听变更的日志目录：Codex 会话目录与各 Claude 数据目录下的日志子目录。
+pub fn usage_log_dirs(scan: &ScanSettings) -> Vec<PathBuf> {
+	let mut dirs = codex::default_codex_session_dirs();
+	if let Ok(base_dirs) = claude::default_claude_base_dirs(&scan.cc) {
+		dirs.extend(claude::claude_log_dirs(&base_dirs, &scan.cc));
+	}
+	dirs
+}
+
+/// 监听到变更的路径是否是参与统计的日志：Codex 会话目录下按会话文件判断（含 `.jsonl.gz`），
+/// 其余按 Claude 的规则判断（排除的项目不触发刷新）。
+pub fn usage_log_filter(scan: &ScanSettings) -> impl Fn(&Path) -> bool + Send + 'static {
+	usage_log_filter_for(codex::default_codex_session_dirs(), scan.cc.clone())
+}
+
+fn usage_log_filter_for(
+	codex_dirs: Vec<PathBuf>,
+	cc: claude::ScanOptions,
+) -> impl Fn(&Path) -> bool + Send + 'static {
+	move |path| {
+		if codex_dirs.iter().any(|dir| path.starts_with(dir)) {
+			codex::is_session_file(path)
+		} else {
+			claude::is_usage_file(path, &cc)
+		}
+	}
+}
+
 pub fn load_cx_totals_with_pricing(
 	range: &DateRange,
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
 ) -> UsageTotals {
 	let session_dirs = codex::default_codex_session_dirs();
 	if session_dirs.is_empty() {
@@ -80,11 +257,831 @@ pub fn load_cx_totals_with_pricing(
 		&session_dirs,
 		range,
 		dataset,
+		&scan.cx,
+	)
+}
+
+pub fn load_cc_breakdown_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<HashMap<String, UsageTotals>, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+
+	Ok(claude::load_claude_breakdown_from_base_dirs_with_pricing(
+		&base_dirs,
+		range,
+		dataset,
+		&scan.cc,
+	))
+}
+
+/// 把任意来源的行（如 stdin）按 Codex 会话日志解析，按模型拆分；`range` 为 None 时不过滤日期。
+pub fn load_cx_breakdown_from_lines_with_pricing(
+	lines: impl IntoIterator<Item = String>,
+	range: Option<&DateRange>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> HashMap<String, UsageTotals> {
+	codex::load_codex_breakdown_from_lines_with_pricing(lines, range, dataset, &scan.cx)
+}
+
+/// 同 [`load_cx_breakdown_from_lines_with_pricing`]，按 Claude 日志解析。
+pub fn load_cc_breakdown_from_lines_with_pricing(
+	lines: impl IntoIterator<Item = String>,
+	range: Option<&DateRange>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> HashMap<String, UsageTotals> {
+	claude::load_claude_breakdown_from_lines_with_pricing(lines, range, dataset, &scan.cc)
+}
+
+/// Claude 用量按项目拆分（key 为 `projects/` 下的目录名）。
+pub fn load_cc_breakdown_by_project_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<HashMap<String, UsageTotals>, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+
+	Ok(claude::load_claude_breakdown_by_project_with_pricing(
+		&base_dirs,
+		range,
+		dataset,
+		&scan.cc,
+	))
+}
+
+/// 同 [`load_cx_totals_with_pricing`]，额外返回逐行的处理统计（诊断用，比常规统计慢）。
+pub fn load_cx_totals_with_diagnostics(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> (UsageTotals, ScanDiagnostics) {
+	let files = codex::session_files_from_dirs(&codex::default_codex_session_dirs());
+	codex::load_codex_totals_with_diagnostics_from_files(&files, range, dataset, &scan.cx)
+}
+
+/// 同 [`load_cc_totals_with_pricing`]，额外返回逐行的处理统计（诊断用，比常规统计慢）。
+pub fn load_cc_totals_with_diagnostics(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<(UsageTotals, ScanDiagnostics), UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+	let files = claude::usage_files_from_claude_base_dirs(&base_dirs, &scan.cc);
+	Ok(claude::load_claude_totals_with_diagnostics_from_files(&files, range, dataset, &scan.cc))
+}
+
+/// 解析出的日志根目录，以及从中找到、参与统计的日志文件。
+#[derive(Debug, Clone, Default)]
+pub struct ScannedFiles {
+	pub base_dirs: Vec<PathBuf>,
+	pub files: Vec<PathBuf>,
+}
+
+/// 参与统计的 Codex 会话目录与文件（`tokbar-stats --verbose`，复用文件列表缓存）。
+pub fn cx_scanned_files() -> ScannedFiles {
+	let base_dirs = codex::default_codex_session_dirs();
+	let files = codex::session_files_from_dirs(&base_dirs);
+	ScannedFiles { base_dirs, files }
+}
+
+/// 参与统计的 Claude 数据目录与日志文件；本机没有 Claude 数据目录时返回错误。
+pub fn cc_scanned_files(scan: &ScanSettings) -> Result<ScannedFiles, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+	let files = claude::usage_files_from_claude_base_dirs(&base_dirs, &scan.cc);
+	Ok(ScannedFiles { base_dirs, files })
+}
+
+/// 当前参与统计的 Codex 会话文件数（复用文件列表缓存）。
+pub fn cx_file_count() -> usize {
+	let session_dirs = codex::default_codex_session_dirs();
+	if session_dirs.is_empty() {
+		return 0;
+	}
+	codex::session_files_from_dirs(&session_dirs).len()
+}
+
+/// 当前参与统计的 Claude 日志文件数；本机没有 Claude 数据目录时返回错误。
+pub fn cc_file_count(scan: &ScanSettings) -> Result<usize, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+	Ok(claude::usage_files_from_claude_base_dirs(&base_dirs, &scan.cc).len())
+}
+
+/// 按本地日期汇总的 Claude 用量（token 按类别拆开）。
+pub fn load_cc_daily_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<BTreeMap<NaiveDate, claude::ClaudeDailyUsage>, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+
+	Ok(claude::load_claude_daily_from_base_dirs_with_pricing(
+		&base_dirs,
+		range,
+		dataset,
+		&scan.cc,
+	))
+}
+
+pub fn load_cx_breakdown_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> HashMap<String, UsageTotals> {
+	let session_dirs = codex::default_codex_session_dirs();
+	if session_dirs.is_empty() {
+		return HashMap::new();
+	}
+
+	codex::load_codex_breakdown_from_session_dirs_with_pricing(
+		&session_dirs,
+		range,
+		dataset,
+		&scan.cx,
 	)
 }
 
+/// 全量扫描 Codex 日志，列出出现过的每个模型及其最后使用日期。
+pub fn load_cx_model_history(scan: &ScanSettings) -> HashMap<String, ModelHistory> {
+	let session_dirs = codex::default_codex_session_dirs();
+	if session_dirs.is_empty() {
+		return HashMap::new();
+	}
+	let files = codex::session_files_from_dirs(&session_dirs);
+	codex::load_codex_model_history_from_files(&files, &scan.cx)
+}
+
+/// 全量扫描 Claude 日志，列出出现过的每个模型及其最后使用日期。
+pub fn load_cc_model_history(
+	scan: &ScanSettings,
+) -> Result<HashMap<String, ModelHistory>, UsageError> {
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+	let files = claude::usage_files_from_claude_base_dirs(&base_dirs, &scan.cc);
+	Ok(claude::load_claude_model_history_from_files(&files, &scan.cc))
+}
+
+/// 按最后使用日期从近到远排序（同一天按模型名），没有日期的排在最后。
+pub fn sorted_model_history(history: HashMap<String, ModelHistory>) -> Vec<(String, ModelHistory)> {
+	let mut rows: Vec<(String, ModelHistory)> = history.into_iter().collect();
+	rows.sort_by(|(a_model, a), (b_model, b)| {
+		b.last_used.cmp(&a.last_used).then_with(|| a_model.cmp(b_model))
+	});
+	rows
+}
+
+/// 按模型拆分结果的合计。
+pub fn sum_breakdown(breakdown: &HashMap<String, UsageTotals>) -> UsageTotals {
+	let mut totals = UsageTotals::default();
+	for model_totals in breakdown.values() {
+		totals += *model_totals;
+	}
+	totals
+}
+
+/// 合并多份按模型拆分结果（同名模型累加，不做跨来源归一化）。
+pub fn merge_breakdowns(breakdowns: &[&HashMap<String, UsageTotals>]) -> HashMap<String, UsageTotals> {
+	let mut merged: HashMap<String, UsageTotals> = HashMap::new();
+	for breakdown in breakdowns {
+		for (model, totals) in breakdown.iter() {
+			*merged.entry(model.clone()).or_default() += *totals;
+		}
+	}
+	merged
+}
+
+/// 归一化模型名用于跨来源比较：忽略大小写与 provider 前缀（如 `openai/`），并套用 Codex 的模型别名。
+fn normalize_model_key(model: &str) -> String {
+	let lower = model.trim().to_ascii_lowercase();
+	let bare = lower.rsplit('/').next().unwrap_or(lower.as_str());
+	codex::model_alias(bare).unwrap_or(bare).to_string()
+}
+
+/// 在若干份按模型拆分的统计中，汇总某一个模型（归一化后相同即视为同一模型）的用量。
+pub fn sum_model_in_breakdowns(
+	breakdowns: &[&HashMap<String, UsageTotals>],
+	model: &str,
+) -> UsageTotals {
+	let wanted = normalize_model_key(model);
+	let mut totals = UsageTotals::default();
+	for breakdown in breakdowns {
+		for (key, model_totals) in breakdown.iter() {
+			if normalize_model_key(key) == wanted {
+				totals += *model_totals;
+			}
+		}
+	}
+	totals
+}
+
+/// 实际用过的模型里，哪些能在价格表里找到价格（结果按模型名排序、去重）。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PricingCoverage {
+	pub matched: Vec<String>,
+	pub unmatched: Vec<String>,
+}
+
+/// 用真实的按模型拆分结果与价格表求交集。
+///
+/// 说明：匹配规则沿用各来源计费时的规则（Codex 的别名 / Claude 的前缀），
+/// 因此“未匹配”就意味着这些模型的成本被按 0 计算了。
+pub fn pricing_coverage(
+	cx_breakdown: &HashMap<String, UsageTotals>,
+	cc_breakdown: &HashMap<String, UsageTotals>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> PricingCoverage {
+	let mut matched = BTreeSet::new();
+	let mut unmatched = BTreeSet::new();
+	let cx_strict = scan.cx.strict_model_matching();
+	let cc_strict = scan.cc.strict_model_matching;
+	let observed = cx_breakdown
+		.keys()
+		.map(|model| (model, codex::has_pricing(model, dataset, cx_strict)))
+		.chain(
+			cc_breakdown
+				.keys()
+				.map(|model| (model, claude::has_pricing(model, dataset, cc_strict))),
+		);
+	for (model, has_pricing) in observed {
+		if has_pricing {
+			matched.insert(model.clone());
+		} else {
+			unmatched.insert(model.clone());
+		}
+	}
+	// 同名模型只要有一处能匹配就算已覆盖。
+	for model in &matched {
+		unmatched.remove(model);
+	}
+
+	PricingCoverage {
+		matched: matched.into_iter().collect(),
+		unmatched: unmatched.into_iter().collect(),
+	}
+}
+
+/// 单个来源的结构化统计（给前端面板用）：总计、按模型拆分，以及价格表里找不到的模型。
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+	pub totals: UsageTotals,
+	pub breakdown: BTreeMap<String, UsageTotals>,
+	/// 成本被按 0 计算的模型（见 [`pricing_coverage`]）。
+	pub unpriced_models: Vec<String>,
+}
+
+/// 一个时间范围的结构化报告；未选中的来源为 null，`cost_available = false` 时成本恒为 0。
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+	pub period: String,
+	pub cost_available: bool,
+	pub cx: Option<SourceReport>,
+	pub cc: Option<SourceReport>,
+}
+
+/// 由 Codex 的按模型拆分结果生成报告（总计即拆分之和，与 totals 加载函数一致）。
+pub fn cx_source_report(
+	breakdown: HashMap<String, UsageTotals>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> SourceReport {
+	let coverage = pricing_coverage(&breakdown, &HashMap::new(), dataset, scan);
+	SourceReport {
+		totals: sum_breakdown(&breakdown),
+		breakdown: breakdown.into_iter().collect(),
+		unpriced_models: coverage.unmatched,
+	}
+}
+
+/// 由 Claude 的按模型拆分结果生成报告。
+pub fn cc_source_report(
+	breakdown: HashMap<String, UsageTotals>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> SourceReport {
+	let coverage = pricing_coverage(&HashMap::new(), &breakdown, dataset, scan);
+	SourceReport {
+		totals: sum_breakdown(&breakdown),
+		breakdown: breakdown.into_iter().collect(),
+		unpriced_models: coverage.unmatched,
+	}
+}
+
+pub fn load_cx_report_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> SourceReport {
+	cx_source_report(load_cx_breakdown_with_pricing(range, dataset, scan), dataset, scan)
+}
+
+pub fn load_cc_report_with_pricing(
+	range: &DateRange,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<SourceReport, UsageError> {
+	let breakdown = load_cc_breakdown_with_pricing(range, dataset, scan)?;
+	Ok(cc_source_report(breakdown, dataset, scan))
+}
+
+/// CSV 导出的一行：某个本地日期、某个来源的用量。
+#[derive(Debug, Clone, Copy)]
+pub struct DailySourceUsage {
+	pub date: NaiveDate,
+	pub source: &'static str,
+	pub totals: UsageTotals,
+}
+
+/// 逐日统计 `[since, until]` 内 cx 与 cc 的用量：每天构造一个单日区间调用一次加载函数。
+/// `load_cc` 返回 None（本机没有 Claude 数据目录）时不输出 cc 行。
+pub fn daily_source_usage(
+	since: NaiveDate,
+	until: NaiveDate,
+	load_cx: impl Fn(&DateRange) -> UsageTotals,
+	load_cc: impl Fn(&DateRange) -> Option<UsageTotals>,
+) -> Vec<DailySourceUsage> {
+	let mut rows = Vec::new();
+	for date in since.iter_days().take_while(|date| *date <= until) {
+		let Some(range) = time_range::range_between(date, date) else {
+			continue;
+		};
+		rows.push(DailySourceUsage {
+			date,
+			source: "cx",
+			totals: load_cx(&range),
+		});
+		if let Some(totals) = load_cc(&range) {
+			rows.push(DailySourceUsage {
+				date,
+				source: "cc",
+				totals,
+			});
+		}
+	}
+	rows
+}
+
+/// 渲染为 `date,source,total_tokens,cost_usd` 格式的 CSV（含表头；成本为未换算的 USD）。
+pub fn daily_usage_csv(rows: &[DailySourceUsage]) -> String {
+	let mut out = String::from("date,source,total_tokens,cost_usd\n");
+	for row in rows {
+		out.push_str(&format!(
+			"{},{},{},{:.6}\n",
+			row.date.format("%Y-%m-%d"),
+			row.source,
+			row.totals.total_tokens,
+			row.totals.cost_usd
+		));
+	}
+	out
+}
+
+/// 把 `[since, until]` 逐日的 cx/cc 用量写成 CSV 文件，返回数据行数（不含表头）。
+pub fn export_daily_usage_csv(
+	since: NaiveDate,
+	until: NaiveDate,
+	path: &Path,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Result<usize, String> {
+	if since > until {
+		return Err("起始日期不能晚于结束日期。".to_string());
+	}
+	let rows = daily_source_usage(
+		since,
+		until,
+		|range| load_cx_totals_with_pricing(range, dataset, scan),
+		|range| load_cc_totals_with_pricing(range, dataset, scan).ok(),
+	);
+	fs::write(path, daily_usage_csv(&rows))
+		.map_err(|e| format!("写入 {} 失败：{e}", path.display()))?;
+	Ok(rows.len())
+}
+
+/// 单个模型名的价格查找耗时（`tokbar-stats --bench pricing` 用）。
+#[derive(Debug, Clone, PartialEq)]
+pub struct PricingLookupTiming {
+	pub source: &'static str,
+	pub model: String,
+	pub found: bool,
+	/// 多次查找的平均耗时。
+	pub per_lookup: Duration,
+}
+
+fn time_lookup(
+	source: &'static str,
+	model: &str,
+	iterations: u32,
+	lookup: impl Fn(&str) -> bool,
+) -> PricingLookupTiming {
+	let iterations = iterations.max(1);
+	let found = lookup(model);
+	let started = Instant::now();
+	for _ in 0..iterations {
+		std::hint::black_box(lookup(std::hint::black_box(model)));
+	}
+	PricingLookupTiming {
+		source,
+		model: model.to_string(),
+		found,
+		per_lookup: started.elapsed() / iterations,
+	}
+}
+
+/// 按各来源计费时的匹配规则，逐个测量价格查找耗时（未命中时会走线性的子串回退）。
+pub fn time_pricing_lookups(
+	cx_models: &[&str],
+	cc_models: &[&str],
+	iterations: u32,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Vec<PricingLookupTiming> {
+	let cx_strict = scan.cx.strict_model_matching();
+	let cc_strict = scan.cc.strict_model_matching;
+	let cx = cx_models.iter().map(|model| {
+		time_lookup("cx", model, iterations, |m| codex::has_pricing(m, dataset, cx_strict))
+	});
+	let cc = cc_models.iter().map(|model| {
+		time_lookup("cc", model, iterations, |m| claude::has_pricing(m, dataset, cc_strict))
+	});
+	cx.chain(cc).collect()
+}
+
+/// 单价（美元/token）换算成“每百万 token”的展示文本；去掉多余的尾随 0。
+fn fmt_rate_per_million(rate: Option<f64>) -> String {
+	match rate {
+		Some(rate) => {
+			let text = format!("{:.6}", rate * 1_000_000.0);
+			let text = text.trim_end_matches('0').trim_end_matches('.');
+			format!("${text} / 1M tokens")
+		}
+		None => "-".to_string(),
+	}
+}
+
+fn describe_pricing(
+	source: &str,
+	model: &str,
+	resolved: Option<(String, LiteLLMModelPricing)>,
+) -> String {
+	let Some((key, pricing)) = resolved else {
+		return format!("{source} {model}: no pricing found");
+	};
+	let mut lines = vec![format!("{source} {model}: matched \"{key}\"")];
+	for (label, rate) in [
+		("input", pricing.input_cost_per_token),
+		("output", pricing.output_cost_per_token),
+		("cache read", pricing.cache_read_input_token_cost),
+		("cache creation", pricing.cache_creation_input_token_cost),
+	] {
+		lines.push(format!("  {label:<15}{}", fmt_rate_per_million(rate)));
+	}
+	if pricing.input_cost_per_token_above_200k_tokens.is_some()
+		|| pricing.output_cost_per_token_above_200k_tokens.is_some()
+	{
+		let threshold_k = pricing.tier_threshold() / 1000;
+		lines.push(format!(
+			"  {:<15}{}",
+			format!("input >{threshold_k}k"),
+			fmt_rate_per_million(pricing.input_cost_per_token_above_200k_tokens)
+		));
+		lines.push(format!(
+			"  {:<15}{}",
+			format!("output >{threshold_k}k"),
+			fmt_rate_per_million(pricing.output_cost_per_token_above_200k_tokens)
+		));
+	}
+	lines.join("\n")
+}
+
+/// 按 Codex 的匹配规则（前缀 + 别名）解析模型价格，输出命中的 key 与单价（`tokbar-stats --price`）。
+pub fn describe_cx_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> String {
+	let strict = scan.cx.strict_model_matching();
+	describe_pricing("cx", model, codex::resolve_pricing(model, dataset, strict))
+}
+
+/// 按 Claude 的匹配规则解析模型价格，输出命中的 key 与单价（`tokbar-stats --price`）。
+pub fn describe_cc_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> String {
+	let strict = scan.cc.strict_model_matching;
+	describe_pricing("cc", model, claude::resolve_pricing(model, dataset, strict))
+}
+
+/// 模型在价格表里命中的 key 与对应价格。
+#[derive(Debug, Clone, Serialize)]
+pub struct PricingMatch {
+	pub key: String,
+	pub pricing: LiteLLMModelPricing,
+}
+
+/// 同一个模型名分别按 Codex 与 Claude 的匹配规则查价的结果（`tokbar_lookup_pricing`）。
+#[derive(Debug, Clone, Serialize)]
+pub struct PricingLookup {
+	pub cx: Option<PricingMatch>,
+	pub cc: Option<PricingMatch>,
+}
+
+/// 按 Codex 与 Claude 两套前缀规则查价；两边都找不到时返回 None。
+pub fn lookup_pricing(
+	model: &str,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> Option<PricingLookup> {
+	let to_match = |(key, pricing)| PricingMatch { key, pricing };
+	let model = model.trim();
+	let cx = codex::resolve_pricing(model, dataset, scan.cx.strict_model_matching());
+	let cc = claude::resolve_pricing(model, dataset, scan.cc.strict_model_matching);
+	let (cx, cc) = (cx.map(to_match), cc.map(to_match));
+	if cx.is_none() && cc.is_none() {
+		return None;
+	}
+	Some(PricingLookup { cx, cc })
+}
+
+/// 单个日志文件里时间最晚、且带显式时区偏移的条目：`(毫秒时间戳, 偏移)`。
+fn latest_offset_in_file(path: &Path) -> Option<(i64, FixedOffset)> {
+	let lines = codex::session_file_lines(path)?;
+	let mut latest: Option<(i64, FixedOffset)> = None;
+	for line in lines {
+		if !line.contains("\"timestamp\"") {
+			continue;
+		}
+		let Ok(value) = serde_json::from_str::<Value>(&line) else {
+			continue;
+		};
+		let Some(timestamp) = value.get("timestamp").and_then(Value::as_str) else {
+			continue;
+		};
+		let Some(offset) = time_parse::explicit_utc_offset(timestamp) else {
+			continue;
+		};
+		let Some(parsed) = time_parse::parse_js_timestamp(timestamp) else {
+			continue;
+		};
+		if latest.is_none_or(|(millis, _)| parsed.millis >= millis) {
+			latest = Some((parsed.millis, offset));
+		}
+	}
+	latest
+}
+
+fn most_recently_modified(files: &[PathBuf]) -> Option<&PathBuf> {
+	files
+		.iter()
+		.max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
+
+/// 最近一条带显式时区的日志条目的 UTC 偏移：分别看 cx/cc 最近修改的日志文件，取更晚的那条。
+pub fn latest_entry_utc_offset(scan: &ScanSettings) -> Option<FixedOffset> {
+	let cx_files = codex::session_files_from_dirs(&codex::default_codex_session_dirs());
+	let cc_files = claude::default_claude_base_dirs(&scan.cc)
+		.map(|dirs| claude::usage_files_from_claude_base_dirs(&dirs, &scan.cc))
+		.unwrap_or_default();
+	[cx_files, cc_files]
+		.iter()
+		.filter_map(|files| most_recently_modified(files))
+		.filter_map(|path| latest_offset_in_file(path))
+		.max_by_key(|(millis, _)| *millis)
+		.map(|(_, offset)| offset)
+}
+
+/// 按设置决定“今天”跟随系统时区，还是跟随最近一条日志所在的时区（出差/VPN 导致系统时区变化时，
+/// 让“今天”保持在实际干活的那个时区）。找不到带时区的条目时回退到系统时区（或 `TOKBAR_TZ`）。
+pub fn apply_day_anchor_from_settings() {
+	let settings = app_settings::load_settings();
+	let offset = if settings.anchor_today_to_latest_entry {
+		latest_entry_utc_offset(&settings.scan_settings())
+	} else {
+		None
+	};
+	time_parse::set_day_offset(offset);
+}
+
+// 说明：只有不带基线的“全部”走增量缓存（按字节位置续读）；设置了 `all_time_since` 时
+// 仍按日期范围整份解析。
+
+/// “全部”统计的下界：设置了 `all_time_since` 时改走按日期过滤的路径（截至今天）。
+/// 基线晚于今天时返回 None（即没有可统计的数据）。
+fn all_time_baseline_range(baseline: NaiveDate) -> Option<DateRange> {
+	time_range::range_between(baseline, time_parse::today())
+}
+
+fn load_cx_all_time_from_files(
+	files: &[PathBuf],
+	baseline: Option<NaiveDate>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &codex::ScanOptions,
+	offset_cache_path: Option<&Path>,
+) -> UsageTotals {
+	let Some(baseline) = baseline else {
+		return codex::load_codex_totals_all_time_incremental(
+			files,
+			dataset,
+			options,
+			offset_cache_path,
+		);
+	};
+	match all_time_baseline_range(baseline) {
+		Some(range) => {
+			codex::load_codex_totals_from_files_with_pricing(files, &range, dataset, options)
+		}
+		None => UsageTotals::default(),
+	}
+}
+
+fn load_cc_all_time_from_files(
+	files: &[PathBuf],
+	baseline: Option<NaiveDate>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	options: &claude::ScanOptions,
+	offset_cache_path: Option<&Path>,
+) -> UsageTotals {
+	let Some(baseline) = baseline else {
+		return claude::load_claude_totals_all_time_incremental(
+			files,
+			dataset,
+			options,
+			offset_cache_path,
+		);
+	};
+	match all_time_baseline_range(baseline) {
+		Some(range) => {
+			claude::load_claude_totals_from_files_with_pricing(files, &range, dataset, options)
+		}
+		None => UsageTotals::default(),
+	}
+}
+
+/// “全部”统计的文件数安全上限：从 `dirs` 逐层往下找，只保留修改时间最新的 `cap` 个日志文件。
+///
+/// 说明：
+/// - 每层的子目录按修改时间从新到旧进入（Codex 的 `YYYY/MM/DD` 目录越新建得越晚），
+///   已经凑够 `cap` 个文件后不再进入更旧的目录，日志很多时不必列出全部文件；
+/// - 目录的修改时间只在新建文件时更新：旧目录里仍在追加的会话可能被略过，这是近似；
+/// - 不跟随符号链接的子目录；返回 `(新的在前的文件, 是否被截断)`。
+fn most_recent_files_in_dirs(
+	dirs: &[PathBuf],
+	cap: usize,
+	accept: &dyn Fn(&Path) -> bool,
+) -> (Vec<PathBuf>, bool) {
+	let mut seen = BTreeSet::new();
+	let mut found: Vec<(Option<SystemTime>, PathBuf)> = Vec::new();
+	let mut truncated = false;
+	for dir in dirs {
+		if seen.insert(fs::canonicalize(dir).unwrap_or_else(|_| dir.clone())) {
+			collect_recent_files(dir, cap, accept, &mut found, &mut truncated);
+		}
+	}
+	// 新的在前；读不到修改时间的排在最后。路径作为次序键，保证结果稳定。
+	found.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+	if found.len() > cap {
+		found.truncate(cap);
+		truncated = true;
+	}
+	(found.into_iter().map(|(_, path)| path).collect(), truncated)
+}
+
+fn collect_recent_files(
+	dir: &Path,
+	cap: usize,
+	accept: &dyn Fn(&Path) -> bool,
+	found: &mut Vec<(Option<SystemTime>, PathBuf)>,
+	truncated: &mut bool,
+) {
+	let Ok(entries) = fs::read_dir(dir) else {
+		return;
+	};
+	let mut subdirs: Vec<(Option<SystemTime>, PathBuf)> = Vec::new();
+	for entry in entries.flatten() {
+		let Ok(file_type) = entry.file_type() else {
+			continue;
+		};
+		let path = entry.path();
+		let modified = entry.metadata().and_then(|m| m.modified()).ok();
+		if file_type.is_dir() {
+			subdirs.push((modified, path));
+		} else if accept(&path) {
+			found.push((modified, path));
+		}
+	}
+	subdirs.sort_by(|a, b| b.cmp(a));
+	for (_, subdir) in subdirs {
+		if found.len() >= cap {
+			*truncated = true;
+			return;
+		}
+		collect_recent_files(&subdir, cap, accept, found, truncated);
+	}
+}
+
+/// 最近一次“全部”统计是否因 `max_files_scanned` 只统计了部分文件：`(cx, cc)`。
+pub fn all_time_partial() -> (bool, bool) {
+	let cx = [cx_all_time_cache(), cx_all_time_cache_with_cost()].into_iter().any(|cache| {
+		let guard = cache_lock::lock_or_reset(cache);
+		guard.computed_at.is_some() && guard.partial
+	});
+	let cc = [cc_all_time_cache(), cc_all_time_cache_with_cost()].into_iter().any(|cache| {
+		let guard = cache_lock::lock_or_reset(cache);
+		guard.computed_at.is_some() && guard.partial
+	});
+	(cx, cc)
+}
+
+/// 丢弃“全部”总计与日志文件列表的缓存，下一次读取时重新扫描（删除旧日志/新增项目目录后使用）。
+pub fn invalidate_all_time_caches() {
+	for cache in [cx_all_time_cache(), cx_all_time_cache_with_cost()] {
+		cache_lock::lock_or_reset(cache).computed_at = None;
+	}
+	for cache in [cc_all_time_cache(), cc_all_time_cache_with_cost()] {
+		cache_lock::lock_or_reset(cache).computed_at = None;
+	}
+	codex::invalidate_session_files_cache();
+	claude::invalidate_usage_files_cache();
+	offset_cache::clear_cache(codex::all_time_offset_cache_path().as_deref());
+	offset_cache::clear_cache(claude::all_time_offset_cache_path().as_deref());
+	for cache in [previous_month_cache(), this_month_cache()] {
+		cache_lock::lock_or_reset(cache).computed_at = None;
+	}
+}
+
+/// 某个自然月的 cx+cc 合计（菜单里的“本月 vs 上月”）。
+#[derive(Debug, Default)]
+struct CachedMonth {
+	computed_at: Option<Instant>,
+	/// 月份区间、扫描设置与价格表指纹的哈希：任何一项变化都要重算。
+	key: u64,
+	totals: UsageTotals,
+}
+
+static PREVIOUS_MONTH_CACHE: OnceLock<Mutex<CachedMonth>> = OnceLock::new();
+static THIS_MONTH_CACHE: OnceLock<Mutex<CachedMonth>> = OnceLock::new();
+
+fn previous_month_cache() -> &'static Mutex<CachedMonth> {
+	PREVIOUS_MONTH_CACHE.get_or_init(|| Mutex::new(CachedMonth::default()))
+}
+
+fn this_month_cache() -> &'static Mutex<CachedMonth> {
+	THIS_MONTH_CACHE.get_or_init(|| Mutex::new(CachedMonth::default()))
+}
+
+/// 上个月的 cx+cc 合计：上个月的记录不会再变，按月份与价格表缓存，不设过期时间。
+pub fn load_previous_month_totals_cached(
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> UsageTotals {
+	let range = time_range::range_previous_month();
+	month_totals_cached(previous_month_cache(), &range, None, dataset, scan)
+}
+
+/// 本月的 cx+cc 合计：与“全部”一样缓存 [`ALL_TIME_TTL`]，不必每次刷新都重扫整月的日志。
+pub fn load_this_month_totals_cached(
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> UsageTotals {
+	let range = time_range::range_month();
+	month_totals_cached(this_month_cache(), &range, Some(ALL_TIME_TTL), dataset, scan)
+}
+
+fn month_totals_cached(
+	cache: &Mutex<CachedMonth>,
+	range: &DateRange,
+	ttl: Option<Duration>,
+	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
+) -> UsageTotals {
+	let fingerprint = pricing::dataset_fingerprint(dataset);
+	let key = offset_cache::fnv1a64(format!("{range:?}\n{scan:?}\n{fingerprint:016x}").as_bytes());
+	{
+		let guard = cache_lock::lock_or_reset(cache);
+		if let Some(at) = guard.computed_at {
+			if guard.key == key && ttl.is_none_or(|ttl| at.elapsed() < ttl) {
+				return guard.totals;
+			}
+		}
+	}
+
+	let mut totals = load_cx_totals_with_pricing(range, dataset, scan);
+	if let Ok(cc) = load_cc_totals_with_pricing(range, dataset, scan) {
+		totals += cc;
+	}
+	let mut guard = cache_lock::lock_or_reset(cache);
+	guard.computed_at = Some(Instant::now());
+	guard.key = key;
+	guard.totals = totals;
+	totals
+}
+
 pub fn load_cx_totals_all_time_cached_with_pricing(
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
 ) -> UsageTotals {
 	let should_calculate_cost = !dataset.is_empty();
 	let cache = if should_calculate_cost {
@@ -93,30 +1090,52 @@ pub fn load_cx_totals_all_time_cached_with_pricing(
 		cx_all_time_cache()
 	};
 
+	let baseline = scan.all_time_since;
+	let max_files = scan.max_files_scanned;
+
 	{
-		let guard = cache.lock().expect("cx_all_time_cache lock poisoned");
+		let guard = cache_lock::lock_or_reset(cache);
 		if let Some(at) = guard.computed_at {
-			if Instant::now().duration_since(at) < ALL_TIME_TTL {
+			if guard.baseline == baseline
+				&& guard.max_files == max_files
+				&& Instant::now().duration_since(at) < ALL_TIME_TTL
+			{
 				return guard.totals;
 			}
 		}
 	}
 
 	let session_dirs = codex::default_codex_session_dirs();
-	let totals = if session_dirs.is_empty() {
-		UsageTotals::default()
+	let (totals, partial) = if session_dirs.is_empty() {
+		(UsageTotals::default(), false)
 	} else {
-		codex::load_codex_totals_from_session_dirs_all_time_with_pricing(&session_dirs, dataset)
+		let (files, partial) = match max_files {
+			Some(cap) => most_recent_files_in_dirs(&session_dirs, cap, &codex::is_session_file),
+			None => (codex::session_files_from_dirs(&session_dirs), false),
+		};
+		let cache_path = codex::all_time_offset_cache_path();
+		let totals = load_cx_all_time_from_files(
+			&files,
+			baseline,
+			dataset,
+			&scan.cx,
+			cache_path.as_deref(),
+		);
+		(totals, partial)
 	};
 
-	let mut guard = cache.lock().expect("cx_all_time_cache lock poisoned");
+	let mut guard = cache_lock::lock_or_reset(cache);
 	guard.computed_at = Some(Instant::now());
+	guard.baseline = baseline;
+	guard.max_files = max_files;
+	guard.partial = partial;
 	guard.totals = totals;
 	totals
 }
 
 pub fn load_cc_totals_all_time_cached_with_pricing(
 	dataset: &HashMap<String, LiteLLMModelPricing>,
+	scan: &ScanSettings,
 ) -> Result<UsageTotals, UsageError> {
 	let should_calculate_cost = !dataset.is_empty();
 	let cache = if should_calculate_cost {
@@ -125,21 +1144,563 @@ pub fn load_cc_totals_all_time_cached_with_pricing(
 		cc_all_time_cache()
 	};
 
+	let baseline = scan.all_time_since;
+	let max_files = scan.max_files_scanned;
+
 	{
-		let guard = cache.lock().expect("cc_all_time_cache lock poisoned");
+		let guard = cache_lock::lock_or_reset(cache);
 		if let (Some(at), Some(totals)) = (guard.computed_at, guard.totals) {
-			if Instant::now().duration_since(at) < ALL_TIME_TTL {
+			if guard.baseline == baseline
+				&& guard.max_files == max_files
+				&& guard.exclude_projects == scan.cc.exclude_projects
+				&& Instant::now().duration_since(at) < ALL_TIME_TTL
+			{
 				return Ok(totals);
 			}
 		}
 	}
 
-	let base_dirs = claude::default_claude_base_dirs()?;
+	let base_dirs = claude::default_claude_base_dirs(&scan.cc)?;
+	let (files, partial) = match max_files {
+		Some(cap) => {
+			let dirs = claude::claude_log_dirs(&base_dirs, &scan.cc);
+			most_recent_files_in_dirs(&dirs, cap, &|path| claude::is_usage_file(path, &scan.cc))
+		}
+		None => (claude::usage_files_from_claude_base_dirs(&base_dirs, &scan.cc), false),
+	};
+	let cache_path = claude::all_time_offset_cache_path();
 	let totals =
-		claude::load_claude_totals_from_base_dirs_all_time_with_pricing(&base_dirs, dataset);
+		load_cc_all_time_from_files(&files, baseline, dataset, &scan.cc, cache_path.as_deref());
 
-	let mut guard = cache.lock().expect("cc_all_time_cache lock poisoned");
+	let mut guard = cache_lock::lock_or_reset(cache);
 	guard.computed_at = Some(Instant::now());
+	guard.baseline = baseline;
+	guard.max_files = max_files;
+	guard.exclude_projects = scan.cc.exclude_projects.clone();
+	guard.partial = partial;
 	guard.totals = Some(totals);
 	Ok(totals)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util;
+	use chrono::Local;
+
+	#[test]
+	fn sums_single_model_across_codex_and_claude() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let day = test_util::local_noon_20260206();
+
+		let codex_file = tmp.path().join("codex.jsonl");
+		let codex_lines = [
+			serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5-codex" } }),
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": day,
+				"payload": {
+					"type": "token_count",
+					"info": { "last_token_usage": { "input_tokens": 100, "output_tokens": 50, "total_tokens": 150 } }
+				}
+			}),
+		];
+		std::fs::write(
+			&codex_file,
+			codex_lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write codex");
+
+		let claude_file = tmp.path().join("claude.jsonl");
+		let claude_lines = [
+			serde_json::json!({
+				"timestamp": day,
+				"requestId": "r1",
+				"message": { "id": "m1", "model": "openai/gpt-5", "usage": { "input_tokens": 10, "output_tokens": 5 } }
+			}),
+			serde_json::json!({
+				"timestamp": day,
+				"requestId": "r2",
+				"message": { "id": "m2", "model": "claude-sonnet-4", "usage": { "input_tokens": 1000, "output_tokens": 1000 } }
+			}),
+		];
+		std::fs::write(
+			&claude_file,
+			claude_lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write claude");
+
+		let range = test_util::day_range("20260206");
+		let dataset = HashMap::new();
+		let scan = ScanSettings::default();
+		let cx = codex::load_codex_breakdown_from_files_with_pricing(
+			&[codex_file],
+			&range,
+			&dataset,
+			&scan.cx,
+		);
+		let cc = claude::load_claude_breakdown_from_files_with_pricing(
+			&[claude_file],
+			&range,
+			&dataset,
+			&scan.cc,
+		);
+
+		let totals = sum_model_in_breakdowns(&[&cx, &cc], "gpt-5");
+		assert_eq!(totals.total_tokens, 150 + 15);
+	}
+
+	#[test]
+	fn coverage_partitions_matched_and_unmatched_models() {
+		let mut dataset = HashMap::new();
+		dataset.insert("gpt-5".to_string(), LiteLLMModelPricing::default());
+		dataset.insert(
+			"anthropic/claude-sonnet-4-20250514".to_string(),
+			LiteLLMModelPricing::default(),
+		);
+
+		let cx: HashMap<String, UsageTotals> = ["gpt-5-codex", "o9-preview"]
+			.into_iter()
+			.map(|m| (m.to_string(), UsageTotals::default()))
+			.collect();
+		let cc: HashMap<String, UsageTotals> =
+			["claude-sonnet-4-20250514", claude::UNKNOWN_MODEL_KEY, "glm-4.6"]
+				.into_iter()
+				.map(|m| (m.to_string(), UsageTotals::default()))
+				.collect();
+
+		let coverage = pricing_coverage(&cx, &cc, &dataset, &ScanSettings::default());
+		assert_eq!(
+			coverage.matched,
+			vec!["claude-sonnet-4-20250514".to_string(), "gpt-5-codex".to_string()]
+		);
+		assert_eq!(
+			coverage.unmatched,
+			vec![
+				"glm-4.6".to_string(),
+				"o9-preview".to_string(),
+				claude::UNKNOWN_MODEL_KEY.to_string()
+			]
+		);
+	}
+
+	#[test]
+	fn source_reports_match_the_totals_loaders() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let day = test_util::local_noon_20260206();
+		let write_lines = |name: &str, lines: &[serde_json::Value]| {
+			let path = tmp.path().join(name);
+			let body = lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n");
+			std::fs::write(&path, body).expect("write fixture");
+			path
+		};
+
+		let codex_file = write_lines(
+			"codex.jsonl",
+			&[
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "gpt-5" } }),
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": { "last_token_usage": { "input_tokens": 1000, "output_tokens": 200, "total_tokens": 1200 } }
+					}
+				}),
+				serde_json::json!({ "type": "turn_context", "payload": { "model": "o9-preview" } }),
+				serde_json::json!({
+					"type": "event_msg",
+					"timestamp": day,
+					"payload": {
+						"type": "token_count",
+						"info": { "last_token_usage": { "input_tokens": 10, "output_tokens": 5, "total_tokens": 15 } }
+					}
+				}),
+			],
+		);
+		let claude_file = write_lines(
+			"claude.jsonl",
+			&[serde_json::json!({
+				"timestamp": day,
+				"requestId": "r1",
+				"message": { "id": "m1", "model": "glm-4.6", "usage": { "input_tokens": 10, "output_tokens": 5 } }
+			})],
+		);
+
+		let range = test_util::day_range("20260206");
+		let mut dataset = HashMap::new();
+		dataset.insert(
+			"gpt-5".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(1.25e-6),
+				output_cost_per_token: Some(1e-5),
+				..LiteLLMModelPricing::default()
+			},
+		);
+		let files = [codex_file];
+		let scan = ScanSettings::default();
+		let cx = cx_source_report(
+			codex::load_codex_breakdown_from_files_with_pricing(&files, &range, &dataset, &scan.cx),
+			&dataset,
+			&scan,
+		);
+		let cx_totals =
+			codex::load_codex_totals_from_files_with_pricing(&files, &range, &dataset, &scan.cx);
+		assert_eq!(cx.totals.total_tokens, cx_totals.total_tokens);
+		assert_eq!(cx.totals.total_tokens, 1215);
+		assert!(cx.totals.cost_usd > 0.0);
+		assert!((cx.totals.cost_usd - cx_totals.cost_usd).abs() < 1e-12);
+		assert_eq!(cx.breakdown.keys().collect::<Vec<_>>(), vec!["gpt-5", "o9-preview"]);
+		assert_eq!(cx.unpriced_models, vec!["o9-preview".to_string()]);
+
+		let files = [claude_file];
+		let cc_breakdown = claude::load_claude_breakdown_from_files_with_pricing(
+			&files, &range, &dataset, &scan.cc,
+		);
+		let cc = cc_source_report(cc_breakdown, &dataset, &scan);
+		let cc_totals =
+			claude::load_claude_totals_from_files_with_pricing(&files, &range, &dataset, &scan.cc);
+		assert_eq!(cc.totals.total_tokens, cc_totals.total_tokens);
+		assert_eq!(cc.unpriced_models, vec!["glm-4.6".to_string()]);
+	}
+
+	#[test]
+	fn csv_export_has_one_row_per_day_and_source() {
+		let date = |d: u32| NaiveDate::from_ymd_opt(2026, 2, d).expect("date");
+		let rows = daily_source_usage(
+			date(27),
+			NaiveDate::from_ymd_opt(2026, 3, 1).expect("date"),
+			|range| UsageTotals {
+				total_tokens: range.since_yyyymmdd[6..].parse().expect("day"),
+				cost_usd: 0.5,
+				..UsageTotals::default()
+			},
+			|range| (range.since_yyyymmdd == "20260228").then(UsageTotals::default),
+		);
+
+		assert_eq!(
+			daily_usage_csv(&rows),
+			"date,source,total_tokens,cost_usd\n\
+			 2026-02-27,cx,27,0.500000\n\
+			 2026-02-28,cx,28,0.500000\n\
+			 2026-02-28,cc,0,0.000000\n\
+			 2026-03-01,cx,1,0.500000\n"
+		);
+	}
+
+	#[test]
+	fn all_time_baseline_excludes_older_entries() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let today = chrono::Local::now().date_naive();
+		let at = |days_ago: i64| {
+			(today - chrono::Duration::days(days_ago))
+				.and_hms_opt(12, 0, 0)
+				.and_then(|dt| dt.and_local_timezone(chrono::Local).single())
+				.expect("local dt")
+				.to_rfc3339()
+		};
+
+		let codex_file = tmp.path().join("codex.jsonl");
+		let codex_lines = [3, 1].map(|days_ago| {
+			serde_json::json!({
+				"type": "event_msg",
+				"timestamp": at(days_ago),
+				"payload": {
+					"type": "token_count",
+					"info": {
+						"last_token_usage": { "input_tokens": 100 * days_ago, "output_tokens": 0, "total_tokens": 100 * days_ago }
+					}
+				}
+			})
+		});
+		std::fs::write(
+			&codex_file,
+			codex_lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write codex");
+
+		let claude_file = tmp.path().join("claude.jsonl");
+		let claude_lines = [3, 1].map(|days_ago| {
+			serde_json::json!({
+				"timestamp": at(days_ago),
+				"message": { "usage": { "input_tokens": 10 * days_ago, "output_tokens": 0 } }
+			})
+		});
+		std::fs::write(
+			&claude_file,
+			claude_lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write claude");
+
+		let dataset = HashMap::new();
+		let baseline = Some(today - chrono::Duration::days(2));
+		let cx_files = [codex_file];
+		let cc_files = [claude_file];
+
+		let scan = ScanSettings::default();
+		let cx =
+			|baseline| load_cx_all_time_from_files(&cx_files, baseline, &dataset, &scan.cx, None);
+		let cc =
+			|baseline| load_cc_all_time_from_files(&cc_files, baseline, &dataset, &scan.cc, None);
+		assert_eq!(cx(None).total_tokens, 400);
+		assert_eq!(cx(baseline).total_tokens, 100);
+		assert_eq!(cc(None).total_tokens, 40);
+		assert_eq!(cc(baseline).total_tokens, 10);
+
+		// 基线在未来：没有可统计的数据。
+		let future = Some(today + chrono::Duration::days(1));
+		assert_eq!(cx(future).total_tokens, 0);
+	}
+
+	#[test]
+	fn pricing_lookup_timings_report_hits_and_misses_in_order() {
+		let mut dataset = HashMap::new();
+		dataset.insert("gpt-5".to_string(), LiteLLMModelPricing::default());
+		dataset.insert(
+			"anthropic/claude-sonnet-4-20250514".to_string(),
+			LiteLLMModelPricing::default(),
+		);
+
+		let timings = time_pricing_lookups(
+			&["gpt-5-codex", "o9-preview"],
+			&["claude-sonnet-4-20250514", "glm-4.6"],
+			3,
+			&dataset,
+			&ScanSettings::default(),
+		);
+		let summary: Vec<(&str, &str, bool)> = timings
+			.iter()
+			.map(|t| (t.source, t.model.as_str(), t.found))
+			.collect();
+		assert_eq!(
+			summary,
+			vec![
+				("cx", "gpt-5-codex", true),
+				("cx", "o9-preview", false),
+				("cc", "claude-sonnet-4-20250514", true),
+				("cc", "glm-4.6", false),
+			]
+		);
+	}
+
+	#[test]
+	fn describe_pricing_reports_rates_and_matched_key() {
+		let mut dataset = HashMap::new();
+		let scan = ScanSettings::default();
+		dataset.insert(
+			"gpt-5".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(1.25e-6),
+				output_cost_per_token: Some(1e-5),
+				..Default::default()
+			},
+		);
+		dataset.insert(
+			"anthropic/claude-sonnet-4-20250514".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(3e-6),
+				output_cost_per_token: Some(1.5e-5),
+				cache_read_input_token_cost: Some(3e-7),
+				..Default::default()
+			},
+		);
+
+		let cx = describe_cx_pricing("gpt-5", &dataset, &scan);
+		assert!(cx.starts_with("cx gpt-5: matched \"gpt-5\""), "{cx}");
+		assert!(cx.contains("input          $1.25 / 1M tokens"), "{cx}");
+		assert!(cx.contains("output         $10 / 1M tokens"), "{cx}");
+		assert!(cx.contains("cache creation -"), "{cx}");
+
+		let cc = describe_cc_pricing("claude-sonnet-4-20250514", &dataset, &scan);
+		assert!(
+			cc.starts_with("cc claude-sonnet-4-20250514: matched \"anthropic/claude-sonnet-4-20250514\""),
+			"{cc}"
+		);
+		assert!(cc.contains("input          $3 / 1M tokens"), "{cc}");
+		assert!(cc.contains("output         $15 / 1M tokens"), "{cc}");
+		assert!(cc.contains("cache read     $0.3 / 1M tokens"), "{cc}");
+
+		assert_eq!(
+			describe_cc_pricing("unknown", &dataset, &scan),
+			"cc unknown: no pricing found"
+		);
+	}
+
+	#[test]
+	fn lookup_pricing_uses_both_prefix_sets() {
+		let mut dataset = HashMap::new();
+		let scan = ScanSettings::default();
+		dataset.insert("openai/gpt-5".to_string(), LiteLLMModelPricing::default());
+		dataset.insert(
+			"anthropic/claude-sonnet-4-20250514".to_string(),
+			LiteLLMModelPricing {
+				input_cost_per_token: Some(3e-6),
+				..Default::default()
+			},
+		);
+
+		let found =
+			lookup_pricing(" claude-sonnet-4-20250514 ", &dataset, &scan).expect("found");
+		let cc = found.cc.expect("cc match");
+		assert_eq!(cc.key, "anthropic/claude-sonnet-4-20250514");
+		assert_eq!(cc.pricing.input_cost_per_token, Some(3e-6));
+
+		let found = lookup_pricing("gpt-5", &dataset, &scan).expect("found");
+		assert_eq!(found.cx.map(|m| m.key).as_deref(), Some("openai/gpt-5"));
+
+		assert!(lookup_pricing("unknown", &dataset, &scan).is_none());
+		let value =
+			serde_json::to_value(lookup_pricing("gpt-5", &dataset, &scan)).expect("serialize");
+		assert!(value["cx"]["pricing"]["input_cost_per_token"].is_null());
+	}
+
+	#[test]
+	fn file_cap_walks_newest_dirs_first_and_flags_partial() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let day = Local::now().to_rfc3339();
+		let line = serde_json::json!({
+			"type": "event_msg",
+			"timestamp": day,
+			"payload": {
+				"type": "token_count",
+				"info": { "last_token_usage": { "input_tokens": 100, "output_tokens": 0, "total_tokens": 100 } }
+			}
+		})
+		.to_string();
+
+		// Codex 式的按日期分层目录：越新的会话在越新的目录里。
+		let base = SystemTime::now() - Duration::from_secs(3600);
+		let mut files = Vec::new();
+		for i in 0..6u64 {
+			let dir = tmp.path().join("2026").join("02").join(format!("{:02}", i + 1));
+			fs::create_dir_all(&dir).expect("mkdir");
+			let path = dir.join(format!("session-{i}.jsonl"));
+			std::fs::write(&path, &line).expect("write");
+			let file = fs::File::options().write(true).open(&path).expect("open");
+			file.set_modified(base + Duration::from_secs(i * 60)).expect("set mtime");
+			files.push(path);
+		}
+		std::fs::write(tmp.path().join("notes.txt"), "skip").expect("write");
+		let dirs = [tmp.path().to_path_buf()];
+		let accept = |path: &Path| path.extension().is_some_and(|ext| ext == "jsonl");
+
+		let (picked, partial) = most_recent_files_in_dirs(&dirs, 2, &accept);
+		assert!(partial);
+		assert_eq!(picked, vec![files[5].clone(), files[4].clone()]);
+		let dataset = HashMap::new();
+		let scan = ScanSettings::default();
+		let picked_totals = load_cx_all_time_from_files(&picked, None, &dataset, &scan.cx, None);
+		assert_eq!(picked_totals.total_tokens, 200);
+
+		let (all, partial) = most_recent_files_in_dirs(&dirs, 6, &accept);
+		assert!(!partial);
+		assert_eq!(all.len(), 6);
+	}
+
+	#[test]
+	fn invalidation_clears_all_time_caches() {
+		for cache in [cx_all_time_cache(), cx_all_time_cache_with_cost()] {
+			cache.lock().expect("cache lock").computed_at = Some(Instant::now());
+		}
+		for cache in [cc_all_time_cache(), cc_all_time_cache_with_cost()] {
+			cache.lock().expect("cache lock").computed_at = Some(Instant::now());
+		}
+
+		invalidate_all_time_caches();
+
+		for cache in [cx_all_time_cache(), cx_all_time_cache_with_cost()] {
+			assert!(cache.lock().expect("cache lock").computed_at.is_none());
+		}
+		for cache in [cc_all_time_cache(), cc_all_time_cache_with_cost()] {
+			assert!(cache.lock().expect("cache lock").computed_at.is_none());
+		}
+		for cache in [previous_month_cache(), this_month_cache()] {
+			assert!(cache.lock().expect("cache lock").computed_at.is_none());
+		}
+	}
+
+	#[test]
+	fn usage_log_filter_follows_the_codex_and_claude_scan_rules() {
+		let cc = claude::ScanOptions {
+			exclude_projects: vec!["secret".to_string()],
+			..Default::default()
+		};
+		let is_usage_log = usage_log_filter_for(vec![PathBuf::from("/c/sessions")], cc);
+		assert!(is_usage_log(Path::new("/c/sessions/2026/02/06/rollout.jsonl")));
+		assert!(is_usage_log(Path::new("/c/sessions/rollout.jsonl.gz")));
+		assert!(is_usage_log(Path::new("/h/.claude/projects/app/s.jsonl")));
+		assert!(!is_usage_log(Path::new("/h/.claude/projects/secret/s.jsonl")));
+		assert!(!is_usage_log(Path::new("/h/.claude/projects/app/s.jsonl.gz")));
+		assert!(!is_usage_log(Path::new("/h/.claude/settings.json")));
+	}
+
+	#[test]
+	fn month_totals_are_reused_until_the_inputs_change() {
+		let cache = Mutex::new(CachedMonth::default());
+		let range = time_range::range_previous_month();
+		let dataset = HashMap::new();
+		let scan = ScanSettings::default();
+		month_totals_cached(&cache, &range, None, &dataset, &scan);
+
+		// 命中缓存时直接返回存下的值，不再扫描。
+		let marker = UsageTotals {
+			total_tokens: 42,
+			..Default::default()
+		};
+		cache.lock().expect("cache lock").totals = marker;
+		assert_eq!(month_totals_cached(&cache, &range, None, &dataset, &scan).total_tokens, 42);
+
+		// 扫描设置变了就重算。
+		let mut excluding = scan.clone();
+		excluding.cc.exclude_projects = vec!["p1".to_string()];
+		let recomputed = month_totals_cached(&cache, &range, None, &dataset, &excluding);
+		assert_ne!(recomputed.total_tokens, 42);
+		// 过期后也重算。
+		cache.lock().expect("cache lock").totals = marker;
+		let expired = Some(Duration::ZERO);
+		assert_ne!(month_totals_cached(&cache, &range, expired, &dataset, &scan).total_tokens, 42);
+
+		// 上次计算中途 panic（锁中毒）时不跟着 panic，丢掉缓存重新统计。
+		cache.lock().expect("cache lock").totals = marker;
+		let _ = std::panic::catch_unwind(|| {
+			let _guard = cache.lock().expect("cache lock");
+			panic!("refresh panicked while holding the month cache");
+		});
+		assert_ne!(month_totals_cached(&cache, &range, None, &dataset, &scan).total_tokens, 42);
+	}
+
+	#[test]
+	fn latest_entry_offset_follows_the_newest_zoned_timestamp() {
+		let tmp = tempfile::tempdir().expect("tempdir");
+		let file_path = tmp.path().join("session.jsonl");
+		let lines = [
+			serde_json::json!({ "timestamp": "2026-02-06T10:00:00+09:00" }),
+			serde_json::json!({ "timestamp": "2026-02-06T09:00:00-05:00" }),
+			serde_json::json!({ "type": "no-timestamp" }),
+			// 不带时区：无从判断，跳过。
+			serde_json::json!({ "timestamp": "2026-02-07T08:00:00" }),
+		];
+		std::fs::write(
+			&file_path,
+			lines.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n"),
+		)
+		.expect("write");
+
+		let (_, offset) = latest_offset_in_file(&file_path).expect("zoned entry");
+		assert_eq!(offset, FixedOffset::west_opt(5 * 3600).unwrap());
+
+		// Codex/Claude 实际落盘的是带毫秒的 `Z`：就是 UTC。
+		let mut lines = lines.map(|v| v.to_string()).to_vec();
+		lines.push(
+			serde_json::json!({
+				"timestamp": "2026-02-06T23:00:00.123Z",
+				"type": "event_msg",
+				"payload": { "type": "token_count" }
+			})
+			.to_string(),
+		);
+		std::fs::write(&file_path, lines.join("\n")).expect("write");
+		let (_, offset) = latest_offset_in_file(&file_path).expect("zoned entry");
+		assert_eq!(offset, FixedOffset::east_opt(0).unwrap());
+	}
+}
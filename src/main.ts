@@ -5,6 +5,13 @@ type ProxyConfig = {
   http?: string | null;
   https?: string | null;
   socks5?: string | null;
+  username?: string | null;
+  password?: string | null;
+};
+
+type ProxyTestResult = {
+  ok: boolean;
+  error?: string | null;
 };
 
 type ProxySaveResult = {
@@ -125,6 +132,83 @@ async function renderRightcodesLogin(root: HTMLElement) {
   });
 }
 
+async function renderCostAlert(root: HTMLElement) {
+  root.innerHTML = "";
+
+  const wrap = document.createElement("div");
+  wrap.className = "tokbar-wrap";
+
+  const title = document.createElement("div");
+  title.className = "tokbar-title";
+  title.textContent = "每日花费提醒";
+
+  const desc = document.createElement("div");
+  desc.className = "tokbar-desc";
+  desc.textContent =
+    "今天 cx+cc 的花费首次达到该金额时发送系统通知（每天最多一次）。需要能获取模型价格。";
+
+  const amount = inputRowWithType("阈值（USD）", "cost-alert-usd", "例如 20", "number");
+  amount.input.min = "0";
+  amount.input.step = "0.01";
+
+  const buttonRow = document.createElement("div");
+  buttonRow.className = "tokbar-actions";
+
+  const status = document.createElement("div");
+  status.className = "tokbar-status";
+
+  const save = document.createElement("button");
+  save.className = "tokbar-button";
+  save.textContent = "保存";
+
+  const clear = document.createElement("button");
+  clear.className = "tokbar-button tokbar-button-secondary";
+  clear.textContent = "关闭提醒";
+
+  buttonRow.append(save, clear);
+  wrap.append(title, desc, amount.row, buttonRow, status);
+  root.append(wrap);
+
+  const existing = (await invoke("tokbar_get_cost_alert")) as number | null;
+  amount.input.value = existing == null ? "" : String(existing);
+
+  function setStatus(text: string, kind: "ok" | "err" | "info") {
+    status.textContent = text;
+    status.dataset.kind = kind;
+  }
+
+  async function submit(threshold: number | null) {
+    save.disabled = true;
+    clear.disabled = true;
+    try {
+      await invoke("tokbar_set_cost_alert", { threshold });
+      setStatus(
+        threshold == null ? "已关闭每日花费提醒。" : `已设置：今天花费达到 $${threshold} 时提醒。`,
+        "ok",
+      );
+    } catch (e) {
+      setStatus(`保存失败：${String(e)}`, "err");
+    } finally {
+      save.disabled = false;
+      clear.disabled = false;
+    }
+  }
+
+  save.addEventListener("click", () => {
+    const text = amount.input.value.trim();
+    const value = Number(text);
+    if (!text.length || !Number.isFinite(value) || value <= 0) {
+      setStatus("请输入大于 0 的金额。", "err");
+      return;
+    }
+    void submit(value);
+  });
+  clear.addEventListener("click", () => {
+    amount.input.value = "";
+    void submit(null);
+  });
+}
+
 async function renderProxySettings(root: HTMLElement) {
   root.innerHTML = "";
 
@@ -144,6 +228,9 @@ async function renderProxySettings(root: HTMLElement) {
   const http = inputRow("HTTP", "proxy-http", "127.0.0.1:7897");
   const https = inputRow("HTTPS", "proxy-https", "127.0.0.1:7897");
   const socks5 = inputRow("SOCKS5", "proxy-socks5", "127.0.0.1:7897");
+  // 代理地址里已内嵌 user:pass@ 时以内嵌的为准，这里的账号密码不会再合并进去。
+  const proxyUser = inputRow("用户名", "proxy-username", "可选");
+  const proxyPass = inputRowWithType("密码", "proxy-password", "可选", "password");
 
   const buttonRow = document.createElement("div");
   buttonRow.className = "tokbar-actions";
@@ -159,7 +246,15 @@ async function renderProxySettings(root: HTMLElement) {
   clear.className = "tokbar-button tokbar-button-secondary";
   clear.textContent = "清空";
 
-  buttonRow.append(save, clear);
+  const copyEnv = document.createElement("button");
+  copyEnv.className = "tokbar-button tokbar-button-secondary";
+  copyEnv.textContent = "复制为环境变量";
+
+  const test = document.createElement("button");
+  test.className = "tokbar-button tokbar-button-secondary";
+  test.textContent = "测试代理";
+
+  buttonRow.append(save, test, clear, copyEnv);
 
   wrap.append(
     title,
@@ -168,6 +263,8 @@ async function renderProxySettings(root: HTMLElement) {
     http.row,
     https.row,
     socks5.row,
+    proxyUser.row,
+    proxyPass.row,
     buttonRow,
     status,
   );
@@ -178,6 +275,8 @@ async function renderProxySettings(root: HTMLElement) {
   http.input.value = existing.http ?? "";
   https.input.value = existing.https ?? "";
   socks5.input.value = existing.socks5 ?? "";
+  proxyUser.input.value = existing.username ?? "";
+  proxyPass.input.value = existing.password ?? "";
 
   function setStatus(text: string, kind: "ok" | "err" | "info") {
     status.textContent = text;
@@ -189,27 +288,70 @@ async function renderProxySettings(root: HTMLElement) {
     http.input.value = "";
     https.input.value = "";
     socks5.input.value = "";
+    proxyUser.input.value = "";
+    proxyPass.input.value = "";
     setStatus("已清空（还未保存）", "info");
   });
 
+  function currentConfig(): ProxyConfig {
+    return {
+      aggregated: normalizeOptionalText(aggregated.input.value),
+      http: normalizeOptionalText(http.input.value),
+      https: normalizeOptionalText(https.input.value),
+      socks5: normalizeOptionalText(socks5.input.value),
+      username: normalizeOptionalText(proxyUser.input.value),
+      // 密码不做 trim，与后端保持一致：只有空串视为未设置。
+      password: proxyPass.input.value.length ? proxyPass.input.value : null,
+    };
+  }
+
+  test.addEventListener("click", async () => {
+    test.disabled = true;
+    try {
+      setStatus("测试中…", "info");
+      // 只测试可达性：不保存配置，也不影响托盘当前的价格状态。
+      const result = (await invoke("tokbar_test_proxy", {
+        config: currentConfig(),
+      })) as ProxyTestResult;
+      if (result.ok) {
+        setStatus("代理可访问价格地址（尚未保存）。", "ok");
+      } else {
+        const reason = (result.error ?? "").trim();
+        setStatus(reason.length ? `无法访问价格地址：${reason}` : "无法访问价格地址。", "err");
+      }
+    } catch (e) {
+      setStatus(`测试失败：${String(e)}`, "err");
+    } finally {
+      test.disabled = false;
+    }
+  });
+
+  copyEnv.addEventListener("click", async () => {
+    try {
+      const text = (await invoke("tokbar_proxy_env_exports", {
+        config: currentConfig(),
+      })) as string;
+      await navigator.clipboard.writeText(text);
+      // 代理地址中的账号密码会原样复制，这里明确提醒用户。
+      setStatus("已复制 export 语句（如包含账号密码也会一并复制，请勿分享）。", "ok");
+    } catch (e) {
+      setStatus(`复制失败：${String(e)}`, "err");
+    }
+  });
+
   save.addEventListener("click", async () => {
     save.disabled = true;
     clear.disabled = true;
     try {
       setStatus("保存中…", "info");
-      const cfg: ProxyConfig = {
-        aggregated: normalizeOptionalText(aggregated.input.value),
-        http: normalizeOptionalText(http.input.value),
-        https: normalizeOptionalText(https.input.value),
-        socks5: normalizeOptionalText(socks5.input.value),
-      };
+      const cfg = currentConfig();
 
       const result = (await invoke("tokbar_set_proxy_config", {
         config: cfg,
       })) as ProxySaveResult;
 
       if (result.available) {
-        setStatus("已连接：模型价格获取成功。", "ok");
+        setStatus("代理可用，价格已更新。", "ok");
       } else {
         const reason = (result.last_error ?? "").trim();
         setStatus(
@@ -239,6 +381,8 @@ window.addEventListener("DOMContentLoaded", async () => {
     await renderProxySettings(root);
   } else if (view === "rightcodes_login") {
     await renderRightcodesLogin(root);
+  } else if (view === "cost_alert") {
+    await renderCostAlert(root);
   } else {
     renderEmpty(root);
   }